@@ -0,0 +1,99 @@
+//! Compare evaluating conditions by walking their tree with compiling them first.
+//!
+//! Run with `cargo bench --bench condition_eval`. Every condition is evaluated at each
+//! of the time indices of a generated waveform, without skipping samples where its
+//! signals do not change.
+
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+use waveform_mcp::{count_matching_samples, Evaluator};
+
+const NUM_TIME_STEPS: u64 = 50_000;
+const RUNS: u32 = 3;
+
+const CONDITIONS: &[&str] = &[
+    "top.valid && top.ready",
+    "top.data == 8'hff && top.valid",
+    "top.count + top.data > 16'd300",
+    "top.data[3:0] == top.count[7:4] || $rose(top.valid)",
+    "$signed(top.count) < 0 && $past(top.ready, 2)",
+    "top.valid ? top.data : top.count[7:0]",
+    "{top.data, top.count} != 0 && $countones(top.data) == 4",
+    "top.wide[99:40] == 0 || top.wide > top.count",
+];
+
+/// A waveform where every signal changes at every time step.
+fn generate_vcd() -> String {
+    let mut vcd = String::from(
+        "$timescale 1ns $end\n\
+         $scope module top $end\n\
+         $var wire 1 ! valid $end\n\
+         $var wire 1 \" ready $end\n\
+         $var wire 8 # data $end\n\
+         $var wire 16 $ count $end\n\
+         $var wire 100 % wide $end\n\
+         $upscope $end\n\
+         $enddefinitions $end\n",
+    );
+    // A simple linear congruential generator keeps the waveform the same in every run
+    let mut state: u64 = 1;
+    for time in 0..NUM_TIME_STEPS {
+        state = state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        let bits = state >> 16;
+        writeln!(vcd, "#{}", time * 10).unwrap();
+        writeln!(vcd, "{}!", bits & 1).unwrap();
+        writeln!(vcd, "{}\"", bits >> 1 & 1).unwrap();
+        writeln!(vcd, "b{:b} #", bits >> 2 & 0xff).unwrap();
+        writeln!(vcd, "b{:b} $", bits >> 10 & 0xffff).unwrap();
+        writeln!(vcd, "b{:b}{:064b} %", bits >> 26 & 0xfff, state).unwrap();
+    }
+    vcd
+}
+
+/// The fastest of several runs of an evaluator, and the number of matching samples.
+fn time_evaluator(
+    waveform: &mut wellen::simple::Waveform,
+    condition: &str,
+    evaluator: Evaluator,
+) -> (Duration, usize) {
+    let mut best = Duration::MAX;
+    let mut matches = 0;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        matches = count_matching_samples(waveform, condition, evaluator)
+            .unwrap_or_else(|e| panic!("Failed to evaluate {}: {}", condition, e));
+        best = best.min(start.elapsed());
+    }
+    (best, matches)
+}
+
+fn main() {
+    let temp_file = tempfile::NamedTempFile::new().expect("Failed to create temp file");
+    std::fs::write(temp_file.path(), generate_vcd()).expect("Failed to write VCD file");
+    let mut waveform = wellen::simple::read(temp_file.path()).expect("Failed to read VCD file");
+
+    println!(
+        "{:<60} {:>10} {:>10} {:>8}",
+        "condition", "tree", "compiled", "speedup"
+    );
+    for condition in CONDITIONS {
+        let (tree, tree_matches) = time_evaluator(&mut waveform, condition, Evaluator::Tree);
+        let (compiled, compiled_matches) =
+            time_evaluator(&mut waveform, condition, Evaluator::Compiled);
+        assert_eq!(
+            tree_matches, compiled_matches,
+            "Evaluators disagree on {}",
+            condition
+        );
+        println!(
+            "{:<60} {:>8.1}ms {:>8.1}ms {:>7.1}x",
+            condition,
+            tree.as_secs_f64() * 1000.0,
+            compiled.as_secs_f64() * 1000.0,
+            tree.as_secs_f64() / compiled.as_secs_f64()
+        );
+    }
+}
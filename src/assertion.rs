@@ -0,0 +1,156 @@
+//! Checking properties against a waveform, assertion style.
+
+use super::{
+    condition::load_condition_signals, condition::resolve_clock, condition::resolve_signal_scopes,
+    condition::ConditionOptions, formatting::format_time, sequence::check_attempt,
+    sequence::parse_property, sequence::CompiledProperty,
+};
+
+/// A failing attempt of a property, with the values of its signals.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Counterexample {
+    /// Time index the failing attempt started at
+    pub start_idx: usize,
+    /// Time index the failure was detected at
+    pub end_idx: usize,
+    /// Formatted signal values at every cycle from start to end
+    pub trace: Vec<String>,
+}
+
+/// Outcome of checking one property.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PropertyReport {
+    pub property: String,
+    /// Number of attempts that passed
+    pub passed: usize,
+    /// Number of attempts that failed
+    pub failed: usize,
+    /// The first failing attempts, up to the requested number
+    pub counterexamples: Vec<Counterexample>,
+}
+
+impl std::fmt::Display for PropertyReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let status = if self.failed == 0 { "PASS" } else { "FAIL" };
+        write!(
+            f,
+            "{} '{}': {} passed, {} failed",
+            status, self.property, self.passed, self.failed
+        )?;
+        for counterexample in &self.counterexamples {
+            write!(
+                f,
+                "\n  Failure from time index {} to time index {}:",
+                counterexample.start_idx, counterexample.end_idx
+            )?;
+            for line in &counterexample.trace {
+                write!(f, "\n    {}", line)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Check a list of properties over a time range.
+///
+/// Properties use the syntax of `find_sequence_matches`, and every cycle in the
+/// time range starts a new attempt of each property. A plain condition such as
+/// `!(TOP.grant_a && TOP.grant_b)` must hold at every cycle; a sequence must match
+/// starting at every cycle; an implication is only attempted where its antecedent
+/// matches. Attempts whose outcome depends on cycles past the end of the waveform
+/// are neither passed nor failed.
+///
+/// # Arguments
+/// * `waveform` - The waveform to read from
+/// * `properties` - The properties to check
+/// * `start_idx` - Starting time index of attempts (inclusive)
+/// * `end_idx` - Ending time index of attempts (inclusive)
+/// * `max_counterexamples` - Maximum number of failing attempts to trace per property
+/// * `options` - Additional search options, such as the sampling clock
+///
+/// # Returns
+/// A report per property, in order, or an error if a property cannot be checked.
+pub fn check_assertions(
+    waveform: &mut wellen::simple::Waveform,
+    properties: &[String],
+    start_idx: usize,
+    end_idx: usize,
+    max_counterexamples: usize,
+    options: &ConditionOptions,
+) -> Result<Vec<PropertyReport>, String> {
+    // Get timescale before any mutable operations
+    let timescale = waveform.hierarchy().timescale();
+
+    let mut reports = Vec::new();
+    for property_str in properties {
+        let with_context = |e: String| format!("Property '{}': {}", property_str, e);
+
+        let parsed = parse_property(property_str).map_err(|e| with_context(e.to_string()))?;
+        let mut clock = resolve_clock(parsed.clock, options).map_err(with_context)?;
+        let mut property = parsed.property;
+        resolve_signal_scopes(
+            waveform.hierarchy(),
+            property.conditions_mut(),
+            clock.as_mut(),
+            options,
+        )
+        .map_err(with_context)?;
+
+        let loaded = load_condition_signals(
+            waveform,
+            &property.conditions(),
+            clock.as_ref(),
+            options.paths.as_deref(),
+        )
+        .map_err(with_context)?;
+        let waveform = &*waveform;
+        let ctx = loaded.context(waveform);
+        let time_table = waveform.time_table();
+        let compiled = CompiledProperty::compile(&property, &ctx).map_err(with_context)?;
+
+        let mut report = PropertyReport {
+            property: property_str.clone(),
+            passed: 0,
+            failed: 0,
+            counterexamples: Vec::new(),
+        };
+
+        for sample_idx in ctx.first_sample_from(start_idx)..ctx.num_samples() {
+            if ctx.report_idx(sample_idx) > end_idx {
+                break;
+            }
+
+            let Some(attempt) = check_attempt(&compiled, &ctx, sample_idx).map_err(with_context)?
+            else {
+                continue;
+            };
+            if attempt.passed {
+                report.passed += 1;
+                continue;
+            }
+
+            report.failed += 1;
+            if report.counterexamples.len() < max_counterexamples {
+                let mut trace = Vec::new();
+                for trace_sample in attempt.start..=attempt.end {
+                    let report_idx = ctx.report_idx(trace_sample);
+                    trace.push(format!(
+                        "Time index {} ({}): {}",
+                        report_idx,
+                        format_time(time_table[report_idx], timescale.as_ref()),
+                        ctx.format_signal_values(&loaded.signal_names, trace_sample)?
+                    ));
+                }
+                report.counterexamples.push(Counterexample {
+                    start_idx: ctx.report_idx(attempt.start),
+                    end_idx: ctx.report_idx(attempt.end),
+                    trace,
+                });
+            }
+        }
+
+        reports.push(report);
+    }
+
+    Ok(reports)
+}
@@ -1,23 +1,41 @@
 //! Condition parsing and evaluation for conditional event search.
 
 use super::{
-    formatting::format_signal_value, formatting::format_time, hierarchy::find_var_by_path,
+    derived::DerivedSignals, formatting::format_signal_value, formatting::format_time,
+    hierarchy::resolve_relative_path, hierarchy::PathIndex, hierarchy::PathLookup,
+    program::Program, value::ArithOp, value::CompareOp, value::SampledFunction, value::ShiftOp,
+    value::Value,
 };
-use lalrpop_util::lalrpop_mod;
+use lalrpop_util::{lalrpop_mod, lexer::Token, ParseError};
 use num_bigint::BigUint;
-use num_traits::Zero;
-use std::ops::{BitAnd, BitOr, BitXor};
+use num_traits::{ToPrimitive, Zero};
+use rayon::prelude::*;
 use wellen;
 
 // Import generated parser
-lalrpop_mod!(condition);
+lalrpop_mod!(pub(super) condition);
 
 /// Literal value for signal comparison.
 #[derive(Debug, Clone, PartialEq)]
 pub(super) enum Literal {
-    Binary(Vec<bool>, u32), // bits, bit width
-    Decimal(u64, u32),      // value, bit width
-    Hexadecimal(u64, u32),  // value, bit width
+    Binary(String, u32),   // digits (0, 1, x or z), bit width
+    Decimal(BigUint, u32), // value, bit width
+    Fill(char),            // '0, '1, 'x or 'z, repeated to the width of the context
+    Real(f64),             // 1.2, 2.5e-3
+    String(String),        // "text", with escapes resolved
+    Time(u128, String),    // 10ns, 2.5us: zeptoseconds and the literal as written
+}
+
+/// Bits chosen by a part-select, using the indices of the selected expression.
+#[derive(Debug, Clone)]
+pub(super) enum Selection {
+    Bit(Box<Condition>), // [index], where the index may be any expression
+    Range(u32, u32),     // [msb:lsb]
+    Indexed {
+        base: Box<Condition>,
+        width: u32,
+        ascending: bool, // `+:` when true, `-:` when false
+    },
 }
 
 /// Condition for finding events based on signal values.
@@ -28,21 +46,506 @@ pub(super) enum Condition {
     Not(Box<Condition>),
     BitwiseNot(Box<Condition>),
     Signal(String),
-    BitExtract(String, Option<u32>, Option<u32>), // signal, msb (optional), lsb (optional)
+    Select(Box<Condition>, Selection),
     Eq(Box<Condition>, Box<Condition>),
     Neq(Box<Condition>, Box<Condition>),
+    CaseEq(Box<Condition>, Box<Condition>),
+    CaseNeq(Box<Condition>, Box<Condition>),
+    WildcardEq(Box<Condition>, Box<Condition>),
+    WildcardNeq(Box<Condition>, Box<Condition>),
+    Lt(Box<Condition>, Box<Condition>),
+    Le(Box<Condition>, Box<Condition>),
+    Gt(Box<Condition>, Box<Condition>),
+    Ge(Box<Condition>, Box<Condition>),
     BitwiseAnd(Box<Condition>, Box<Condition>),
     BitwiseOr(Box<Condition>, Box<Condition>),
     BitwiseXor(Box<Condition>, Box<Condition>),
+    Add(Box<Condition>, Box<Condition>),
+    Sub(Box<Condition>, Box<Condition>),
+    Mul(Box<Condition>, Box<Condition>),
+    Div(Box<Condition>, Box<Condition>),
+    Mod(Box<Condition>, Box<Condition>),
+    ShiftLeft(Box<Condition>, Box<Condition>),
+    ShiftRight(Box<Condition>, Box<Condition>),
+    ArithShiftRight(Box<Condition>, Box<Condition>),
+    Concat(Vec<Condition>),
+    Replicate(u32, Vec<Condition>), // count, items
     Literal(Literal),
-    Past(Box<Condition>),
+    Past(Box<Condition>, u32), // expression, number of time indices to look back
+    Rose(Box<Condition>),
+    Fell(Box<Condition>),
+    Stable(Box<Condition>),
+    Changed(Box<Condition>),
+    ReduceAnd(Box<Condition>),
+    ReduceOr(Box<Condition>),
+    ReduceXor(Box<Condition>),
+    CountOnes(Box<Condition>),
+    OneHot(Box<Condition>),
+    OneHot0(Box<Condition>),
+    IsUnknown(Box<Condition>),
+    Neg(Box<Condition>),
+    Signed(Box<Condition>),
+    Unsigned(Box<Condition>),
+    Ternary(Box<Condition>, Box<Condition>, Box<Condition>), // condition, value if true, value if false
+    Scoped(String, Box<Condition>), // `with scope { expr }`, removed by `resolve_signal_scopes`
+    Abs(Box<Condition>),            // real absolute value
+    Approx(Box<Condition>, Box<Condition>, Box<Condition>), // left, right, tolerance
+    Contains(Box<Condition>, Box<Condition>), // string, substring
+    Time,                           // `$time`, in timescale units
+}
+
+impl Condition {
+    /// The operator of an arithmetic expression such as `a + b`.
+    pub(super) fn arith_op(&self) -> Option<ArithOp> {
+        match self {
+            Condition::Add(_, _) => Some(ArithOp::Add),
+            Condition::Sub(_, _) => Some(ArithOp::Sub),
+            Condition::Mul(_, _) => Some(ArithOp::Mul),
+            Condition::Div(_, _) => Some(ArithOp::Div),
+            Condition::Mod(_, _) => Some(ArithOp::Mod),
+            _ => None,
+        }
+    }
+
+    /// The operator of a shift such as `a << b`.
+    pub(super) fn shift_op(&self) -> Option<ShiftOp> {
+        match self {
+            Condition::ShiftLeft(_, _) => Some(ShiftOp::Left),
+            Condition::ShiftRight(_, _) => Some(ShiftOp::Right),
+            Condition::ArithShiftRight(_, _) => Some(ShiftOp::ArithRight),
+            _ => None,
+        }
+    }
+
+    /// The operator of a comparison such as `a == b`.
+    pub(super) fn compare_op(&self) -> Option<CompareOp> {
+        match self {
+            Condition::Eq(_, _) => Some(CompareOp::Eq),
+            Condition::Neq(_, _) => Some(CompareOp::Neq),
+            Condition::CaseEq(_, _) => Some(CompareOp::CaseEq),
+            Condition::CaseNeq(_, _) => Some(CompareOp::CaseNeq),
+            Condition::WildcardEq(_, _) => Some(CompareOp::WildcardEq),
+            Condition::WildcardNeq(_, _) => Some(CompareOp::WildcardNeq),
+            Condition::Lt(_, _) => Some(CompareOp::Lt),
+            Condition::Le(_, _) => Some(CompareOp::Le),
+            Condition::Gt(_, _) => Some(CompareOp::Gt),
+            Condition::Ge(_, _) => Some(CompareOp::Ge),
+            _ => None,
+        }
+    }
+
+    /// The function of a sampled value function call such as `$rose(a)`.
+    pub(super) fn sampled_function(&self) -> Option<SampledFunction> {
+        match self {
+            Condition::Rose(_) => Some(SampledFunction::Rose),
+            Condition::Fell(_) => Some(SampledFunction::Fell),
+            Condition::Stable(_) => Some(SampledFunction::Stable),
+            Condition::Changed(_) => Some(SampledFunction::Changed),
+            _ => None,
+        }
+    }
+
+    /// The direct subexpressions of the condition.
+    pub(super) fn children(&self) -> Vec<&Condition> {
+        match self {
+            Condition::And(left, right)
+            | Condition::Or(left, right)
+            | Condition::Eq(left, right)
+            | Condition::Neq(left, right)
+            | Condition::CaseEq(left, right)
+            | Condition::CaseNeq(left, right)
+            | Condition::WildcardEq(left, right)
+            | Condition::WildcardNeq(left, right)
+            | Condition::Lt(left, right)
+            | Condition::Le(left, right)
+            | Condition::Gt(left, right)
+            | Condition::Ge(left, right)
+            | Condition::BitwiseAnd(left, right)
+            | Condition::BitwiseOr(left, right)
+            | Condition::BitwiseXor(left, right)
+            | Condition::Add(left, right)
+            | Condition::Sub(left, right)
+            | Condition::Mul(left, right)
+            | Condition::Div(left, right)
+            | Condition::Mod(left, right)
+            | Condition::ShiftLeft(left, right)
+            | Condition::ShiftRight(left, right)
+            | Condition::ArithShiftRight(left, right) => vec![left.as_ref(), right.as_ref()],
+            Condition::Not(expr)
+            | Condition::BitwiseNot(expr)
+            | Condition::Past(expr, _)
+            | Condition::Rose(expr)
+            | Condition::Fell(expr)
+            | Condition::Stable(expr)
+            | Condition::Changed(expr)
+            | Condition::ReduceAnd(expr)
+            | Condition::ReduceOr(expr)
+            | Condition::ReduceXor(expr)
+            | Condition::CountOnes(expr)
+            | Condition::OneHot(expr)
+            | Condition::OneHot0(expr)
+            | Condition::IsUnknown(expr)
+            | Condition::Neg(expr)
+            | Condition::Signed(expr)
+            | Condition::Unsigned(expr)
+            | Condition::Scoped(_, expr)
+            | Condition::Abs(expr) => vec![expr.as_ref()],
+            Condition::Contains(left, right) => vec![left.as_ref(), right.as_ref()],
+            Condition::Ternary(cond, if_true, if_false)
+            | Condition::Approx(cond, if_true, if_false) => {
+                vec![cond.as_ref(), if_true.as_ref(), if_false.as_ref()]
+            }
+            Condition::Select(expr, selection) => match selection {
+                Selection::Bit(index) => vec![expr.as_ref(), index.as_ref()],
+                Selection::Indexed { base, .. } => vec![expr.as_ref(), base.as_ref()],
+                Selection::Range(_, _) => vec![expr.as_ref()],
+            },
+            Condition::Concat(items) | Condition::Replicate(_, items) => items.iter().collect(),
+            Condition::Signal(_) | Condition::Literal(_) | Condition::Time => Vec::new(),
+        }
+    }
+
+    /// The direct subexpressions of the condition.
+    pub(super) fn children_mut(&mut self) -> Vec<&mut Condition> {
+        match self {
+            Condition::And(left, right)
+            | Condition::Or(left, right)
+            | Condition::Eq(left, right)
+            | Condition::Neq(left, right)
+            | Condition::CaseEq(left, right)
+            | Condition::CaseNeq(left, right)
+            | Condition::WildcardEq(left, right)
+            | Condition::WildcardNeq(left, right)
+            | Condition::Lt(left, right)
+            | Condition::Le(left, right)
+            | Condition::Gt(left, right)
+            | Condition::Ge(left, right)
+            | Condition::BitwiseAnd(left, right)
+            | Condition::BitwiseOr(left, right)
+            | Condition::BitwiseXor(left, right)
+            | Condition::Add(left, right)
+            | Condition::Sub(left, right)
+            | Condition::Mul(left, right)
+            | Condition::Div(left, right)
+            | Condition::Mod(left, right)
+            | Condition::ShiftLeft(left, right)
+            | Condition::ShiftRight(left, right)
+            | Condition::ArithShiftRight(left, right) => vec![left.as_mut(), right.as_mut()],
+            Condition::Not(expr)
+            | Condition::BitwiseNot(expr)
+            | Condition::Past(expr, _)
+            | Condition::Rose(expr)
+            | Condition::Fell(expr)
+            | Condition::Stable(expr)
+            | Condition::Changed(expr)
+            | Condition::ReduceAnd(expr)
+            | Condition::ReduceOr(expr)
+            | Condition::ReduceXor(expr)
+            | Condition::CountOnes(expr)
+            | Condition::OneHot(expr)
+            | Condition::OneHot0(expr)
+            | Condition::IsUnknown(expr)
+            | Condition::Neg(expr)
+            | Condition::Signed(expr)
+            | Condition::Unsigned(expr)
+            | Condition::Scoped(_, expr)
+            | Condition::Abs(expr) => vec![expr.as_mut()],
+            Condition::Contains(left, right) => vec![left.as_mut(), right.as_mut()],
+            Condition::Ternary(cond, if_true, if_false)
+            | Condition::Approx(cond, if_true, if_false) => {
+                vec![cond.as_mut(), if_true.as_mut(), if_false.as_mut()]
+            }
+            Condition::Select(expr, selection) => match selection {
+                Selection::Bit(index) => vec![expr.as_mut(), index.as_mut()],
+                Selection::Indexed { base, .. } => vec![expr.as_mut(), base.as_mut()],
+                Selection::Range(_, _) => vec![expr.as_mut()],
+            },
+            Condition::Concat(items) | Condition::Replicate(_, items) => items.iter_mut().collect(),
+            Condition::Signal(_) | Condition::Literal(_) | Condition::Time => Vec::new(),
+        }
+    }
+}
+
+/// Clock edge used to sample a condition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockEdge {
+    Posedge,
+    Negedge,
+    Edge, // both edges
+}
+
+impl std::str::FromStr for ClockEdge {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "posedge" => Ok(ClockEdge::Posedge),
+            "negedge" => Ok(ClockEdge::Negedge),
+            "edge" => Ok(ClockEdge::Edge),
+            _ => Err(format!(
+                "Invalid clock edge '{}', expected posedge, negedge or edge",
+                s
+            )),
+        }
+    }
+}
+
+/// Clock that a condition is sampled on, e.g. `@(posedge TOP.clk)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SamplingClock {
+    pub signal: String,
+    pub edge: ClockEdge,
+}
+
+/// Options for conditional event search.
+#[derive(Debug, Clone, Default)]
+pub struct ConditionOptions {
+    /// Evaluate the condition only at edges of this clock
+    pub clock: Option<SamplingClock>,
+    /// Scope that signal names are relative to, e.g. "TOP.soc.cpu0"
+    pub scope: Option<String>,
+    /// Derived signals that the condition may refer to by name
+    pub derived: DerivedSignals,
+    /// Index of the paths in the waveform; without one, paths are searched for
+    pub paths: Option<std::sync::Arc<PathIndex>>,
+}
+
+impl ConditionOptions {
+    /// Look up paths with the index in the options, if any.
+    pub(super) fn path_lookup<'a>(&'a self, hierarchy: &'a wellen::Hierarchy) -> PathLookup<'a> {
+        PathLookup::new(hierarchy, self.paths.as_deref())
+    }
+}
+
+/// A parsed condition with its optional `@(edge signal)` sampling clock.
+#[derive(Debug, Clone)]
+pub(super) struct SampledCondition {
+    pub(super) clock: Option<SamplingClock>,
+    pub(super) condition: Condition,
+}
+
+/// Error raised by a grammar action, such as a malformed literal.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct SpannedError {
+    /// Byte range of the offending text
+    pub(super) span: std::ops::Range<usize>,
+    pub(super) message: String,
+}
+
+/// Attach the byte range of the offending text to the error of a grammar action.
+pub(super) fn with_span<T, Tok>(
+    result: Result<T, String>,
+    start: usize,
+    end: usize,
+) -> Result<T, ParseError<usize, Tok, SpannedError>> {
+    result.map_err(|message| ParseError::User {
+        error: SpannedError {
+            span: start..end,
+            message,
+        },
+    })
+}
+
+/// Error found while parsing a condition or property, with the text it refers to.
+///
+/// Displays as the message, the location and the tokens that were expected,
+/// followed by the offending line with the error marked and an optional hint:
+///
+/// ```text
+/// Unexpected `and` at column 9, expected one of `&&`, `||`, ...
+///   TOP.req and TOP.ack
+///           ^^^
+///   hint: did you mean `&&`?
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct ConditionError {
+    pub(super) message: String,
+    /// The text that was parsed
+    pub(super) source: String,
+    /// Byte range of the offending text in `source`
+    pub(super) span: std::ops::Range<usize>,
+    /// Descriptions of the tokens that would have been accepted, if known
+    pub(super) expected: Vec<String>,
+    pub(super) hint: Option<String>,
+}
+
+impl ConditionError {
+    /// Convert an error of the generated parser.
+    pub(super) fn from_parse_error(
+        source: &str,
+        error: ParseError<usize, Token<'_>, SpannedError>,
+    ) -> Self {
+        let (message, span, expected) = match error {
+            ParseError::InvalidToken { location } => {
+                let found = source[location..].chars().next().unwrap_or(' ');
+                (
+                    format!("Invalid character `{}`", found),
+                    location..location + found.len_utf8(),
+                    Vec::new(),
+                )
+            }
+            ParseError::UnrecognizedEof { location, expected } => (
+                "Unexpected end of input".to_string(),
+                location..location,
+                expected,
+            ),
+            ParseError::UnrecognizedToken {
+                token: (start, token, end),
+                expected,
+            } => (format!("Unexpected `{}`", token.1), start..end, expected),
+            ParseError::ExtraToken {
+                token: (start, token, end),
+            } => (
+                format!("Unexpected `{}` after the end of the expression", token.1),
+                start..end,
+                Vec::new(),
+            ),
+            ParseError::User { error } => (error.message, error.span, Vec::new()),
+        };
+        let expected: Vec<String> = expected.iter().map(|t| describe_terminal(t)).collect();
+        let hint = parse_hint(source, &span);
+
+        ConditionError {
+            message,
+            source: source.to_string(),
+            span,
+            expected,
+            hint,
+        }
+    }
+
+    /// Line of the start of the error, counting from 1.
+    pub(super) fn line(&self) -> usize {
+        self.source[..self.span.start].matches('\n').count() + 1
+    }
+
+    /// Column of the start of the error within its line, in characters counting from 1.
+    pub(super) fn column(&self) -> usize {
+        let line_start = self.source[..self.span.start]
+            .rfind('\n')
+            .map_or(0, |i| i + 1);
+        self.source[line_start..self.span.start].chars().count() + 1
+    }
+}
+
+impl std::fmt::Display for ConditionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)?;
+        if self.source.contains('\n') {
+            write!(f, " at line {}, column {}", self.line(), self.column())?;
+        } else {
+            write!(f, " at column {}", self.column())?;
+        }
+        if self.expected.iter().any(|t| t == "signal name") {
+            // Every operand can start here; listing them all is noise
+            write!(f, ", expected an expression")?;
+        } else if self.expected.len() == 1 {
+            write!(f, ", expected {}", self.expected[0])?;
+        } else if !self.expected.is_empty() {
+            write!(f, ", expected one of {}", self.expected.join(", "))?;
+        }
+
+        let line_start = self.source[..self.span.start]
+            .rfind('\n')
+            .map_or(0, |i| i + 1);
+        let line_end = self.source[self.span.start..]
+            .find('\n')
+            .map_or(self.source.len(), |i| self.span.start + i);
+        // Keep tabs so the marker lines up with the text above it
+        let indent: String = self.source[line_start..self.span.start]
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let marked = self.source[self.span.start..self.span.end.min(line_end)]
+            .chars()
+            .count()
+            .max(1);
+        write!(
+            f,
+            "\n  {}\n  {}{}",
+            &self.source[line_start..line_end],
+            indent,
+            "^".repeat(marked)
+        )?;
+
+        if let Some(hint) = &self.hint {
+            write!(f, "\n  hint: {}", hint)?;
+        }
+        Ok(())
+    }
+}
+
+/// Describe a terminal of the grammar as reported in the expected tokens of a parse error.
+fn describe_terminal(terminal: &str) -> String {
+    match terminal
+        .strip_prefix("r#\"")
+        .and_then(|t| t.strip_suffix("\"#"))
+    {
+        Some(pattern) if pattern.contains("[a-zA-Z_]") => "signal name".to_string(),
+        Some("[0-9]+") => "number".to_string(),
+        Some(pattern) if pattern.starts_with('\'') => "fill literal".to_string(),
+        Some(_) => "literal".to_string(),
+        None => format!("`{}`", terminal.trim_matches('"')),
+    }
+}
+
+/// Suggest a fix for common mistakes, such as writing `and` instead of `&&`.
+fn parse_hint(source: &str, span: &std::ops::Range<usize>) -> Option<String> {
+    let rest = &source[span.start..];
+    let found = &source[span.clone()];
+    let opened = |open: char, close: char| {
+        source.matches(open).count() as isize - source.matches(close).count() as isize
+    };
+
+    match found.to_lowercase().as_str() {
+        "and" => return Some("did you mean `&&`?".to_string()),
+        "or" => return Some("did you mean `||`?".to_string()),
+        "not" => return Some("did you mean `!`?".to_string()),
+        _ => {}
+    }
+    if rest.starts_with('=') && !rest.starts_with("==") {
+        Some("did you mean `==`?".to_string())
+    } else if rest.starts_with('>') && source[..span.start].trim_end().ends_with('<') {
+        Some("did you mean `!=`?".to_string())
+    } else if rest.starts_with('$') {
+        Some(
+            "unknown system function; supported are $past, $rose, $fell, $stable, $changed, \
+             $countones, $onehot, $onehot0, $isunknown, $signed, $unsigned, $abs, $approx, \
+             $contains and $time"
+                .to_string(),
+        )
+    } else if rest.starts_with('\'') {
+        Some("literals are written like 4'b0101, 8'hFF, 'd10 or '1".to_string())
+    } else if source[..span.start].ends_with('0')
+        && found.len() > 1
+        && found.starts_with(['x', 'X'])
+        && found[1..]
+            .chars()
+            .all(|c| c.is_ascii_hexdigit() || c == '_')
+    {
+        Some(format!("hex literals are written like 'h{}", &found[1..]))
+    } else if found == ")" && opened('(', ')') < 0 {
+        Some("this `)` has no matching `(`".to_string())
+    } else if span.is_empty() && opened('(', ')') > 0 {
+        Some("a `)` is missing".to_string())
+    } else if span.is_empty() && opened('[', ']') > 0 {
+        Some("a `]` is missing".to_string())
+    } else if span.is_empty() && opened('{', '}') > 0 {
+        Some("a `}` is missing".to_string())
+    } else {
+        None
+    }
 }
 
 /// Parse a simple condition string into a Condition AST.
 ///
 /// Supports:
 /// - Signal paths (e.g., "TOP.signal")
-/// - Bit extraction: `signal[bit]` for single bit, `signal[msb:lsb]` for range
+/// - Part-selects on any expression: `a[bit]`, `a[msb:lsb]`, `(a ^ b)[3:0]`, `$past(a)[7]`
+/// - Indexed part-selects `a[base +: width]` and `a[base -: width]`; bit indices and bases
+///   may be expressions such as `a[i*8 +: 8]`, and select X when they are unknown or
+///   out of range
+/// - Signals are indexed by their declared range, e.g. `[15:8]` or `[0:7]`
 /// - `&&` for logical AND
 /// - `||` for logical OR
 /// - `!` for logical NOT
@@ -52,357 +555,1688 @@ pub(super) enum Condition {
 /// - `^` for bitwise XOR
 /// - `==` for equality comparison
 /// - `!=` for inequality comparison
+/// - `===`, `!==` for case equality, matching X and Z bits exactly
+/// - `==?`, `!=?` for wildcard equality, where X and Z bits on the right match anything
+/// - `<`, `<=`, `>`, `>=` for relational comparison
+/// - `+`, `-`, `*`, `/`, `%` for arithmetic with Verilog width and wrap-around semantics
+/// - Unary `-` for two's complement negation
+/// - `$signed(expr)`, `$unsigned(expr)` casts; like Verilog, an operation is signed only
+///   if all of its context-determined operands are signed
+/// - `<<`, `<<<`, `>>`, `>>>` for logical and arithmetic shifts
+/// - `{a, b}` for concatenation and `{N{a}}` for replication
 /// - `$past(signal)` to read signal value from previous time index
+/// - `$past(signal, N)` to read signal value from N time indices earlier
+/// - `$rose(expr)`, `$fell(expr)` to detect the least significant bit changing to 1 or 0
+/// - `$stable(expr)`, `$changed(expr)` to detect whether the value changed
+/// - `&`, `|`, `^`, `~&`, `~|`, `~^` as unary reduction operators
+/// - `$countones(expr)` to count bits set to 1
+/// - `$onehot(expr)`, `$onehot0(expr)` to check that exactly one or at most one bit is 1
+/// - `$isunknown(expr)` to check whether any bit is X or Z
+/// - `cond ? a : b` to choose between two values; when `cond` is X or Z, bits that
+///   differ between `a` and `b` are X
 /// - Parentheses for grouping
-/// - Verilog-style literals: 4'b0101, 3'd2, 5'h1A
+/// - Verilog-style literals of any width: 4'b0101, 3'd2, 12'o777, 128'hDEAD_BEEF, with x, z
+///   or ? digits in binary, octal and hex literals
+/// - Signed literals: 8'sd5, 4'sb1011, 8'shF0
+/// - Unsized literals: `42` (signed, at least 32 bits), `'hFF` (unsigned, at least 32 bits)
+/// - Fill literals `'0`, `'1`, `'x`, `'z` that take the width of their context
+/// - Names of enum values compared against a signal with an enum type: `TOP.state == IDLE`
+/// - Real signals and literals (`1.2`, `2.5e-3`) with comparisons, `+`, `-`, `*`, `/`,
+///   `$abs(expr)` and `$approx(a, b, tolerance)`; bit vectors are converted to reals
+/// - String signals and literals (`"RESET"`) with comparisons and `$contains(text, part)`
+/// - `$time` for the time of the sample in timescale units, and time literals with
+///   units (`10ns`, `2.5us`; units s, ms, us, ns, ps and fs) to compare it against
+/// - `with scope { expr }` to resolve signal names in `expr` relative to `scope`
+/// - `$root.path` to refer to a signal from the top of the hierarchy inside a scope
+/// - An optional `@(posedge signal)`, `@(negedge signal)` or `@(edge signal)` prefix
+///   to sample the condition on a clock
 ///
 /// Uses lalrpop-generated parser.
-pub(super) fn parse_condition(condition: &str) -> Result<Condition, String> {
-    let parser = crate::condition::condition::ExprParser::new();
-    parser.parse(condition).map_err(|e| e.to_string())
+pub(super) fn parse_condition(condition: &str) -> Result<SampledCondition, ConditionError> {
+    let parser = crate::condition::condition::SampledExprParser::new();
+    parser
+        .parse(condition)
+        .map_err(|e| ConditionError::from_parse_error(condition, e))
+}
+
+/// Signals and sample points shared by every evaluation of a condition.
+pub(super) struct EvalContext<'a> {
+    pub(super) waveform: &'a wellen::simple::Waveform,
+    pub(super) signal_cache: &'a std::collections::HashMap<String, wellen::VarRef>,
+    /// Time indices sampled by a clock, or `None` to sample every time index
+    samples: Option<&'a [usize]>,
+}
+
+impl EvalContext<'_> {
+    /// Map a sample index to the time index it reads values from.
+    pub(super) fn time_idx(&self, sample_idx: usize) -> usize {
+        match self.samples {
+            Some(samples) => samples[sample_idx],
+            None => sample_idx,
+        }
+    }
+
+    /// Map a sample index to the time index it is reported at.
+    ///
+    /// Clocked samples read values just before the edge but are reported at the edge.
+    pub(super) fn report_idx(&self, sample_idx: usize) -> usize {
+        match self.samples {
+            Some(samples) => samples[sample_idx] + 1,
+            None => sample_idx,
+        }
+    }
+
+    /// Total number of samples in the waveform.
+    pub(super) fn num_samples(&self) -> usize {
+        match self.samples {
+            Some(samples) => samples.len(),
+            None => self.waveform.time_table().len(),
+        }
+    }
+
+    /// The first sample reported at or after `time_idx`.
+    pub(super) fn first_sample_from(&self, time_idx: usize) -> usize {
+        match self.samples {
+            Some(samples) => samples.partition_point(|&idx| idx + 1 < time_idx),
+            None => time_idx,
+        }
+    }
+
+    /// The first sample that reads values at or after `time_idx`.
+    fn first_sample_reading(&self, time_idx: usize) -> usize {
+        match self.samples {
+            Some(samples) => samples.partition_point(|&idx| idx < time_idx),
+            None => time_idx,
+        }
+    }
+
+    /// The samples at which the value of `condition` may differ from the previous sample,
+    /// in increasing order.
+    ///
+    /// A condition depends only on its signals at the current sample and at most
+    /// `history_depth` samples before, so its value can only change at a sample where
+    /// one of its signals changed or up to that many samples later. Returns `None` if
+    /// the value may change at every sample, as with `$time`.
+    pub(super) fn change_samples(
+        &self,
+        condition: &Condition,
+    ) -> Result<Option<Vec<usize>>, String> {
+        let Some(depth) = history_depth(condition) else {
+            return Ok(None);
+        };
+
+        let hierarchy = self.waveform.hierarchy();
+        let mut changes = vec![0];
+        for signal_name in extract_signal_names(condition) {
+            let var_ref = self
+                .signal_cache
+                .get(&signal_name)
+                .ok_or_else(|| format!("Signal not found in cache: {}", signal_name))?;
+            let signal = self
+                .waveform
+                .get_signal(hierarchy[*var_ref].signal_ref())
+                .ok_or_else(|| format!("Signal {} not loaded", signal_name))?;
+            changes.extend(
+                signal
+                    .time_indices()
+                    .iter()
+                    .map(|&time_idx| self.first_sample_reading(time_idx as usize)),
+            );
+        }
+        changes.sort_unstable();
+
+        // Each change can affect the samples up to `depth` later
+        let num_samples = self.num_samples();
+        let mut samples: Vec<usize> = Vec::new();
+        for change in changes {
+            let from = samples.last().map_or(change, |&last| change.max(last + 1));
+            let to = change
+                .saturating_add(depth)
+                .min(num_samples.saturating_sub(1));
+            samples.extend(from..=to);
+        }
+        Ok(Some(samples))
+    }
+
+    /// Format the sampled values of signals as `name = value` pairs.
+    pub(super) fn format_signal_values(
+        &self,
+        signal_names: &[String],
+        sample_idx: usize,
+    ) -> Result<String, String> {
+        let time_idx = self.time_idx(sample_idx);
+        let mut signal_values = Vec::new();
+        for signal_name in signal_names {
+            if let Some(var_ref) = self.signal_cache.get(signal_name) {
+                let hierarchy = self.waveform.hierarchy();
+                let signal_ref = hierarchy[*var_ref].signal_ref();
+                if let Some(signal) = self.waveform.get_signal(signal_ref) {
+                    let time_table_idx: wellen::TimeTableIdx = time_idx
+                        .try_into()
+                        .map_err(|_| format!("Time index {} too large", time_idx))?;
+
+                    if let Some(offset) = signal.get_offset(time_table_idx) {
+                        let signal_value = signal.get_value_at(&offset, 0);
+                        let value_str = format_signal_value(signal_value);
+                        signal_values.push(format!("{} = {}", signal_name, value_str));
+                    }
+                }
+            }
+        }
+        Ok(signal_values.join(", "))
+    }
 }
 
-/// Evaluate a condition at a specific time index.
+/// Signals loaded for a search, together with the samples it is evaluated at.
+pub(super) struct LoadedSignals {
+    /// Signals referenced by the searched conditions, in order of first use
+    pub(super) signal_names: Vec<String>,
+    signal_cache: std::collections::HashMap<String, wellen::VarRef>,
+    /// Time indices sampled by a clock, or `None` to sample every time index
+    samples: Option<Vec<usize>>,
+}
+
+impl LoadedSignals {
+    /// Create an evaluation context over the loaded signals.
+    pub(super) fn context<'a>(&'a self, waveform: &'a wellen::simple::Waveform) -> EvalContext<'a> {
+        EvalContext {
+            waveform,
+            signal_cache: &self.signal_cache,
+            samples: self.samples.as_deref(),
+        }
+    }
+}
+
+/// Pick the sampling clock from a parsed `@(edge signal)` prefix or the search options.
+pub(super) fn resolve_clock(
+    parsed: Option<SamplingClock>,
+    options: &ConditionOptions,
+) -> Result<Option<SamplingClock>, String> {
+    match (parsed, &options.clock) {
+        (Some(_), Some(_)) => {
+            Err("Sampling clock given both in the condition and as an option".to_string())
+        }
+        (clock, option) => Ok(clock.or_else(|| option.clone())),
+    }
+}
+
+/// Resolve signal names relative to the `scope` option and `with scope { ... }` blocks.
+///
+/// Every signal name in the conditions and the sampling clock is replaced by the
+/// path it resolves to, as described in `resolve_relative_path`, and `with` blocks
+/// are removed. A name that matches in more than one enclosing scope is an error.
+/// Names of derived signals in `options` are replaced by their expressions, and time
+/// literals are converted to the timescale units of the waveform.
+pub(super) fn resolve_signal_scopes(
+    hierarchy: &wellen::Hierarchy,
+    conditions: Vec<&mut Condition>,
+    clock: Option<&mut SamplingClock>,
+    options: &ConditionOptions,
+) -> Result<(), String> {
+    let paths = options.path_lookup(hierarchy);
+    let mut scopes = Vec::new();
+    if let Some(scope) = &options.scope {
+        let scope = scope.strip_prefix("$root.").unwrap_or(scope);
+        paths
+            .find_scope(scope)
+            .ok_or_else(|| format!("Scope not found: {}", scope))?;
+        scopes.push(scope.to_string());
+    }
+
+    if let Some(clock) = clock {
+        clock.signal = resolve_relative_path(&clock.signal, &scopes, "Signal", |path| {
+            paths.find_var(path)
+        })?;
+    }
+    for condition in conditions {
+        resolve_condition_scopes(hierarchy, paths, condition, &mut scopes, &options.derived)?;
+    }
+    Ok(())
+}
+
+fn resolve_condition_scopes(
+    hierarchy: &wellen::Hierarchy,
+    paths: PathLookup,
+    condition: &mut Condition,
+    scopes: &mut Vec<String>,
+    derived: &DerivedSignals,
+) -> Result<(), String> {
+    match condition {
+        Condition::Signal(path) => {
+            if let Some(signal) = derived.get(path) {
+                if !scopes.is_empty() {
+                    if let Ok(found) =
+                        resolve_relative_path(path, scopes, "Signal", |path| paths.find_var(path))
+                    {
+                        return Err(format!(
+                            "Signal {} is ambiguous: it matches the derived signal {} and {}",
+                            path, path, found
+                        ));
+                    }
+                }
+                // Derived signals are already resolved when they are defined
+                *condition = signal.condition.clone();
+                return Ok(());
+            }
+            *path = resolve_relative_path(path, scopes, "Signal", |path| paths.find_var(path))?;
+        }
+        Condition::Scoped(scope, expr) => {
+            // The scope of a `with` block may itself be relative to the enclosing scopes
+            let scope =
+                resolve_relative_path(scope, scopes, "Scope", |path| paths.find_scope(path))?;
+            if paths.find_scope(&scope).is_none() {
+                return Err(format!("Scope not found: {}", scope));
+            }
+            scopes.push(scope);
+            let resolved = resolve_condition_scopes(hierarchy, paths, expr, scopes, derived);
+            scopes.pop();
+            resolved?;
+
+            let expr = std::mem::replace(expr.as_mut(), Condition::Signal(String::new()));
+            *condition = expr;
+        }
+        Condition::Literal(Literal::Time(zeptoseconds, text)) => {
+            *condition = time_in_timescale_units(*zeptoseconds, text, hierarchy.timescale())?;
+        }
+        Condition::Eq(left, right)
+        | Condition::Neq(left, right)
+        | Condition::CaseEq(left, right)
+        | Condition::CaseNeq(left, right)
+        | Condition::WildcardEq(left, right)
+        | Condition::WildcardNeq(left, right)
+        | Condition::Lt(left, right)
+        | Condition::Le(left, right)
+        | Condition::Gt(left, right)
+        | Condition::Ge(left, right) => {
+            // Names of enum values are looked up in the enum type of the other operand
+            resolve_enum_name(hierarchy, paths, left, right, scopes, derived)?;
+            resolve_enum_name(hierarchy, paths, right, left, scopes, derived)?;
+            resolve_condition_scopes(hierarchy, paths, left, scopes, derived)?;
+            resolve_condition_scopes(hierarchy, paths, right, scopes, derived)?;
+        }
+        _ => {
+            for child in condition.children_mut() {
+                resolve_condition_scopes(hierarchy, paths, child, scopes, derived)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Convert a time literal to the timescale units of a waveform, like `$time` values.
+///
+/// A time that is not a whole number of units, e.g. `1.5ps` with a timescale of 1ns,
+/// becomes a real number.
+fn time_in_timescale_units(
+    zeptoseconds: u128,
+    text: &str,
+    timescale: Option<wellen::Timescale>,
+) -> Result<Condition, String> {
+    let unit_zeptoseconds = timescale
+        .and_then(|ts| {
+            let exponent = ts.unit.to_exponent()?;
+            Some(u128::from(ts.factor) * 10u128.pow((exponent + 21) as u32))
+        })
+        .ok_or_else(|| format!("Time {} needs a waveform with a known timescale", text))?;
+    if !zeptoseconds.is_multiple_of(unit_zeptoseconds) {
+        return Ok(Condition::Literal(Literal::Real(
+            zeptoseconds as f64 / unit_zeptoseconds as f64,
+        )));
+    }
+    let units = u64::try_from(zeptoseconds / unit_zeptoseconds)
+        .map_err(|_| format!("Time {} is too large", text))?;
+    Ok(Condition::Literal(Literal::Decimal(
+        BigUint::from(units),
+        64,
+    )))
+}
+
+/// Replace `operand` by the encoding of an enum value if it is the name of a value
+/// in the enum type of `other`, e.g. `IDLE` in `TOP.fsm.state == IDLE`.
+///
+/// A name that is not a value of the enum is kept as a signal name if such a signal
+/// exists, and is an error otherwise.
+fn resolve_enum_name(
+    hierarchy: &wellen::Hierarchy,
+    paths: PathLookup,
+    operand: &mut Condition,
+    other: &Condition,
+    scopes: &[String],
+    derived: &DerivedSignals,
+) -> Result<(), String> {
+    let Condition::Signal(name) = operand else {
+        return Ok(());
+    };
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Ok(());
+    }
+    let Some(enum_type) = enum_type_of(hierarchy, paths, other, scopes, derived) else {
+        return Ok(());
+    };
+
+    // A name may also be a derived signal or a signal found relative to a scope
+    let signal = if derived.get(name).is_some() {
+        Some(format!("the derived signal {}", name))
+    } else {
+        resolve_relative_path(name, scopes, "Signal", |path| paths.find_var(path))
+            .ok()
+            .filter(|path| paths.find_var(path).is_some())
+    };
+
+    let values = &enum_type.values;
+    match (values.iter().find(|(_, value)| value == name), signal) {
+        (Some(_), Some(signal)) => Err(format!(
+            "Name {} is ambiguous: it is a value of enum {} and also matches {}",
+            name, enum_type.type_name, signal
+        )),
+        (Some((bits, _)), None) => {
+            let digits = bits.to_ascii_lowercase();
+            if digits.is_empty() || !digits.chars().all(|c| "01xz".contains(c)) {
+                return Err(format!(
+                    "Enum value {} of {} has encoding '{}', which is not a bit string",
+                    name, enum_type.type_name, bits
+                ));
+            }
+            let width = digits.len() as u32;
+            *operand = Condition::Literal(Literal::Binary(digits, width));
+            Ok(())
+        }
+        (None, Some(_)) => Ok(()),
+        (None, None) => {
+            let names: Vec<&str> = values.iter().map(|(_, value)| value.as_str()).collect();
+            Err(format!(
+                "Unknown value {} for {} of enum {}, expected one of: {}",
+                name,
+                enum_type.path,
+                enum_type.type_name,
+                names.join(", ")
+            ))
+        }
+    }
+}
+
+/// The enum type of a signal, from the waveform's enum tables.
+struct SignalEnumType {
+    /// Path of the signal
+    path: String,
+    type_name: String,
+    /// Encoding and name of every value
+    values: Vec<(String, String)>,
+}
+
+/// The enum type of an expression that reads a signal with an enum type, such as
+/// `TOP.fsm.state` or `$past(TOP.fsm.state)`.
+fn enum_type_of(
+    hierarchy: &wellen::Hierarchy,
+    paths: PathLookup,
+    condition: &Condition,
+    scopes: &[String],
+    derived: &DerivedSignals,
+) -> Option<SignalEnumType> {
+    match condition {
+        Condition::Signal(name) => {
+            if let Some(signal) = derived.get(name) {
+                return enum_type_of(hierarchy, paths, &signal.condition, &[], derived);
+            }
+            let path =
+                resolve_relative_path(name, scopes, "Signal", |path| paths.find_var(path)).ok()?;
+            let var_ref = paths.find_var(&path)?;
+            let (type_name, values) = hierarchy[var_ref].enum_type(hierarchy)?;
+            let values = values
+                .into_iter()
+                .map(|(bits, value)| (bits.to_string(), value.to_string()))
+                .collect();
+            Some(SignalEnumType {
+                path,
+                type_name: type_name.to_string(),
+                values,
+            })
+        }
+        Condition::Past(expr, _) => enum_type_of(hierarchy, paths, expr, scopes, derived),
+        _ => None,
+    }
+}
+
+/// Find and load all signals used by `conditions`, plus the sampling clock.
+///
+/// With a clock, every edge of the clock becomes a sample, and values are sampled
+/// just before the edge.
+pub(super) fn load_condition_signals(
+    waveform: &mut wellen::simple::Waveform,
+    conditions: &[&Condition],
+    clock: Option<&SamplingClock>,
+    paths: Option<&PathIndex>,
+) -> Result<LoadedSignals, String> {
+    // Extract all signal names from the conditions
+    let mut signal_names = Vec::new();
+    for condition in conditions {
+        for name in extract_signal_names(condition) {
+            if !signal_names.contains(&name) {
+                signal_names.push(name);
+            }
+        }
+    }
+
+    // Find and load all signals
+    let mut signal_cache = std::collections::HashMap::new();
+    let hierarchy = waveform.hierarchy();
+    let paths = PathLookup::new(hierarchy, paths);
+
+    // Collect all signal_refs first
+    let mut signal_refs = Vec::new();
+    for signal_name in &signal_names {
+        let var_ref = paths
+            .find_var(signal_name)
+            .ok_or_else(|| format!("Signal not found: {}", signal_name))?;
+        let signal_ref = hierarchy[var_ref].signal_ref();
+        signal_cache.insert(signal_name.clone(), var_ref);
+        signal_refs.push(signal_ref);
+    }
+    let clock_var = match clock {
+        Some(clock) => {
+            let var_ref = paths
+                .find_var(&clock.signal)
+                .ok_or_else(|| format!("Clock signal not found: {}", clock.signal))?;
+            signal_refs.push(hierarchy[var_ref].signal_ref());
+            Some((clock.edge, var_ref))
+        }
+        None => None,
+    };
+
+    // Load all signals at once
+    waveform.load_signals(&signal_refs);
+
+    let samples = match clock_var {
+        Some((edge, var_ref)) => {
+            let edges = find_clock_edges(waveform, var_ref, edge)?;
+            Some(edges.iter().map(|edge_idx| edge_idx - 1).collect())
+        }
+        None => None,
+    };
+
+    Ok(LoadedSignals {
+        signal_names,
+        signal_cache,
+        samples,
+    })
+}
+
+/// Evaluate a condition at a specific sample.
 ///
 /// # Arguments
 /// * `condition` - The condition to evaluate
-/// * `waveform` - The waveform to read from
-/// * `signal_cache` - Cache of variable references
-/// * `time_idx` - The time index to evaluate at
+/// * `ctx` - The waveform, signal cache and sample points to evaluate with
+/// * `sample_idx` - The sample to evaluate at; without a sampling clock this is the time index
 ///
 /// # Returns
-/// A BigUint value where 0 = false and any non-zero value = true.
-fn evaluate_condition(
+/// A four-state value; the condition holds only if the value is known to be non-zero.
+pub(super) fn evaluate_condition(
     condition: &Condition,
-    waveform: &mut wellen::simple::Waveform,
-    signal_cache: &std::collections::HashMap<String, wellen::VarRef>,
-    time_idx: usize,
-) -> Result<BigUint, String> {
-    let (value, _width) =
-        evaluate_condition_with_width(condition, waveform, signal_cache, time_idx)?;
-    Ok(value)
+    ctx: &EvalContext,
+    sample_idx: usize,
+) -> Result<Value, String> {
+    let (width, signed) = self_determined_sizing(condition, ctx)?;
+    evaluate_condition_with_width(condition, ctx, sample_idx, width, signed)
+}
+
+/// The context width and signedness an expression is evaluated with on its own.
+pub(super) fn self_determined_sizing(
+    condition: &Condition,
+    ctx: &EvalContext,
+) -> Result<(u32, bool), String> {
+    let width = expression_width(condition, ctx.waveform.hierarchy(), ctx.signal_cache)?;
+    Ok((width, expression_signed(condition)))
 }
 
-/// Evaluate a condition at a specific time index, returning both value and bit width.
+/// Evaluate a condition at a specific time index, returning a value with its bit width.
 ///
-/// # Returns
-/// A tuple of (value, bit_width) where bit_width is the bit width of the value.
+/// Following Verilog expression sizing rules, arithmetic and bitwise operators are
+/// context-determined: their operands are extended to `context_width` bits before the
+/// operation, so that e.g. the carry of `a + b` is kept when compared against a wider
+/// value. Pass 0 to evaluate an expression with its self-determined width.
+///
+/// The context is `signed` only if every context-determined operand is signed; operands
+/// narrower than a signed context are sign-extended, and division, remainder, relational
+/// comparison and `>>>` use two's complement semantics.
+///
+/// X and Z bits propagate through operators following IEEE 1800 rules.
 fn evaluate_condition_with_width(
     condition: &Condition,
-    waveform: &mut wellen::simple::Waveform,
-    signal_cache: &std::collections::HashMap<String, wellen::VarRef>,
-    time_idx: usize,
-) -> Result<(BigUint, u32), String> {
+    ctx: &EvalContext,
+    sample_idx: usize,
+    context_width: u32,
+    signed: bool,
+) -> Result<Value, String> {
+    let value = evaluate_operator(condition, ctx, sample_idx, context_width, signed)?;
+    Ok(if signed {
+        value.sign_extend(context_width)
+    } else {
+        value
+    })
+}
+
+/// Evaluate the top-level operator of a condition for `evaluate_condition_with_width`.
+pub(super) fn evaluate_operator(
+    condition: &Condition,
+    ctx: &EvalContext,
+    sample_idx: usize,
+    context_width: u32,
+    signed: bool,
+) -> Result<Value, String> {
     match condition {
         Condition::And(left, right) => {
-            let left_val = evaluate_condition(left, waveform, signal_cache, time_idx)?;
-            let right_val = evaluate_condition(right, waveform, signal_cache, time_idx)?;
-            Ok((
-                if !left_val.is_zero() && !right_val.is_zero() {
-                    BigUint::from(1u32)
-                } else {
-                    BigUint::from(0u32)
+            let left_val = evaluate_condition(left, ctx, sample_idx)?;
+            let right_val = evaluate_condition(right, ctx, sample_idx)?;
+            // A known false operand makes the result false even if the other is unknown
+            Ok(Value::from_truth(
+                match (left_val.truth(), right_val.truth()) {
+                    (Some(false), _) | (_, Some(false)) => Some(false),
+                    (Some(true), Some(true)) => Some(true),
+                    _ => None,
                 },
-                1, // Logical operations return 1-bit result
             ))
         }
         Condition::Or(left, right) => {
-            let left_val = evaluate_condition(left, waveform, signal_cache, time_idx)?;
-            let right_val = evaluate_condition(right, waveform, signal_cache, time_idx)?;
-            Ok((
-                if !left_val.is_zero() || !right_val.is_zero() {
-                    BigUint::from(1u32)
-                } else {
-                    BigUint::from(0u32)
+            let left_val = evaluate_condition(left, ctx, sample_idx)?;
+            let right_val = evaluate_condition(right, ctx, sample_idx)?;
+            // A known true operand makes the result true even if the other is unknown
+            Ok(Value::from_truth(
+                match (left_val.truth(), right_val.truth()) {
+                    (Some(true), _) | (_, Some(true)) => Some(true),
+                    (Some(false), Some(false)) => Some(false),
+                    _ => None,
                 },
-                1, // Logical operations return 1-bit result
             ))
         }
-        Condition::BitwiseAnd(left, right) => {
-            let (left_val, left_width) =
-                evaluate_condition_with_width(left, waveform, signal_cache, time_idx)?;
-            let (right_val, right_width) =
-                evaluate_condition_with_width(right, waveform, signal_cache, time_idx)?;
-            let width = left_width.max(right_width);
-            Ok((left_val.bitand(right_val), width))
+        Condition::BitwiseAnd(left, right)
+        | Condition::BitwiseOr(left, right)
+        | Condition::BitwiseXor(left, right) => {
+            let left_val =
+                evaluate_condition_with_width(left, ctx, sample_idx, context_width, signed)?;
+            let right_val =
+                evaluate_condition_with_width(right, ctx, sample_idx, context_width, signed)?;
+            Ok(match condition {
+                Condition::BitwiseAnd(_, _) => left_val.bitand(&right_val),
+                Condition::BitwiseOr(_, _) => left_val.bitor(&right_val),
+                _ => left_val.bitxor(&right_val),
+            })
         }
-        Condition::BitwiseOr(left, right) => {
-            let (left_val, left_width) =
-                evaluate_condition_with_width(left, waveform, signal_cache, time_idx)?;
-            let (right_val, right_width) =
-                evaluate_condition_with_width(right, waveform, signal_cache, time_idx)?;
-            let width = left_width.max(right_width);
-            Ok((left_val.bitor(right_val), width))
-        }
-        Condition::BitwiseXor(left, right) => {
-            let (left_val, left_width) =
-                evaluate_condition_with_width(left, waveform, signal_cache, time_idx)?;
-            let (right_val, right_width) =
-                evaluate_condition_with_width(right, waveform, signal_cache, time_idx)?;
-            let width = left_width.max(right_width);
-            Ok((left_val.bitxor(right_val), width))
+        Condition::Ternary(cond, if_true, if_false) => {
+            // The condition is self-determined, the values are context-determined
+            let value = match evaluate_condition(cond, ctx, sample_idx)?.truth() {
+                Some(true) => {
+                    evaluate_condition_with_width(if_true, ctx, sample_idx, context_width, signed)?
+                }
+                Some(false) => {
+                    evaluate_condition_with_width(if_false, ctx, sample_idx, context_width, signed)?
+                }
+                None => {
+                    let true_val = evaluate_condition_with_width(
+                        if_true,
+                        ctx,
+                        sample_idx,
+                        context_width,
+                        signed,
+                    )?;
+                    let false_val = evaluate_condition_with_width(
+                        if_false,
+                        ctx,
+                        sample_idx,
+                        context_width,
+                        signed,
+                    )?;
+                    true_val.merge(&false_val)
+                }
+            };
+            // Both values have the width of the wider one; signed values are extended by
+            // the caller
+            Ok(if signed {
+                value
+            } else {
+                value.resize(value.width.max(context_width))
+            })
         }
         Condition::Not(expr) => {
-            let val = evaluate_condition(expr, waveform, signal_cache, time_idx)?;
-            Ok((
-                if !val.is_zero() {
-                    BigUint::from(0u32)
-                } else {
-                    BigUint::from(1u32)
+            let val = evaluate_condition(expr, ctx, sample_idx)?;
+            Ok(Value::from_truth(val.truth().map(|truth| !truth)))
+        }
+        Condition::BitwiseNot(expr) => {
+            let val = evaluate_condition_with_width(expr, ctx, sample_idx, context_width, signed)?;
+            // The operand is extended to the context width before inversion
+            Ok(val.bitnot(context_width))
+        }
+        Condition::Neg(expr) => {
+            let val = evaluate_condition_with_width(expr, ctx, sample_idx, context_width, signed)?;
+            // Two's complement negation wraps around modulo 2^width
+            Ok(val.negate(context_width))
+        }
+        Condition::Signed(expr) | Condition::Unsigned(expr) => {
+            // The cast operand is self-determined; only the interpretation of its bits changes
+            evaluate_condition(expr, ctx, sample_idx)
+        }
+        Condition::Scoped(_, expr) => {
+            evaluate_condition_with_width(expr, ctx, sample_idx, context_width, signed)
+        }
+        Condition::Add(left, right)
+        | Condition::Sub(left, right)
+        | Condition::Mul(left, right)
+        | Condition::Div(left, right)
+        | Condition::Mod(left, right) => {
+            let left_val =
+                evaluate_condition_with_width(left, ctx, sample_idx, context_width, signed)?;
+            let right_val =
+                evaluate_condition_with_width(right, ctx, sample_idx, context_width, signed)?;
+            let op = condition.arith_op().ok_or("Not an arithmetic operator")?;
+            Ok(left_val.arithmetic(op, &right_val, context_width, signed))
+        }
+        Condition::ShiftLeft(left, right)
+        | Condition::ShiftRight(left, right)
+        | Condition::ArithShiftRight(left, right) => {
+            // The shifted operand is context-determined, the shift amount is self-determined
+            let left_val =
+                evaluate_condition_with_width(left, ctx, sample_idx, context_width, signed)?;
+            let amount = evaluate_condition(right, ctx, sample_idx)?;
+            let op = condition.shift_op().ok_or("Not a shift operator")?;
+            Ok(left_val.shift(op, amount.shift_amount(), context_width, signed))
+        }
+        Condition::Concat(items) => evaluate_concatenation(items, ctx, sample_idx),
+        Condition::Replicate(count, items) => {
+            let item_val = evaluate_concatenation(items, ctx, sample_idx)?;
+            replication_width(*count, item_val.width)?;
+            Ok(item_val.replicate(*count))
+        }
+        Condition::Signal(path) => read_signal_value(path, ctx, sample_idx),
+        Condition::Select(expr, selection) => {
+            // The selected expression is self-determined
+            let value = evaluate_condition(expr, ctx, sample_idx)?;
+            let declared = declared_range(expr, ctx.waveform.hierarchy(), ctx.signal_cache)?;
+            let (first, last) = match selection {
+                Selection::Bit(index) => match evaluate_index(index, ctx, sample_idx)? {
+                    Some(index) => (index, index),
+                    // An unknown index selects X
+                    None => return Ok(Value::all_x(1)),
                 },
-                1, // Logical NOT returns 1-bit result
+                Selection::Range(msb, lsb) => {
+                    check_range_direction(*msb, *lsb, declared)?;
+                    (*msb as i64, *lsb as i64)
+                }
+                Selection::Indexed {
+                    base,
+                    width,
+                    ascending,
+                } => {
+                    check_indexed_width(*width)?;
+                    match evaluate_index(base, ctx, sample_idx)? {
+                        Some(base) if *ascending => (base, base.saturating_add(*width as i64 - 1)),
+                        Some(base) => (base.saturating_sub(*width as i64 - 1), base),
+                        None => return Ok(Value::all_x(*width)),
+                    }
+                }
+            };
+            let (first, last) = (bit_offset(first, declared), bit_offset(last, declared));
+            let num_bits = part_select_width(first, last)?;
+            Ok(value.select(first.min(last), num_bits))
+        }
+        Condition::Eq(left, right)
+        | Condition::Neq(left, right)
+        | Condition::CaseEq(left, right)
+        | Condition::CaseNeq(left, right)
+        | Condition::WildcardEq(left, right)
+        | Condition::WildcardNeq(left, right)
+        | Condition::Lt(left, right)
+        | Condition::Le(left, right)
+        | Condition::Gt(left, right)
+        | Condition::Ge(left, right) => {
+            let hierarchy = ctx.waveform.hierarchy();
+            let op = condition.compare_op().ok_or("Not a comparison operator")?;
+            let value_type = comparison_type(left, right, hierarchy, ctx.signal_cache)?;
+            if value_type != ValueType::Bits {
+                let left_val = evaluate_typed(left, value_type, ctx, sample_idx)?;
+                let right_val = evaluate_typed(right, value_type, ctx, sample_idx)?;
+                let (Some(left_val), Some(right_val)) = (left_val, right_val) else {
+                    return Ok(Value::all_x(1));
+                };
+                // Reals and strings have no X or Z bits, so case equality is plain equality
+                let result = match op {
+                    CompareOp::Eq | CompareOp::CaseEq => left_val == right_val,
+                    CompareOp::Neq | CompareOp::CaseNeq => left_val != right_val,
+                    CompareOp::Lt => left_val < right_val,
+                    CompareOp::Le => left_val <= right_val,
+                    CompareOp::Gt => left_val > right_val,
+                    CompareOp::Ge => left_val >= right_val,
+                    CompareOp::WildcardEq | CompareOp::WildcardNeq => {
+                        return Err(
+                            "Wildcard equality cannot be used with real or string values"
+                                .to_string(),
+                        )
+                    }
+                };
+                return Ok(Value::from_bool(result));
+            }
+
+            // Both operands are sized to the wider of their self-determined widths, and
+            // compared as signed only if both are signed
+            let operand_width = expression_width(left, hierarchy, ctx.signal_cache)?
+                .max(expression_width(right, hierarchy, ctx.signal_cache)?);
+            let operand_signed = expression_signed(left) && expression_signed(right);
+            let left_val = evaluate_condition_with_width(
+                left,
+                ctx,
+                sample_idx,
+                operand_width,
+                operand_signed,
+            )?;
+            let right_val = evaluate_condition_with_width(
+                right,
+                ctx,
+                sample_idx,
+                operand_width,
+                operand_signed,
+            )?;
+            // Comparison operations return 1-bit result
+            Ok(Value::from_truth(left_val.compare(
+                op,
+                &right_val,
+                operand_signed,
+            )))
+        }
+        Condition::Literal(Literal::Fill(bit)) => {
+            // Fill literals repeat their bit over the whole context
+            let width = context_width.max(1) as usize;
+            Ok(Value::from_bit_string(&bit.to_string().repeat(width)))
+        }
+        Condition::Literal(literal) => literal_to_value(literal),
+        // Clocked samples are reported at the clock edge, so that is their time
+        Condition::Time => Ok(Value::known(
+            BigUint::from(ctx.waveform.time_table()[ctx.report_idx(sample_idx)]),
+            64,
+        )),
+        Condition::Past(expr, depth) => {
+            evaluate_past(expr, *depth, ctx, sample_idx, context_width, signed)
+        }
+        Condition::Stable(expr) | Condition::Changed(expr)
+            if expression_type(expr, ctx.waveform.hierarchy(), ctx.signal_cache)?
+                != ValueType::Bits =>
+        {
+            let value_type = expression_type(expr, ctx.waveform.hierarchy(), ctx.signal_cache)?;
+            let current = evaluate_typed(expr, value_type, ctx, sample_idx)?;
+            let previous = evaluate_typed(
+                &Condition::Past(Box::new(expr.as_ref().clone()), 1),
+                value_type,
+                ctx,
+                sample_idx,
+            )?;
+            let stable = current == previous;
+            Ok(Value::from_bool(match condition {
+                Condition::Stable(_) => stable,
+                _ => !stable,
+            }))
+        }
+        Condition::Rose(expr)
+        | Condition::Fell(expr)
+        | Condition::Stable(expr)
+        | Condition::Changed(expr) => {
+            let (width, signed) = self_determined_sizing(expr, ctx)?;
+            let current = evaluate_condition_with_width(expr, ctx, sample_idx, width, signed)?;
+            let previous = evaluate_past(expr, 1, ctx, sample_idx, width, signed)?;
+            let function = condition
+                .sampled_function()
+                .ok_or("Not a sampled value function")?;
+            Ok(Value::from_bool(
+                current.sampled_function(function, &previous),
             ))
         }
-        Condition::BitwiseNot(expr) => {
-            let (val, width) =
-                evaluate_condition_with_width(expr, waveform, signal_cache, time_idx)?;
-            // Create a mask with all bits set for the given width
-            let mask = (BigUint::from(1u32) << width) - BigUint::from(1u32);
-            // Bitwise NOT is value XOR mask
-            Ok((val.bitxor(mask), width))
+        Condition::ReduceAnd(expr) | Condition::ReduceOr(expr) | Condition::ReduceXor(expr) => {
+            // Reduction operands are self-determined
+            let val = evaluate_condition(expr, ctx, sample_idx)?;
+            Ok(match condition {
+                Condition::ReduceAnd(_) => val.reduce_and(),
+                Condition::ReduceOr(_) => val.reduce_or(),
+                _ => val.reduce_xor(),
+            })
+        }
+        Condition::CountOnes(expr) => {
+            let val = evaluate_condition(expr, ctx, sample_idx)?;
+            // Like SystemVerilog, $countones returns a 32-bit int
+            Ok(Value::known(BigUint::from(val.count_ones()), 32))
+        }
+        Condition::Abs(_) => Err(
+            "$abs returns a real value, which can only be compared or used in real arithmetic"
+                .to_string(),
+        ),
+        Condition::Approx(left, right, tolerance) => {
+            let mut values = Vec::new();
+            for expr in [left, right, tolerance] {
+                match evaluate_typed(expr, ValueType::Real, ctx, sample_idx)? {
+                    Some(TypedValue::Real(value)) => values.push(value),
+                    _ => return Ok(Value::all_x(1)),
+                }
+            }
+            Ok(Value::from_bool((values[0] - values[1]).abs() <= values[2]))
+        }
+        Condition::Contains(text, part) => {
+            let hierarchy = ctx.waveform.hierarchy();
+            for expr in [text, part] {
+                if expression_type(expr, hierarchy, ctx.signal_cache)? != ValueType::String {
+                    return Err("$contains needs two string values".to_string());
+                }
+            }
+            let text = evaluate_typed(text, ValueType::String, ctx, sample_idx)?;
+            let part = evaluate_typed(part, ValueType::String, ctx, sample_idx)?;
+            Ok(Value::from_truth(match (text, part) {
+                (Some(TypedValue::String(text)), Some(TypedValue::String(part))) => {
+                    Some(text.contains(&part))
+                }
+                _ => None,
+            }))
+        }
+        Condition::OneHot(expr) | Condition::OneHot0(expr) | Condition::IsUnknown(expr) => {
+            let val = evaluate_condition(expr, ctx, sample_idx)?;
+            // X and Z bits are not counted as ones
+            let result = match condition {
+                Condition::OneHot(_) => val.count_ones() == 1,
+                Condition::OneHot0(_) => val.count_ones() <= 1,
+                _ => val.has_unknown(),
+            };
+            Ok(Value::from_bool(result))
         }
+    }
+}
+
+/// Evaluate an expression `depth` samples before `sample_idx`.
+///
+/// Before the first sample there is no past value, and the result is 0.
+fn evaluate_past(
+    expr: &Condition,
+    depth: u32,
+    ctx: &EvalContext,
+    sample_idx: usize,
+    context_width: u32,
+    signed: bool,
+) -> Result<Value, String> {
+    if depth == 0 {
+        return Err("$past depth must be at least 1".to_string());
+    }
+    match sample_idx.checked_sub(depth as usize) {
+        Some(past_idx) => evaluate_condition_with_width(expr, ctx, past_idx, context_width, signed),
+        // Return false (0) when there's no past
+        None => Ok(Value::from_bool(false)),
+    }
+}
+
+/// Evaluate the items of a concatenation, placing the first item in the most significant bits.
+fn evaluate_concatenation(
+    items: &[Condition],
+    ctx: &EvalContext,
+    sample_idx: usize,
+) -> Result<Value, String> {
+    let mut value = Value::known(BigUint::zero(), 0);
+    for item in items {
+        // Concatenation operands are self-determined
+        let item_val = evaluate_condition(item, ctx, sample_idx)?;
+        value = value.concat(&item_val);
+    }
+    Ok(value)
+}
+
+/// The declared `(msb, lsb)` indices of an expression that is part-selected.
+///
+/// Signals use the range they are declared with, which may be ascending or start at
+/// a non-zero index; any other expression is indexed `[width-1:0]`.
+pub(super) fn declared_range(
+    expr: &Condition,
+    hierarchy: &wellen::Hierarchy,
+    signal_cache: &std::collections::HashMap<String, wellen::VarRef>,
+) -> Result<(i64, i64), String> {
+    match expr {
         Condition::Signal(path) => {
-            // Get var ref from cache
             let var_ref = signal_cache
                 .get(path)
                 .ok_or_else(|| format!("Signal not found in cache: {}", path))?;
+            if let Some(index) = hierarchy[*var_ref].index() {
+                return Ok((index.msb(), index.lsb()));
+            }
+        }
+        Condition::Past(expr, _) => return declared_range(expr, hierarchy, signal_cache),
+        _ => {}
+    }
+    let width = expression_width(expr, hierarchy, signal_cache)?;
+    Ok((width as i64 - 1, 0))
+}
 
-            // Get signal width from hierarchy
-            let hierarchy = waveform.hierarchy();
-            let width = hierarchy[*var_ref]
-                .length()
-                .ok_or_else(|| format!("Signal {} has no width (string/real type)", path))?;
+/// Position of the bit with the given index, counting from the least significant bit.
+pub(super) fn bit_offset(index: i64, (msb, lsb): (i64, i64)) -> i64 {
+    if msb >= lsb {
+        index.saturating_sub(lsb)
+    } else {
+        lsb.saturating_sub(index)
+    }
+}
 
-            // Get signal from waveform
-            let signal = waveform
-                .get_signal(hierarchy[*var_ref].signal_ref())
-                .ok_or_else(|| format!("Signal not found in waveform: {}", path))?;
+/// Check that a `[msb:lsb]` part-select runs in the same direction as the declared range.
+pub(super) fn check_range_direction(
+    msb: u32,
+    lsb: u32,
+    (declared_msb, declared_lsb): (i64, i64),
+) -> Result<(), String> {
+    let descending = declared_msb >= declared_lsb;
+    if msb != lsb && (msb > lsb) != descending {
+        return Err(format!(
+            "Invalid bit range [{}:{}] - msb must be {} lsb for a value declared [{}:{}]",
+            msb,
+            lsb,
+            if descending { ">=" } else { "<=" },
+            declared_msb,
+            declared_lsb
+        ));
+    }
+    Ok(())
+}
 
-            // Get value at time index
-            let time_table_idx: wellen::TimeTableIdx = time_idx
-                .try_into()
-                .map_err(|_| format!("Time index {} too large", time_idx))?;
+pub(super) fn check_indexed_width(width: u32) -> Result<(), String> {
+    if width == 0 {
+        return Err("Width of an indexed part-select must be at least 1".to_string());
+    }
+    if width > MAX_WIDTH {
+        return Err(format!(
+            "Width of an indexed part-select must be at most {}",
+            MAX_WIDTH
+        ));
+    }
+    Ok(())
+}
 
-            let offset = signal
-                .get_offset(time_table_idx)
-                .ok_or_else(|| format!("No data for signal {} at time index {}", path, time_idx))?;
+/// The widest value a literal, part-select or replication may produce, in bits.
+pub(super) const MAX_WIDTH: u32 = 1 << 20;
 
-            let signal_value = signal.get_value_at(&offset, 0);
-            let value = signal_value_to_biguint(signal_value)?;
-            Ok((value, width))
-        }
-        Condition::BitExtract(path, msb, lsb) => {
-            // Get var ref from cache
-            let var_ref = signal_cache
-                .get(path)
-                .ok_or_else(|| format!("Signal not found in cache: {}", path))?;
+/// The number of bits of a part-select from bit offset `first` to bit offset `last`.
+pub(super) fn part_select_width(first: i64, last: i64) -> Result<u32, String> {
+    u32::try_from(first.abs_diff(last).saturating_add(1))
+        .ok()
+        .filter(|width| *width <= MAX_WIDTH)
+        .ok_or_else(|| "Part-select is too wide".to_string())
+}
 
-            // Get signal width from hierarchy
-            let hierarchy = waveform.hierarchy();
-            let full_width = hierarchy[*var_ref]
-                .length()
-                .ok_or_else(|| format!("Signal {} has no width (string/real type)", path))?;
+/// The width of `count` copies of a value `width` bits wide.
+pub(super) fn replication_width(count: u32, width: u32) -> Result<u32, String> {
+    if count == 0 {
+        return Err("Replication count must be greater than zero".to_string());
+    }
+    count
+        .checked_mul(width)
+        .filter(|width| *width <= MAX_WIDTH)
+        .ok_or_else(|| "Replication is too wide".to_string())
+}
 
-            // Get signal from waveform
-            let signal = waveform
-                .get_signal(hierarchy[*var_ref].signal_ref())
-                .ok_or_else(|| format!("Signal not found in waveform: {}", path))?;
+/// Evaluate a bit index or part-select base, returning `None` if it has X or Z bits.
+fn evaluate_index(
+    index: &Condition,
+    ctx: &EvalContext,
+    sample_idx: usize,
+) -> Result<Option<i64>, String> {
+    let value = evaluate_condition(index, ctx, sample_idx)?;
+    if value.has_unknown() {
+        return Ok(None);
+    }
+    // Indices too large for an i64 are far out of range; treat them like X
+    Ok(if expression_signed(index) {
+        value.to_signed().to_i64()
+    } else {
+        value.value.to_i64()
+    })
+}
+
+/// Read the value of a signal at a sample.
+fn read_signal_value(path: &str, ctx: &EvalContext, sample_idx: usize) -> Result<Value, String> {
+    // Get signal width from hierarchy
+    let hierarchy = ctx.waveform.hierarchy();
+    let width = hierarchy[signal_var(path, ctx)?]
+        .length()
+        .ok_or_else(|| format!("Signal {} has no width", path))?;
+
+    signal_value_to_value(read_raw_signal_value(path, ctx, sample_idx)?, width)
+}
 
-            // Get value at time index
-            let time_table_idx: wellen::TimeTableIdx = time_idx
-                .try_into()
-                .map_err(|_| format!("Time index {} too large", time_idx))?;
+/// Look up the variable of a loaded signal.
+fn signal_var(path: &str, ctx: &EvalContext) -> Result<wellen::VarRef, String> {
+    ctx.signal_cache
+        .get(path)
+        .copied()
+        .ok_or_else(|| format!("Signal not found in cache: {}", path))
+}
 
-            let offset = signal
-                .get_offset(time_table_idx)
-                .ok_or_else(|| format!("No data for signal {} at time index {}", path, time_idx))?;
+/// Read the value of a signal at a sample as stored in the waveform.
+fn read_raw_signal_value<'a>(
+    path: &str,
+    ctx: &EvalContext<'a>,
+    sample_idx: usize,
+) -> Result<wellen::SignalValue<'a>, String> {
+    let hierarchy = ctx.waveform.hierarchy();
+    let var_ref = signal_var(path, ctx)?;
 
-            let signal_value = signal.get_value_at(&offset, 0);
-            let full_value = signal_value_to_biguint(signal_value)?;
+    // Get signal from waveform
+    let signal = ctx
+        .waveform
+        .get_signal(hierarchy[var_ref].signal_ref())
+        .ok_or_else(|| format!("Signal not found in waveform: {}", path))?;
 
-            // Extract the specified bits and determine the result width
-            let (result, width) = match (msb, lsb) {
-                (Some(msb), Some(lsb)) => {
-                    if msb < lsb {
-                        return Err(format!(
-                            "Invalid bit range [{}:{}] - msb must be >= lsb",
-                            msb, lsb
-                        ));
-                    }
-                    // Extract bits [msb:lsb] by:
-                    // 1. Shift right by lsb positions
-                    // 2. Create a mask with (msb - lsb + 1) bits set
-                    let num_bits = msb - lsb + 1;
-                    let shifted = full_value >> lsb;
-                    let mask = (BigUint::from(1u32) << num_bits) - BigUint::from(1u32);
-                    (shifted & mask, num_bits)
-                }
-                _ => (full_value, full_width), // No bit extraction needed
-            };
-            Ok((result, width))
-        }
-        Condition::Eq(left, right) => {
-            let left_val = evaluate_condition(left, waveform, signal_cache, time_idx)?;
-            let right_val = evaluate_condition(right, waveform, signal_cache, time_idx)?;
-            Ok((
-                if left_val == right_val {
-                    BigUint::from(1u32)
-                } else {
-                    BigUint::from(0u32)
-                },
-                1, // Comparison operations return 1-bit result
-            ))
+    // Get value at time index
+    let time_idx = ctx.time_idx(sample_idx);
+    let time_table_idx: wellen::TimeTableIdx = time_idx
+        .try_into()
+        .map_err(|_| format!("Time index {} too large", time_idx))?;
+
+    let offset = signal
+        .get_offset(time_table_idx)
+        .ok_or_else(|| format!("No data for signal {} at time index {}", path, time_idx))?;
+
+    Ok(signal.get_value_at(&offset, 0))
+}
+
+/// The kind of value an expression evaluates to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum ValueType {
+    /// A four-state bit vector, evaluated by `evaluate_condition`
+    Bits,
+    /// A real number, from real signals and literals such as `1.2`
+    Real,
+    /// A string, from string signals and literals such as `"RESET"`
+    String,
+}
+
+/// A real or string value, evaluated by `evaluate_typed`.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+enum TypedValue {
+    Real(f64),
+    String(String),
+}
+
+/// Determine the kind of value an expression evaluates to without evaluating it.
+///
+/// Like SystemVerilog, arithmetic with a real operand is real arithmetic. Only
+/// signals, literals, `$past` and `?:` can be strings.
+pub(super) fn expression_type(
+    condition: &Condition,
+    hierarchy: &wellen::Hierarchy,
+    signal_cache: &std::collections::HashMap<String, wellen::VarRef>,
+) -> Result<ValueType, String> {
+    let either_real = |left: &Condition, right: &Condition| -> Result<ValueType, String> {
+        let real = expression_type(left, hierarchy, signal_cache)? == ValueType::Real
+            || expression_type(right, hierarchy, signal_cache)? == ValueType::Real;
+        Ok(if real {
+            ValueType::Real
+        } else {
+            ValueType::Bits
+        })
+    };
+    match condition {
+        Condition::Signal(path) => {
+            let var_ref = signal_cache
+                .get(path)
+                .ok_or_else(|| format!("Signal not found in cache: {}", path))?;
+            Ok(match hierarchy[*var_ref].signal_encoding() {
+                wellen::SignalEncoding::Real => ValueType::Real,
+                wellen::SignalEncoding::String => ValueType::String,
+                _ => ValueType::Bits,
+            })
         }
-        Condition::Neq(left, right) => {
-            let left_val = evaluate_condition(left, waveform, signal_cache, time_idx)?;
-            let right_val = evaluate_condition(right, waveform, signal_cache, time_idx)?;
-            Ok((
-                if left_val != right_val {
-                    BigUint::from(1u32)
-                } else {
-                    BigUint::from(0u32)
-                },
-                1, // Comparison operations return 1-bit result
-            ))
+        Condition::Literal(Literal::Real(_)) | Condition::Abs(_) => Ok(ValueType::Real),
+        Condition::Literal(Literal::String(_)) => Ok(ValueType::String),
+        Condition::Add(left, right)
+        | Condition::Sub(left, right)
+        | Condition::Mul(left, right)
+        | Condition::Div(left, right) => either_real(left, right),
+        Condition::Neg(expr) | Condition::Past(expr, _) | Condition::Scoped(_, expr) => {
+            expression_type(expr, hierarchy, signal_cache)
         }
-        Condition::Literal(literal) => literal_to_biguint(literal),
-        Condition::Past(expr) => {
-            // If at time 0, there's no previous value
-            if time_idx == 0 {
-                return Ok((BigUint::from(0u32), 1)); // Return false (0) when there's no past
+        Condition::Ternary(_, if_true, if_false) => {
+            let types = (
+                expression_type(if_true, hierarchy, signal_cache)?,
+                expression_type(if_false, hierarchy, signal_cache)?,
+            );
+            match types {
+                (ValueType::String, ValueType::String) => Ok(ValueType::String),
+                _ => either_real(if_true, if_false),
             }
-            // Evaluate expression at previous time index
-            evaluate_condition_with_width(expr, waveform, signal_cache, time_idx - 1)
         }
+        _ => Ok(ValueType::Bits),
     }
 }
 
-/// Convert a signal value to BigUint for comparison.
-fn signal_value_to_biguint(signal_value: wellen::SignalValue) -> Result<BigUint, String> {
-    match signal_value {
-        wellen::SignalValue::Binary(data, _) => {
-            // wellen stores the value as a Vec<u32> where each element is a chunk of bits
-            // We need to combine these chunks into a single BigUint
-            let mut value = BigUint::from(0u32);
-            for (i, &chunk) in data.iter().enumerate() {
-                if chunk != 0 {
-                    // Each chunk is at position i * 32 bits
-                    let chunk_value = BigUint::from(chunk);
-                    value |= chunk_value << (i * 32);
-                }
+/// Determine how the operands of a comparison are compared.
+///
+/// A string literal compared against a bit vector is a bit vector of its characters,
+/// as in Verilog; other strings can only be compared against strings.
+pub(super) fn comparison_type(
+    left: &Condition,
+    right: &Condition,
+    hierarchy: &wellen::Hierarchy,
+    signal_cache: &std::collections::HashMap<String, wellen::VarRef>,
+) -> Result<ValueType, String> {
+    let is_string_literal =
+        |condition: &Condition| matches!(condition, Condition::Literal(Literal::String(_)));
+    match (
+        expression_type(left, hierarchy, signal_cache)?,
+        expression_type(right, hierarchy, signal_cache)?,
+    ) {
+        (ValueType::String, ValueType::String) => Ok(ValueType::String),
+        (ValueType::String, ValueType::Bits) if is_string_literal(left) => Ok(ValueType::Bits),
+        (ValueType::Bits, ValueType::String) if is_string_literal(right) => Ok(ValueType::Bits),
+        (ValueType::String, _) | (_, ValueType::String) => {
+            Err("A string can only be compared against another string".to_string())
+        }
+        (ValueType::Real, _) | (_, ValueType::Real) => Ok(ValueType::Real),
+        _ => Ok(ValueType::Bits),
+    }
+}
+
+/// Evaluate an expression as a real number or string, as chosen by `value_type`.
+///
+/// Bit vectors are converted to real numbers, as signed numbers if the expression is
+/// signed. Returns `None` if the value is unknown because it depends on X or Z bits.
+fn evaluate_typed(
+    condition: &Condition,
+    value_type: ValueType,
+    ctx: &EvalContext,
+    sample_idx: usize,
+) -> Result<Option<TypedValue>, String> {
+    let hierarchy = ctx.waveform.hierarchy();
+    let own_type = expression_type(condition, hierarchy, ctx.signal_cache)?;
+    if own_type == ValueType::Bits {
+        if value_type == ValueType::String {
+            return Err("Expected a string value".to_string());
+        }
+        let value = evaluate_condition(condition, ctx, sample_idx)?;
+        if value.has_unknown() {
+            return Ok(None);
+        }
+        let real = if expression_signed(condition) {
+            value.to_signed().to_f64()
+        } else {
+            value.value.to_f64()
+        };
+        return Ok(real.map(TypedValue::Real));
+    }
+    match (own_type, value_type) {
+        (ValueType::String, ValueType::Real) => {
+            return Err("A string cannot be used as a real number".to_string())
+        }
+        (ValueType::Real, ValueType::String) => {
+            return Err("A real number cannot be used as a string".to_string())
+        }
+        _ => {}
+    }
+
+    let real = |condition: &Condition| -> Result<Option<f64>, String> {
+        Ok(
+            match evaluate_typed(condition, ValueType::Real, ctx, sample_idx)? {
+                Some(TypedValue::Real(value)) => Some(value),
+                _ => None,
+            },
+        )
+    };
+    match condition {
+        Condition::Literal(Literal::Real(value)) => Ok(Some(TypedValue::Real(*value))),
+        Condition::Literal(Literal::String(text)) => Ok(Some(TypedValue::String(text.clone()))),
+        Condition::Signal(path) => match read_raw_signal_value(path, ctx, sample_idx)? {
+            wellen::SignalValue::Real(value) => Ok(Some(TypedValue::Real(value))),
+            wellen::SignalValue::String(text) => Ok(Some(TypedValue::String(text.to_string()))),
+            _ => Err(format!("Signal {} has no real or string value", path)),
+        },
+        Condition::Add(left, right)
+        | Condition::Sub(left, right)
+        | Condition::Mul(left, right)
+        | Condition::Div(left, right) => {
+            let (Some(left), Some(right)) = (real(left)?, real(right)?) else {
+                return Ok(None);
+            };
+            let result = match condition {
+                Condition::Add(_, _) => left + right,
+                Condition::Sub(_, _) => left - right,
+                Condition::Mul(_, _) => left * right,
+                _ => left / right,
+            };
+            Ok(Some(TypedValue::Real(result)))
+        }
+        Condition::Neg(expr) => Ok(real(expr)?.map(|value| TypedValue::Real(-value))),
+        Condition::Abs(expr) => Ok(real(expr)?.map(|value| TypedValue::Real(value.abs()))),
+        Condition::Past(expr, depth) => {
+            if *depth == 0 {
+                return Err("$past depth must be at least 1".to_string());
             }
-            Ok(value)
-        }
-        wellen::SignalValue::FourValue(data, _) => {
-            // Same as Binary for FourValue
-            let mut value = BigUint::from(0u32);
-            for (i, &chunk) in data.iter().enumerate() {
-                if chunk != 0 {
-                    let chunk_value = BigUint::from(chunk);
-                    value |= chunk_value << (i * 32);
+            match sample_idx.checked_sub(*depth as usize) {
+                Some(past_idx) => evaluate_typed(expr, value_type, ctx, past_idx),
+                // Like bit vectors, values before the first sample are zero or empty
+                None if own_type == ValueType::String => {
+                    Ok(Some(TypedValue::String(String::new())))
                 }
+                None => Ok(Some(TypedValue::Real(0.0))),
             }
-            Ok(value)
-        }
-        wellen::SignalValue::NineValue(data, _) => {
-            // Same as Binary for NineValue
-            let mut value = BigUint::from(0u32);
-            for (i, &chunk) in data.iter().enumerate() {
-                if chunk != 0 {
-                    let chunk_value = BigUint::from(chunk);
-                    value |= chunk_value << (i * 32);
+        }
+        Condition::Scoped(_, expr) => evaluate_typed(expr, value_type, ctx, sample_idx),
+        Condition::Ternary(cond, if_true, if_false) => {
+            match evaluate_condition(cond, ctx, sample_idx)?.truth() {
+                Some(true) => evaluate_typed(if_true, own_type, ctx, sample_idx),
+                Some(false) => evaluate_typed(if_false, own_type, ctx, sample_idx),
+                // An unknown condition gives a known value only if both values are the same
+                None => {
+                    let if_true = evaluate_typed(if_true, own_type, ctx, sample_idx)?;
+                    let if_false = evaluate_typed(if_false, own_type, ctx, sample_idx)?;
+                    Ok(if_true.filter(|value| Some(value) == if_false.as_ref()))
                 }
             }
-            Ok(value)
-        }
-        wellen::SignalValue::String(s) => {
-            // Handle special string values for boolean context
-            if s == "1" || s.eq_ignore_ascii_case("true") {
-                Ok(BigUint::from(1u32))
-            } else if s == "0" || s.eq_ignore_ascii_case("false") {
-                Ok(BigUint::from(0u32))
-            } else {
-                s.parse::<u64>()
-                    .map(BigUint::from)
-                    .map_err(|_| format!("Cannot convert string '{}' to integer", s))
+        }
+        _ => Err("Expression has no real or string value".to_string()),
+    }
+}
+
+/// Compute the self-determined bit width of an expression without evaluating it.
+pub(super) fn expression_width(
+    condition: &Condition,
+    hierarchy: &wellen::Hierarchy,
+    signal_cache: &std::collections::HashMap<String, wellen::VarRef>,
+) -> Result<u32, String> {
+    match condition {
+        Condition::And(_, _)
+        | Condition::Or(_, _)
+        | Condition::Not(_)
+        | Condition::Eq(_, _)
+        | Condition::Neq(_, _)
+        | Condition::CaseEq(_, _)
+        | Condition::CaseNeq(_, _)
+        | Condition::WildcardEq(_, _)
+        | Condition::WildcardNeq(_, _)
+        | Condition::Lt(_, _)
+        | Condition::Le(_, _)
+        | Condition::Gt(_, _)
+        | Condition::Ge(_, _) => Ok(1),
+        Condition::Rose(_) | Condition::Fell(_) | Condition::Stable(_) | Condition::Changed(_) => {
+            Ok(1)
+        }
+        Condition::ReduceAnd(_)
+        | Condition::ReduceOr(_)
+        | Condition::ReduceXor(_)
+        | Condition::OneHot(_)
+        | Condition::OneHot0(_)
+        | Condition::IsUnknown(_)
+        | Condition::Approx(_, _, _)
+        | Condition::Contains(_, _) => Ok(1),
+        Condition::CountOnes(_) => Ok(32),
+        Condition::Time => Ok(64),
+        Condition::Abs(_) => Err(
+            "$abs returns a real value, which can only be compared or used in real arithmetic"
+                .to_string(),
+        ),
+        Condition::BitwiseNot(expr)
+        | Condition::Neg(expr)
+        | Condition::Signed(expr)
+        | Condition::Unsigned(expr)
+        | Condition::Past(expr, _)
+        | Condition::Scoped(_, expr) => expression_width(expr, hierarchy, signal_cache),
+        Condition::ShiftLeft(left, _)
+        | Condition::ShiftRight(left, _)
+        | Condition::ArithShiftRight(left, _) => expression_width(left, hierarchy, signal_cache),
+        Condition::Concat(items) => concatenation_width(items, hierarchy, signal_cache),
+        Condition::Replicate(count, items) => {
+            replication_width(*count, concatenation_width(items, hierarchy, signal_cache)?)
+        }
+        Condition::BitwiseAnd(left, right)
+        | Condition::BitwiseOr(left, right)
+        | Condition::BitwiseXor(left, right)
+        | Condition::Add(left, right)
+        | Condition::Sub(left, right)
+        | Condition::Mul(left, right)
+        | Condition::Div(left, right)
+        | Condition::Mod(left, right)
+        | Condition::Ternary(_, left, right) => Ok(expression_width(
+            left,
+            hierarchy,
+            signal_cache,
+        )?
+        .max(expression_width(right, hierarchy, signal_cache)?)),
+        Condition::Select(expr, selection) => match selection {
+            Selection::Bit(_) => Ok(1),
+            Selection::Range(msb, lsb) => {
+                check_range_direction(*msb, *lsb, declared_range(expr, hierarchy, signal_cache)?)?;
+                part_select_width(*msb as i64, *lsb as i64)
+            }
+            Selection::Indexed { width, .. } => {
+                check_indexed_width(*width)?;
+                Ok(*width)
+            }
+        },
+        Condition::Signal(path) => {
+            let var_ref = signal_cache
+                .get(path)
+                .ok_or_else(|| format!("Signal not found in cache: {}", path))?;
+            let var = &hierarchy[*var_ref];
+            match var.signal_encoding() {
+                wellen::SignalEncoding::Real => Err(format!(
+                    "Signal {} is real-valued and can only be compared or used in real arithmetic",
+                    path
+                )),
+                wellen::SignalEncoding::String => Err(format!(
+                    "Signal {} is a string and can only be compared or used with $contains",
+                    path
+                )),
+                _ => var
+                    .length()
+                    .ok_or_else(|| format!("Signal {} has no width", path)),
             }
         }
-        wellen::SignalValue::Real(r) => Ok(BigUint::from(r as u64)),
+        Condition::Literal(literal) => Ok(literal_to_value(literal)?.width),
+    }
+}
+
+/// The width of a concatenation of `items`.
+fn concatenation_width(
+    items: &[Condition],
+    hierarchy: &wellen::Hierarchy,
+    signal_cache: &std::collections::HashMap<String, wellen::VarRef>,
+) -> Result<u32, String> {
+    items.iter().try_fold(0u32, |width, item| {
+        width
+            .checked_add(expression_width(item, hierarchy, signal_cache)?)
+            .ok_or_else(|| "Concatenation is too wide".to_string())
+    })
+}
+
+/// The number of samples before the current one that the value of a condition can
+/// depend on through `$past` and the other sampled value functions, or `None` if the
+/// value depends on the sample itself through `$time`.
+fn history_depth(condition: &Condition) -> Option<usize> {
+    let children_depth = |condition: &Condition| {
+        condition
+            .children()
+            .into_iter()
+            .try_fold(0, |depth, child| Some(depth.max(history_depth(child)?)))
+    };
+    match condition {
+        Condition::Time => None,
+        Condition::Past(expr, depth) => Some(history_depth(expr)? + *depth as usize),
+        Condition::Rose(expr)
+        | Condition::Fell(expr)
+        | Condition::Stable(expr)
+        | Condition::Changed(expr) => Some(history_depth(expr)? + 1),
+        _ => children_depth(condition),
+    }
+}
+
+/// Determine whether an expression is signed, following Verilog rules.
+///
+/// Signals, part-selects, concatenations and comparison results are unsigned. An
+/// operation is signed only if all of its context-determined operands are signed.
+pub(super) fn expression_signed(condition: &Condition) -> bool {
+    match condition {
+        Condition::Signed(_) => true,
+        // Like SystemVerilog, $countones returns a signed int
+        Condition::CountOnes(_) => true,
+        Condition::BitwiseAnd(left, right)
+        | Condition::BitwiseOr(left, right)
+        | Condition::BitwiseXor(left, right)
+        | Condition::Add(left, right)
+        | Condition::Sub(left, right)
+        | Condition::Mul(left, right)
+        | Condition::Div(left, right)
+        | Condition::Mod(left, right)
+        | Condition::Ternary(_, left, right) => expression_signed(left) && expression_signed(right),
+        Condition::BitwiseNot(expr)
+        | Condition::Neg(expr)
+        | Condition::Past(expr, _)
+        | Condition::Scoped(_, expr)
+        | Condition::ShiftLeft(expr, _)
+        | Condition::ShiftRight(expr, _)
+        | Condition::ArithShiftRight(expr, _) => expression_signed(expr),
+        _ => false,
+    }
+}
+
+/// Convert a signal value to a four-state value for comparison.
+pub(super) fn signal_value_to_value(
+    signal_value: wellen::SignalValue,
+    width: u32,
+) -> Result<Value, String> {
+    match signal_value {
+        wellen::SignalValue::Binary(data, _) => {
+            // wellen packs two-state values eight bits per byte, most significant byte first
+            Ok(Value::known(BigUint::from_bytes_be(data), width))
+        }
+        wellen::SignalValue::FourValue(_, _) | wellen::SignalValue::NineValue(_, _) => {
+            let bits = signal_value
+                .to_bit_string()
+                .ok_or_else(|| "Cannot convert signal value to bits".to_string())?;
+            Ok(Value::from_bit_string(&bits))
+        }
+        // Real and string signals are evaluated by `evaluate_typed`
+        wellen::SignalValue::String(s) => Err(format!("String value '{}' is not a bit vector", s)),
+        wellen::SignalValue::Real(r) => Err(format!("Real value {} is not a bit vector", r)),
         wellen::SignalValue::Event => Err("Event signal cannot be compared".to_string()),
     }
 }
 
-/// Convert a literal to BigUint for comparison.
-/// Also returns the bit width.
-fn literal_to_biguint(literal: &Literal) -> Result<(BigUint, u32), String> {
+/// Convert a literal to a four-state value for comparison.
+pub(super) fn literal_to_value(literal: &Literal) -> Result<Value, String> {
     match literal {
-        Literal::Binary(bits, width) => {
-            let mut value = BigUint::from(0u32);
-            for (i, &bit) in bits.iter().rev().enumerate() {
-                if bit {
-                    value.set_bit(i as u64, true);
+        Literal::Binary(digits, width) => {
+            let value = Value::from_bit_string(digits);
+            // Like Verilog, a literal whose leftmost digit is X or Z is extended with that digit
+            match digits.chars().next() {
+                Some(fill @ ('x' | 'z')) if value.width < *width => {
+                    let padding: String = std::iter::repeat_n(fill, (width - value.width) as usize)
+                        .chain(digits.chars())
+                        .collect();
+                    Ok(Value::from_bit_string(&padding))
                 }
+                _ => Ok(value.resize(*width)),
             }
-            Ok((value, *width))
         }
-        Literal::Decimal(v, width) => Ok((BigUint::from(*v), *width)),
-        Literal::Hexadecimal(v, width) => Ok((BigUint::from(*v), *width)),
+        Literal::Decimal(v, width) => Ok(Value::known(v.clone(), *width)),
+        // A fill literal on its own is a single bit
+        Literal::Fill(bit) => Ok(Value::from_bit_string(&bit.to_string())),
+        Literal::Real(value) => Err(format!(
+            "Real number {} can only be compared or used in real arithmetic",
+            value
+        )),
+        // Like Verilog, a string is eight bits per character, first character leftmost
+        Literal::String(text) if text.is_empty() => Ok(Value::known(BigUint::zero(), 8)),
+        Literal::String(text) => Ok(Value::known(
+            BigUint::from_bytes_be(text.as_bytes()),
+            text.len() as u32 * 8,
+        )),
+        // Converted by `resolve_signal_scopes`, which knows the timescale
+        Literal::Time(_, text) => Err(format!(
+            "Time {} has not been converted to timescale units",
+            text
+        )),
     }
 }
 
-/// Parse a binary literal (e.g., "4'b0101") from the condition grammar.
+/// Parse a based literal (e.g., "4'b10x1", "8'sd5", "12'o777" or "'hFF") from the condition grammar.
 ///
+/// Binary, octal and hex digits may be x or z, with `?` as an alias for z, as in Verilog.
+/// Literals without a size are at least 32 bits wide, and signed literals (`'s`) are
+/// wrapped in `$signed`. A value that does not fit in its declared width is an error,
+/// as is a literal wider than `MAX_WIDTH`.
 /// This function is called by the lalrpop-generated parser.
-pub(super) fn parse_binary_literal(s: &str) -> Literal {
+pub(super) fn parse_based_literal(s: &str) -> Result<Condition, String> {
     let lower = s.to_lowercase();
-    let parts: Vec<&str> = lower.split('\'').collect();
-    if parts.len() != 2 {
-        panic!("Invalid binary literal: {}", s);
+    let (size, rest) = lower
+        .split_once('\'')
+        .ok_or_else(|| format!("Invalid literal: {}", s))?;
+    let (signed, rest) = match rest.strip_prefix('s') {
+        Some(rest) => (true, rest),
+        None => (false, rest),
+    };
+    let mut chars = rest.chars();
+    let base = chars
+        .next()
+        .ok_or_else(|| format!("Invalid literal: {}", s))?;
+    let digits: String = chars.filter(|&c| c != '_').collect();
+    if digits.is_empty() {
+        return Err(format!("Literal {} has no digits", s));
     }
 
-    let width: u32 = parts[0].parse().expect("Invalid bit width");
-    let value_str = parts[1].trim_start_matches('b').replace('_', "");
-    let mut bits = Vec::new();
-    for c in value_str.chars() {
-        match c {
-            '0' => bits.push(false),
-            '1' => bits.push(true),
-            _ => panic!("Invalid binary digit: {}", c),
+    let width = if size.is_empty() {
+        None
+    } else {
+        match size.parse::<u32>() {
+            Ok(0) => return Err(format!("Literal {} must be at least 1 bit wide", s)),
+            Ok(width) if width > MAX_WIDTH => return Err(literal_too_wide()),
+            Ok(width) => Some(width),
+            Err(_) => return Err(format!("Invalid bit width in literal {}", s)),
+        }
+    };
+
+    let (literal, needed) = match base {
+        'd' => {
+            check_decimal_digits(&digits)?;
+            let value = BigUint::parse_bytes(digits.as_bytes(), 10)
+                .ok_or_else(|| format!("Invalid decimal literal: {}", s))?;
+            let needed = value.bits() as u32;
+            (
+                Literal::Decimal(value, width.unwrap_or(needed.max(32))),
+                needed,
+            )
+        }
+        'b' | 'o' | 'h' => {
+            let bits_per_digit = match base {
+                'b' => 1,
+                'o' => 3,
+                _ => 4,
+            };
+            let mut bits = String::new();
+            for c in digits.chars() {
+                match c {
+                    'x' | 'z' => bits.extend(std::iter::repeat_n(c, bits_per_digit)),
+                    '?' => bits.extend(std::iter::repeat_n('z', bits_per_digit)),
+                    _ => {
+                        let digit = c
+                            .to_digit(1 << bits_per_digit)
+                            .ok_or_else(|| format!("Invalid digit '{}' in literal {}", c, s))?;
+                        bits.push_str(&format!("{:0width$b}", digit, width = bits_per_digit));
+                    }
+                }
+            }
+            let needed = bits.trim_start_matches('0').len() as u32;
+            (
+                Literal::Binary(bits, width.unwrap_or(needed.max(32))),
+                needed,
+            )
         }
+        _ => return Err(format!("Invalid literal base '{}' in {}", base, s)),
+    };
+
+    if needed > MAX_WIDTH {
+        return Err(literal_too_wide());
+    }
+    if let Some(width) = width.filter(|&width| needed > width) {
+        return Err(format!(
+            "Literal {} does not fit in its declared width of {} bits",
+            s, width
+        ));
     }
-    Literal::Binary(bits, width)
+
+    let literal = Condition::Literal(literal);
+    Ok(if signed {
+        Condition::Signed(Box::new(literal))
+    } else {
+        literal
+    })
 }
 
-/// Parse a decimal literal (e.g., "3'd2") from the condition grammar.
-///
-/// This function is called by the lalrpop-generated parser.
-pub(super) fn parse_decimal_literal(s: &str) -> Literal {
-    let lower = s.to_lowercase();
-    let parts: Vec<&str> = lower.split('\'').collect();
-    if parts.len() != 2 {
-        panic!("Invalid decimal literal: {}", s);
+/// The error for a literal wider than `MAX_WIDTH`, which does not repeat the literal
+/// because it may be a very long string of digits.
+fn literal_too_wide() -> String {
+    format!("Width of a literal must be at most {}", MAX_WIDTH)
+}
+
+/// Reject decimal digits too many to fit in `MAX_WIDTH` bits before parsing them, which
+/// takes quadratic time. Every digit after the first adds more than 3 bits.
+fn check_decimal_digits(digits: &str) -> Result<(), String> {
+    let significant = digits.trim_start_matches('0').len();
+    if significant > (MAX_WIDTH / 3) as usize + 1 {
+        return Err(literal_too_wide());
     }
+    Ok(())
+}
 
-    let width: u32 = parts[0].parse().expect("Invalid bit width");
-    let value_str = parts[1].trim_start_matches('d').replace('_', "");
-    let value: u64 = value_str.parse().expect("Invalid decimal value");
-    Literal::Decimal(value, width)
+/// Parse a real literal (e.g., "1.2" or "2.5e-3") from the condition grammar.
+/// This function is called by the lalrpop-generated parser.
+pub(super) fn parse_real_literal(s: &str) -> Result<Condition, String> {
+    s.parse::<f64>()
+        .ok()
+        .filter(|value| value.is_finite())
+        .map(|value| Condition::Literal(Literal::Real(value)))
+        .ok_or_else(|| format!("Real number {} is out of range", s))
 }
 
-/// Parse a hex literal (e.g., "5'h1A") from the condition grammar.
-///
+/// Parse a time literal with a unit (e.g., "10ns" or "2.5us") from the condition grammar.
 /// This function is called by the lalrpop-generated parser.
-pub(super) fn parse_hex_literal(s: &str) -> Literal {
-    let lower = s.to_lowercase();
-    let parts: Vec<&str> = lower.split('\'').collect();
-    if parts.len() != 2 {
-        panic!("Invalid hex literal: {}", s);
+pub(super) fn parse_time_literal(s: &str) -> Result<Condition, String> {
+    let number_len = s
+        .find(|c: char| c.is_ascii_alphabetic())
+        .ok_or_else(|| format!("Time {} has no unit", s))?;
+    let (number, unit) = s.split_at(number_len);
+    let unit_exponent: u32 = match unit {
+        "s" => 21,
+        "ms" => 18,
+        "us" => 15,
+        "ns" => 12,
+        "ps" => 9,
+        "fs" => 6,
+        _ => return Err(format!("Unknown time unit {}", unit)),
+    };
+    let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+    let exponent = unit_exponent
+        .checked_sub(fraction.len() as u32)
+        .ok_or_else(|| format!("Time {} has too many decimal places", s))?;
+    format!("{}{}", whole, fraction)
+        .parse::<u128>()
+        .ok()
+        .and_then(|digits| digits.checked_mul(10u128.pow(exponent)))
+        .map(|zeptoseconds| Condition::Literal(Literal::Time(zeptoseconds, s.to_string())))
+        .ok_or_else(|| format!("Time {} is too large", s))
+}
+
+/// Parse a double-quoted string literal (e.g., `"RESET"`), resolving the escapes `\"`, `\\`,
+/// `\n` and `\t`.
+/// This function is called by the lalrpop-generated parser.
+pub(super) fn parse_string_literal(s: &str) -> Result<Condition, String> {
+    let mut text = String::new();
+    let mut chars = s[1..s.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => text.push('\n'),
+            Some('t') => text.push('\t'),
+            Some(c @ ('"' | '\\')) => text.push(c),
+            Some(c) => return Err(format!("Unknown escape sequence \\{} in string", c)),
+            None => return Err("String ends with a backslash".to_string()),
+        }
     }
+    Ok(Condition::Literal(Literal::String(text)))
+}
 
-    let width: u32 = parts[0].parse().expect("Invalid bit width");
-    let value_str = parts[1].trim_start_matches('h').replace('_', "");
-    let value = u64::from_str_radix(&value_str, 16).expect("Invalid hex value");
-    Literal::Hexadecimal(value, width)
+/// Parse an unsized decimal literal (e.g., "42") from the condition grammar.
+///
+/// Like a Verilog integer, the literal is signed and at least 32 bits wide; wider
+/// values keep a zero sign bit so they stay positive. This function is called by
+/// the lalrpop-generated parser.
+pub(super) fn parse_unsized_literal(s: &str) -> Result<Condition, String> {
+    check_decimal_digits(s)?;
+    let value = BigUint::parse_bytes(s.as_bytes(), 10)
+        .ok_or_else(|| format!("Invalid decimal literal: {}", s))?;
+    let width = u32::try_from(value.bits() + 1)
+        .ok()
+        .filter(|width| *width <= MAX_WIDTH)
+        .ok_or_else(literal_too_wide)?
+        .max(32);
+    Ok(Condition::Signed(Box::new(Condition::Literal(
+        Literal::Decimal(value, width),
+    ))))
 }
 
 /// Find events where a condition is satisfied.
@@ -422,85 +2256,295 @@ pub fn find_conditional_events(
     start_idx: usize,
     end_idx: usize,
     limit: isize,
+) -> Result<Vec<String>, String> {
+    find_conditional_events_with_options(
+        waveform,
+        condition,
+        start_idx,
+        end_idx,
+        limit,
+        &ConditionOptions::default(),
+    )
+}
+
+/// Find events where a condition is satisfied, with additional search options.
+///
+/// When a sampling clock is given, either in `options` or as an `@(posedge sig)`
+/// prefix of the condition, the condition is only evaluated at edges of that clock.
+/// Like SystemVerilog assertions, signal values are sampled just before the edge,
+/// and `$past` and the other sampled value functions refer to previous clock edges.
+/// Events are reported at the time index of the clock edge.
+///
+/// The condition is only evaluated at samples where one of its signals changes, or
+/// where a change can still affect it through `$past`, so the search is fast on long
+/// waveforms where the signals rarely change. It is compiled to a `Program` first, so
+/// signals are looked up and operand widths worked out once rather than at every sample.
+/// Long time ranges are split into chunks that are searched in parallel; events are
+/// still reported in time order, and the search stops once `limit` events are found.
+///
+/// # Arguments
+/// * `waveform` - The waveform to read from (must have signals loaded)
+/// * `condition` - The condition to evaluate (e.g., "TOP.signal1 && TOP.signal2")
+/// * `start_idx` - Starting time index (inclusive)
+/// * `end_idx` - Ending time index (inclusive)
+/// * `limit` - Maximum number of events to return. Use -1 for unlimited.
+/// * `options` - Additional search options
+///
+/// # Returns
+/// A vector of formatted event strings, or an error if the operation fails.
+pub fn find_conditional_events_with_options(
+    waveform: &mut wellen::simple::Waveform,
+    condition: &str,
+    start_idx: usize,
+    end_idx: usize,
+    limit: isize,
+    options: &ConditionOptions,
 ) -> Result<Vec<String>, String> {
     // Get timescale before any mutable operations
     let timescale = waveform.hierarchy().timescale();
 
     // Parse condition
-    let condition_ast = parse_condition(condition)?;
+    let parsed = parse_condition(condition).map_err(|e| e.to_string())?;
+    let mut condition_ast = parsed.condition;
+    let mut clock = resolve_clock(parsed.clock, options)?;
+    resolve_signal_scopes(
+        waveform.hierarchy(),
+        vec![&mut condition_ast],
+        clock.as_mut(),
+        options,
+    )?;
 
-    // Extract all signal names from condition
-    let signal_names = extract_signal_names(&condition_ast);
+    let loaded = load_condition_signals(
+        waveform,
+        &[&condition_ast],
+        clock.as_ref(),
+        options.paths.as_deref(),
+    )?;
+    let waveform = &*waveform;
+    let ctx = loaded.context(waveform);
+    let scan = ConditionScan {
+        ctx: &ctx,
+        program: Program::compile(&condition_ast, &ctx)?,
+        changes: ctx.change_samples(&condition_ast)?,
+        signal_names: &loaded.signal_names,
+        timescale: timescale.as_ref(),
+    };
 
-    // Find and load all signals
-    let mut signal_cache = std::collections::HashMap::new();
-    let hierarchy = waveform.hierarchy();
+    // Samples from `first_sample` up to but not including `end_sample` are reported
+    // within the time range
+    let first_sample = ctx.first_sample_from(start_idx);
+    let end_sample = ctx
+        .first_sample_from(end_idx.saturating_add(1))
+        .min(ctx.num_samples());
+    let limit = usize::try_from(limit).ok();
 
-    // Collect all signal_refs first
-    let mut signal_refs = Vec::new();
-    for signal_name in &signal_names {
-        let var_ref = find_var_by_path(hierarchy, signal_name)
-            .ok_or_else(|| format!("Signal not found: {}", signal_name))?;
-        let signal_ref = hierarchy[var_ref].signal_ref();
-        signal_cache.insert(signal_name.clone(), var_ref);
-        signal_refs.push(signal_ref);
+    // Chunks are scanned in parallel, one batch at a time so that a search with a small
+    // limit stops early
+    let chunks: Vec<std::ops::Range<usize>> = (first_sample..end_sample)
+        .step_by(SCAN_CHUNK_SAMPLES)
+        .map(|chunk_start| chunk_start..(chunk_start + SCAN_CHUNK_SAMPLES).min(end_sample))
+        .collect();
+    let mut events = Vec::new();
+    for batch in chunks.chunks(rayon::current_num_threads()) {
+        let remaining = limit.map(|limit| limit - events.len());
+        let batch_events: Vec<Result<Vec<String>, String>> = batch
+            .par_iter()
+            .map(|chunk| scan.scan(chunk.clone(), remaining))
+            .collect();
+        for chunk_events in batch_events {
+            events.extend(chunk_events?);
+            if let Some(limit) = limit.filter(|&limit| events.len() >= limit) {
+                events.truncate(limit);
+                return Ok(events);
+            }
+        }
     }
 
-    // Load all signals at once
-    waveform.load_signals(&signal_refs);
+    Ok(events)
+}
 
-    // Get time table after loading signals
-    let time_table: Vec<wellen::Time> = waveform.time_table().to_vec();
+/// Number of samples scanned by one task of a parallel condition search.
+const SCAN_CHUNK_SAMPLES: usize = 1 << 14;
 
-    let mut events = Vec::new();
+/// A condition search, shared by the tasks that scan chunks of the time range.
+struct ConditionScan<'a> {
+    ctx: &'a EvalContext<'a>,
+    program: Program<'a>,
+    /// Samples at which the condition may change, see `EvalContext::change_samples`
+    changes: Option<Vec<usize>>,
+    signal_names: &'a [String],
+    timescale: Option<&'a wellen::Timescale>,
+}
 
-    // Scan through time indices
-    let end = end_idx.min(time_table.len().saturating_sub(1));
-    for (idx, &time_value) in time_table[start_idx..=end].iter().enumerate() {
-        let time_idx = start_idx + idx;
-        // Evaluate condition at this time index (0 = false, non-zero = true)
-        if !evaluate_condition(&condition_ast, waveform, &signal_cache, time_idx)?.is_zero() {
-            let formatted_time = format_time(time_value, timescale.as_ref());
-
-            // Build event description with signal values
-            let mut signal_values = Vec::new();
-            for signal_name in &signal_names {
-                if let Some(var_ref) = signal_cache.get(signal_name) {
-                    let hierarchy = waveform.hierarchy();
-                    let signal_ref = hierarchy[*var_ref].signal_ref();
-                    if let Some(signal) = waveform.get_signal(signal_ref) {
-                        let time_table_idx: wellen::TimeTableIdx = time_idx
-                            .try_into()
-                            .map_err(|_| format!("Time index {} too large", time_idx))?;
-
-                        if let Some(offset) = signal.get_offset(time_table_idx) {
-                            let signal_value = signal.get_value_at(&offset, 0);
-                            let value_str = format_signal_value(signal_value);
-                            signal_values.push(format!("{} = {}", signal_name, value_str));
-                        }
-                    }
-                }
+impl ConditionScan<'_> {
+    /// Find the events in a range of samples, stopping after `limit` events.
+    ///
+    /// Each sample is evaluated on its own, reading `$past` values from before the
+    /// range where needed, so ranges can be scanned independently.
+    fn scan(
+        &self,
+        samples: std::ops::Range<usize>,
+        limit: Option<usize>,
+    ) -> Result<Vec<String>, String> {
+        let ctx = self.ctx;
+        let time_table = ctx.waveform.time_table();
+
+        // The condition and its signals keep their values from one change sample to the
+        // next, so it is only evaluated at the start of each such segment
+        let segment_starts: Box<dyn Iterator<Item = usize>> = match &self.changes {
+            Some(changes) => {
+                let later = changes.partition_point(|&idx| idx <= samples.start)
+                    ..changes.partition_point(|&idx| idx < samples.end);
+                Box::new(std::iter::once(samples.start).chain(changes[later].iter().copied()))
             }
+            None => Box::new(samples.clone()),
+        };
+        let mut segment_starts = segment_starts.peekable();
+
+        let mut events = Vec::new();
+        while let Some(segment_start) = segment_starts.next() {
+            let segment_end = segment_starts.peek().copied().unwrap_or(samples.end);
 
-            events.push(format!(
-                "Time index {} ({}): {}",
-                time_idx,
-                formatted_time,
-                signal_values.join(", ")
-            ));
+            // Conditions that are X or Z count as false
+            if !self.program.holds(ctx, segment_start)? {
+                continue;
+            }
+            let signal_values = ctx.format_signal_values(self.signal_names, segment_start)?;
+            for sample_idx in segment_start..segment_end {
+                // Check limit
+                if limit.is_some_and(|limit| events.len() >= limit) {
+                    return Ok(events);
+                }
+
+                let report_idx = ctx.report_idx(sample_idx);
+                let formatted_time = format_time(time_table[report_idx], self.timescale);
+                events.push(format!(
+                    "Time index {} ({}): {}",
+                    report_idx, formatted_time, signal_values
+                ));
+            }
         }
 
-        // Check limit
-        if limit >= 0 && events.len() >= limit as usize {
-            break;
+        Ok(events)
+    }
+}
+
+/// How a condition is evaluated: by walking its tree, or compiled to a `Program`.
+#[cfg(feature = "test-utils")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Evaluator {
+    Tree,
+    Compiled,
+}
+
+/// Evaluate a condition at every sample with the given evaluator.
+#[cfg(feature = "test-utils")]
+fn evaluate_at_every_sample<T>(
+    waveform: &mut wellen::simple::Waveform,
+    condition: &str,
+    evaluator: Evaluator,
+    mut map: impl FnMut(Value) -> T,
+) -> Result<Vec<T>, String> {
+    let parsed = parse_condition(condition).map_err(|e| e.to_string())?;
+    let mut condition_ast = parsed.condition;
+    let options = ConditionOptions::default();
+    let mut clock = resolve_clock(parsed.clock, &options)?;
+    resolve_signal_scopes(
+        waveform.hierarchy(),
+        vec![&mut condition_ast],
+        clock.as_mut(),
+        &options,
+    )?;
+
+    let loaded = load_condition_signals(
+        waveform,
+        &[&condition_ast],
+        clock.as_ref(),
+        options.paths.as_deref(),
+    )?;
+    let waveform = &*waveform;
+    let ctx = loaded.context(waveform);
+    match evaluator {
+        Evaluator::Tree => (0..ctx.num_samples())
+            .map(|sample_idx| Ok(map(evaluate_condition(&condition_ast, &ctx, sample_idx)?)))
+            .collect(),
+        Evaluator::Compiled => {
+            let program = Program::compile(&condition_ast, &ctx)?;
+            (0..ctx.num_samples())
+                .map(|sample_idx| Ok(map(program.evaluate(&ctx, sample_idx)?)))
+                .collect()
         }
     }
+}
 
-    Ok(events)
+/// The formatted value of a condition at every sample, for checking that both
+/// evaluators agree.
+#[cfg(feature = "test-utils")]
+pub fn evaluate_condition_values(
+    waveform: &mut wellen::simple::Waveform,
+    condition: &str,
+    evaluator: Evaluator,
+) -> Result<Vec<String>, String> {
+    evaluate_at_every_sample(waveform, condition, evaluator, |value| {
+        super::formatting::format_value(&value)
+    })
+}
+
+/// The number of samples at which a condition holds, evaluating it at every sample.
+#[cfg(feature = "test-utils")]
+pub fn count_matching_samples(
+    waveform: &mut wellen::simple::Waveform,
+    condition: &str,
+    evaluator: Evaluator,
+) -> Result<usize, String> {
+    let matches = evaluate_at_every_sample(waveform, condition, evaluator, |value| {
+        value.truth() == Some(true)
+    })?;
+    Ok(matches.into_iter().filter(|&matched| matched).count())
+}
+
+/// Find the time indices at which a clock signal has the given edge.
+///
+/// Edges follow Verilog semantics on the least significant bit: a positive edge is a
+/// change from 0 or a change to 1 (including to or from X and Z), and a negative edge
+/// is a change from 1 or a change to 0. Time index 0 is never an edge.
+fn find_clock_edges(
+    waveform: &wellen::simple::Waveform,
+    clock_var: wellen::VarRef,
+    edge: ClockEdge,
+) -> Result<Vec<usize>, String> {
+    let hierarchy = waveform.hierarchy();
+    let width = hierarchy[clock_var].length().unwrap_or(1);
+    let signal = waveform
+        .get_signal(hierarchy[clock_var].signal_ref())
+        .ok_or("Clock signal not found after loading")?;
+
+    let zero = Value::from_bool(false);
+    let one = Value::from_bool(true);
+    let mut edges = Vec::new();
+    let mut previous: Option<Value> = None;
+    for (time_idx, signal_value) in signal.iter_changes() {
+        let time_idx = time_idx as usize;
+        let current = signal_value_to_value(signal_value, width)?.slice(0, 1);
+        if let Some(previous) = previous.filter(|previous| !previous.case_eq(&current)) {
+            let posedge = previous.case_eq(&zero) || current.case_eq(&one);
+            let negedge = previous.case_eq(&one) || current.case_eq(&zero);
+            let matches = match edge {
+                ClockEdge::Posedge => posedge,
+                ClockEdge::Negedge => negedge,
+                ClockEdge::Edge => posedge || negedge,
+            };
+            if matches {
+                edges.push(time_idx);
+            }
+        }
+        previous = Some(current);
+    }
+    Ok(edges)
 }
 
 /// Extract all signal names from a condition AST.
-fn extract_signal_names(condition: &Condition) -> Vec<String> {
+pub(super) fn extract_signal_names(condition: &Condition) -> Vec<String> {
     let mut names = Vec::new();
     extract_signal_names_recursive(condition, &mut names);
     names
@@ -526,7 +2570,15 @@ fn extract_signal_names_recursive(condition: &Condition, names: &mut Vec<String>
             extract_signal_names_recursive(left, names);
             extract_signal_names_recursive(right, names);
         }
-        Condition::Neq(left, right) => {
+        Condition::Neq(left, right)
+        | Condition::CaseEq(left, right)
+        | Condition::CaseNeq(left, right)
+        | Condition::WildcardEq(left, right)
+        | Condition::WildcardNeq(left, right)
+        | Condition::Lt(left, right)
+        | Condition::Le(left, right)
+        | Condition::Gt(left, right)
+        | Condition::Ge(left, right) => {
             extract_signal_names_recursive(left, names);
             extract_signal_names_recursive(right, names);
         }
@@ -538,7 +2590,30 @@ fn extract_signal_names_recursive(condition: &Condition, names: &mut Vec<String>
             extract_signal_names_recursive(left, names);
             extract_signal_names_recursive(right, names);
         }
-        Condition::BitwiseXor(left, right) => {
+        Condition::BitwiseXor(left, right)
+        | Condition::Add(left, right)
+        | Condition::Sub(left, right)
+        | Condition::Mul(left, right)
+        | Condition::Div(left, right)
+        | Condition::Mod(left, right)
+        | Condition::ShiftLeft(left, right)
+        | Condition::ShiftRight(left, right)
+        | Condition::ArithShiftRight(left, right) => {
+            extract_signal_names_recursive(left, names);
+            extract_signal_names_recursive(right, names);
+        }
+        Condition::Concat(items) | Condition::Replicate(_, items) => {
+            for item in items {
+                extract_signal_names_recursive(item, names);
+            }
+        }
+        Condition::Ternary(cond, if_true, if_false)
+        | Condition::Approx(cond, if_true, if_false) => {
+            extract_signal_names_recursive(cond, names);
+            extract_signal_names_recursive(if_true, names);
+            extract_signal_names_recursive(if_false, names);
+        }
+        Condition::Contains(left, right) => {
             extract_signal_names_recursive(left, names);
             extract_signal_names_recursive(right, names);
         }
@@ -547,15 +2622,34 @@ fn extract_signal_names_recursive(condition: &Condition, names: &mut Vec<String>
                 names.push(path.clone());
             }
         }
-        Condition::BitExtract(path, _, _) => {
-            if !names.contains(path) {
-                names.push(path.clone());
+        Condition::Select(expr, selection) => {
+            extract_signal_names_recursive(expr, names);
+            match selection {
+                Selection::Bit(index) => extract_signal_names_recursive(index, names),
+                Selection::Indexed { base, .. } => extract_signal_names_recursive(base, names),
+                Selection::Range(_, _) => {}
             }
         }
-        Condition::Literal(_) => {
-            // Literals don't need to be loaded
+        Condition::Literal(_) | Condition::Time => {
+            // Literals and `$time` don't need to be loaded
         }
-        Condition::Past(expr) => {
+        Condition::Past(expr, _)
+        | Condition::Rose(expr)
+        | Condition::Fell(expr)
+        | Condition::Stable(expr)
+        | Condition::Changed(expr)
+        | Condition::ReduceAnd(expr)
+        | Condition::ReduceOr(expr)
+        | Condition::ReduceXor(expr)
+        | Condition::CountOnes(expr)
+        | Condition::OneHot(expr)
+        | Condition::OneHot0(expr)
+        | Condition::IsUnknown(expr)
+        | Condition::Neg(expr)
+        | Condition::Signed(expr)
+        | Condition::Unsigned(expr)
+        | Condition::Scoped(_, expr)
+        | Condition::Abs(expr) => {
             extract_signal_names_recursive(expr, names);
         }
     }
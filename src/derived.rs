@@ -0,0 +1,256 @@
+//! Derived signals: named expressions that can be used like waveform signals.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::{
+    condition::expression_width, condition::extract_signal_names,
+    condition::load_condition_signals, condition::parse_condition,
+    condition::resolve_signal_scopes, condition::Condition, condition::ConditionOptions,
+    formatting::format_time, formatting::format_value, hierarchy::PathIndex, hierarchy::PathLookup,
+    program::Program, value::Value,
+};
+
+/// A named expression, e.g. `axi_aw_fire := TOP.awvalid && TOP.awready`.
+#[derive(Debug, Clone)]
+pub struct DerivedSignal {
+    pub name: String,
+    /// The expression as it was given
+    pub expression: String,
+    /// Bit width of the expression
+    pub width: u32,
+    /// The expression with signal names resolved and other derived signals expanded
+    pub(super) condition: Condition,
+    /// Index of the paths in the waveform the signal is defined for
+    pub(super) paths: Option<Arc<PathIndex>>,
+}
+
+/// The derived signals defined for one waveform.
+#[derive(Debug, Clone, Default)]
+pub struct DerivedSignals {
+    signals: HashMap<String, DerivedSignal>,
+    paths: Option<Arc<PathIndex>>,
+}
+
+impl DerivedSignals {
+    /// No derived signals yet, for a waveform whose paths are looked up in `paths`.
+    pub fn with_paths(paths: Arc<PathIndex>) -> Self {
+        DerivedSignals {
+            signals: HashMap::new(),
+            paths: Some(paths),
+        }
+    }
+
+    /// Look up a derived signal by name.
+    pub fn get(&self, name: &str) -> Option<&DerivedSignal> {
+        self.signals.get(name)
+    }
+
+    /// Names of all derived signals, sorted.
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.signals.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Define a derived signal, replacing any earlier definition with the same name.
+    ///
+    /// The expression uses the syntax of `find_conditional_events` without a sampling
+    /// clock, and may refer to derived signals defined earlier. Those are expanded
+    /// now, so redefining a signal later does not change signals defined from it.
+    ///
+    /// # Arguments
+    /// * `hierarchy` - The hierarchy of the waveform the signal is defined for
+    /// * `name` - A plain identifier that is not the path of a signal in the waveform
+    /// * `expression` - The expression the signal evaluates to
+    ///
+    /// # Returns
+    /// The new derived signal, or an error if the name or expression is invalid.
+    pub fn define(
+        &mut self,
+        hierarchy: &wellen::Hierarchy,
+        name: &str,
+        expression: &str,
+    ) -> Result<&DerivedSignal, String> {
+        // Keywords such as `with` are not parsed as signal names
+        let is_identifier = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            && matches!(
+                parse_condition(name).map(|parsed| parsed.condition),
+                Ok(Condition::Signal(_))
+            );
+        if !is_identifier {
+            return Err(format!(
+                "Invalid derived signal name '{}': use a plain identifier such as axi_aw_fire",
+                name
+            ));
+        }
+        let paths = PathLookup::new(hierarchy, self.paths.as_deref());
+        if paths.find_var(name).is_some() {
+            return Err(format!(
+                "Cannot define derived signal {}: the waveform already has a signal with that name",
+                name
+            ));
+        }
+
+        let parsed = parse_condition(expression).map_err(|e| e.to_string())?;
+        if parsed.clock.is_some() {
+            return Err(format!(
+                "Derived signal {} cannot have a sampling clock; give the clock where the signal is used",
+                name
+            ));
+        }
+        let mut condition = parsed.condition;
+        let options = ConditionOptions {
+            derived: self.clone(),
+            paths: self.paths.clone(),
+            ..Default::default()
+        };
+        resolve_signal_scopes(hierarchy, vec![&mut condition], None, &options)?;
+
+        let mut signal_cache = HashMap::new();
+        for signal_name in extract_signal_names(&condition) {
+            let var_ref = paths
+                .find_var(&signal_name)
+                .ok_or_else(|| format!("Signal not found: {}", signal_name))?;
+            signal_cache.insert(signal_name, var_ref);
+        }
+        let width = expression_width(&condition, hierarchy, &signal_cache)?;
+
+        let signal = DerivedSignal {
+            name: name.to_string(),
+            expression: expression.to_string(),
+            width,
+            condition,
+            paths: self.paths.clone(),
+        };
+        self.signals.insert(name.to_string(), signal);
+        Ok(&self.signals[name])
+    }
+}
+
+/// Evaluate a derived signal at a range of time indices.
+fn evaluate_derived_signal(
+    waveform: &mut wellen::simple::Waveform,
+    signal: &DerivedSignal,
+    time_indices: impl Iterator<Item = usize>,
+) -> Result<Vec<Value>, String> {
+    let loaded = load_condition_signals(
+        waveform,
+        &[&signal.condition],
+        None,
+        signal.paths.as_deref(),
+    )?;
+    let waveform = &*waveform;
+    let ctx = loaded.context(waveform);
+    let program = Program::compile(&signal.condition, &ctx)?;
+    time_indices
+        .map(|time_idx| program.evaluate(&ctx, time_idx))
+        .collect()
+}
+
+/// Read the values of a derived signal at specific time indices.
+///
+/// # Arguments
+/// * `waveform` - The waveform to read from
+/// * `signal` - The derived signal to read
+/// * `time_indices` - The time indices to read values at
+///
+/// # Returns
+/// A vector of formatted signal value strings, or an error if the operation fails.
+pub fn read_derived_signal_values(
+    waveform: &mut wellen::simple::Waveform,
+    signal: &DerivedSignal,
+    time_indices: &[usize],
+) -> Result<Vec<String>, String> {
+    let num_times = waveform.time_table().len();
+    let in_range = time_indices.iter().copied().filter(|&idx| idx < num_times);
+    let mut values = evaluate_derived_signal(waveform, signal, in_range)?.into_iter();
+    let time_table = waveform.time_table();
+    let timescale = waveform.hierarchy().timescale();
+
+    let mut results = Vec::new();
+    for &time_idx in time_indices {
+        if time_idx >= time_table.len() {
+            results.push(format!(
+                "Time index {} out of range (max: {})",
+                time_idx,
+                time_table.len() - 1
+            ));
+            continue;
+        }
+
+        let formatted_time = format_time(time_table[time_idx], timescale.as_ref());
+        results.push(format!(
+            "Time index {} ({}): {}",
+            time_idx,
+            formatted_time,
+            format_value(&values.next().ok_or("Missing derived signal value")?)
+        ));
+    }
+
+    Ok(results)
+}
+
+/// Find events (changes) of a derived signal within a time range.
+///
+/// Like a waveform signal, a derived signal has an event at time index 0 and at
+/// every time index where its value changes, including changes to and from X or Z.
+///
+/// # Arguments
+/// * `waveform` - The waveform to read from
+/// * `signal` - The derived signal to analyze
+/// * `start_idx` - Starting time index (inclusive)
+/// * `end_idx` - Ending time index (inclusive)
+/// * `limit` - Maximum number of events to return. Use -1 for unlimited.
+///
+/// # Returns
+/// A vector of formatted event strings, or an error if the operation fails.
+pub fn find_derived_signal_events(
+    waveform: &mut wellen::simple::Waveform,
+    signal: &DerivedSignal,
+    start_idx: usize,
+    end_idx: usize,
+    limit: isize,
+) -> Result<Vec<String>, String> {
+    // Start one time index early to see whether the value changes at `start_idx`
+    let first_idx = start_idx.saturating_sub(1);
+    let last_idx = end_idx.min(waveform.time_table().len().saturating_sub(1));
+    let values = evaluate_derived_signal(waveform, signal, first_idx..=last_idx)?;
+    let time_table = waveform.time_table();
+    let timescale = waveform.hierarchy().timescale();
+
+    let mut events = Vec::new();
+    for (time_idx, value) in (first_idx..).zip(&values) {
+        let changed = time_idx == 0 || !values[time_idx - first_idx - 1].case_eq(value);
+        if !changed || time_idx < start_idx {
+            continue;
+        }
+
+        // Check limit (unless unlimited with -1)
+        if limit >= 0 && events.len() >= limit as usize {
+            break;
+        }
+
+        let formatted_time = format_time(time_table[time_idx], timescale.as_ref());
+        events.push(format!(
+            "Time index {} ({}): {}",
+            time_idx,
+            formatted_time,
+            format_value(value)
+        ));
+    }
+
+    Ok(events)
+}
+
+/// Get metadata about a derived signal, in the format of `get_signal_metadata`.
+pub fn get_derived_signal_metadata(signal: &DerivedSignal) -> String {
+    format!(
+        "Signal: {}\nType: Derived ({})\nWidth: {} bits\nIndex: [{}:0]",
+        signal.name,
+        signal.expression,
+        signal.width,
+        signal.width.saturating_sub(1)
+    )
+}
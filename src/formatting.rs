@@ -1,5 +1,6 @@
 //! Formatting utilities for time and signal values.
 
+use super::value::Value;
 use wellen;
 
 /// Format a time value with its timescale into a human-readable string.
@@ -60,6 +61,32 @@ pub fn format_signal_value(signal_value: wellen::SignalValue) -> String {
     }
 }
 
+/// Format a four-state value in the same Verilog style as `format_signal_value`.
+///
+/// Values with X or Z bits are always shown in binary, e.g. `8'b0000xx01`.
+pub(super) fn format_value(value: &Value) -> String {
+    if value.width > 4 && !value.has_unknown() {
+        let hex_chars = value.width.div_ceil(4) as usize;
+        return format!(
+            "{}'h{:0width$x}",
+            value.width,
+            value.value,
+            width = hex_chars
+        );
+    }
+
+    let bin_str: String = (0..u64::from(value.width))
+        .rev()
+        .map(|bit| match (value.unknown.bit(bit), value.value.bit(bit)) {
+            (false, false) => '0',
+            (false, true) => '1',
+            (true, true) => 'x',
+            (true, false) => 'z',
+        })
+        .collect();
+    format!("{}'b{}", value.width, bin_str)
+}
+
 /// Format a binary value (Vec<u8>) in Verilog style.
 fn format_binary_verilog(data: &[u8], bits: u32) -> String {
     // For short signals (<= 4 bits), use binary format
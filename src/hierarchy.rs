@@ -1,28 +1,277 @@
 //! Hierarchy navigation and signal finding utilities.
 
+use std::collections::HashMap;
+
 use wellen;
 
+/// Split a hierarchical path into the names of its scopes and signal.
+///
+/// Names are separated by `.`, except inside:
+/// - Backtick-quoted text, which may contain any character but a backtick, e.g.
+///   ``top.`a.b`.sig``; the backticks are not part of the name
+/// - Verilog escaped identifiers, which start with `\` and end at whitespace, e.g.
+///   `top.\bus.a .sig`
+/// - Indices, such as the generate index in `top.gen_loop[3].u_core`
+fn split_path(path: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut name = String::new();
+    let mut depth = 0;
+    let mut chars = path.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '`' => name.extend(chars.by_ref().take_while(|&c| c != '`')),
+            '\\' if name.is_empty() => {
+                name.push(c);
+                name.extend(chars.by_ref().take_while(|c| !c.is_whitespace()));
+            }
+            '[' => {
+                depth += 1;
+                name.push(c);
+            }
+            ']' => {
+                depth -= 1;
+                name.push(c);
+            }
+            '.' if depth == 0 => names.push(std::mem::take(&mut name)),
+            // Whitespace can only end an escaped identifier
+            c if c.is_whitespace() => {}
+            c => name.push(c),
+        }
+    }
+    names.push(name);
+    names
+}
+
+/// Check whether a name from a path refers to a scope or variable of the hierarchy.
+///
+/// The leading `\` of an escaped identifier is optional, and an element of a
+/// bit-blasted vector, which wellen stores as its name and index, can be referred
+/// to as e.g. `bus[3]`.
+fn name_matches(name: &str, hierarchy_name: &str, index: Option<wellen::VarIndex>) -> bool {
+    let unescaped = |name: &str| name.strip_prefix('\\').unwrap_or(name).to_string();
+    let mut candidates = vec![hierarchy_name.to_string()];
+    if let Some(index) = index {
+        candidates.push(if index.msb() == index.lsb() {
+            format!("{}[{}]", hierarchy_name, index.lsb())
+        } else {
+            format!("{}[{}:{}]", hierarchy_name, index.msb(), index.lsb())
+        });
+    }
+    candidates
+        .iter()
+        .any(|candidate| candidate == name || unescaped(candidate) == unescaped(name))
+}
+
+/// Find a scope by the names of the scopes leading to it.
+fn find_scope_by_names(
+    hierarchy: &wellen::Hierarchy,
+    names: &[String],
+) -> Option<wellen::ScopeRef> {
+    let (first, rest) = names.split_first()?;
+    let mut scope_ref = hierarchy
+        .scopes()
+        .find(|&scope_ref| name_matches(first, hierarchy[scope_ref].name(hierarchy), None))?;
+    for name in rest {
+        scope_ref = hierarchy[scope_ref]
+            .scopes(hierarchy)
+            .find(|&child_ref| name_matches(name, hierarchy[child_ref].name(hierarchy), None))?;
+    }
+    Some(scope_ref)
+}
+
+/// The key a name from a path is looked up with in a `PathIndex`.
+fn index_key(name: &str) -> String {
+    name.strip_prefix('\\').unwrap_or(name).to_string()
+}
+
+/// An index of the scopes and variables of a hierarchy by path.
+///
+/// `find_var_by_path` and the other lookups in this module search the children of
+/// each scope along a path, and fall back to comparing the full name of every scope
+/// or signal. Build an index once per waveform to look up paths in constant time per
+/// name instead; the index finds the same scopes and variables as those functions.
+#[derive(Debug, Clone, Default)]
+pub struct PathIndex {
+    /// Scopes by their parent (`None` at the top) and name
+    scopes: HashMap<(Option<wellen::ScopeRef>, String), wellen::ScopeRef>,
+    /// Variables by their parent and name, also with the index of bit-blasted vectors
+    vars: HashMap<(Option<wellen::ScopeRef>, String), wellen::VarRef>,
+    /// Scopes whose full name is not a path that finds them
+    scope_full_names: HashMap<String, wellen::ScopeRef>,
+    /// Signals of variables whose full name is not a path that finds them
+    signal_full_names: HashMap<String, wellen::SignalRef>,
+}
+
+impl PathIndex {
+    /// Index every scope and variable of a hierarchy.
+    pub fn new(hierarchy: &wellen::Hierarchy) -> Self {
+        let mut index = PathIndex::default();
+
+        // Where several children match a name, the first is found, as in `name_matches`
+        let mut parents = vec![None];
+        while let Some(parent) = parents.pop() {
+            let (scopes, vars): (Vec<wellen::ScopeRef>, Vec<wellen::VarRef>) = match parent {
+                None => (hierarchy.scopes().collect(), hierarchy.vars().collect()),
+                Some(scope_ref) => {
+                    let scope: &wellen::Scope = &hierarchy[scope_ref];
+                    (
+                        scope.scopes(hierarchy).collect(),
+                        scope.vars(hierarchy).collect(),
+                    )
+                }
+            };
+            for scope_ref in scopes {
+                let name = index_key(hierarchy[scope_ref].name(hierarchy));
+                index.scopes.entry((parent, name)).or_insert(scope_ref);
+                parents.push(Some(scope_ref));
+            }
+            for var_ref in vars {
+                let var = &hierarchy[var_ref];
+                let name = var.name(hierarchy);
+                let mut names = vec![index_key(name)];
+                if let Some(index) = var.index() {
+                    names.push(index_key(&if index.msb() == index.lsb() {
+                        format!("{}[{}]", name, index.lsb())
+                    } else {
+                        format!("{}[{}:{}]", name, index.msb(), index.lsb())
+                    }));
+                }
+                for name in names {
+                    index.vars.entry((parent, name)).or_insert(var_ref);
+                }
+            }
+        }
+
+        // Full names are compared in the order `find_signal_by_path` and
+        // `find_scope_by_path` compare them
+        for var in hierarchy.iter_vars() {
+            let full_name = var.full_name(hierarchy);
+            if index.find_var(&full_name).is_none() {
+                index
+                    .signal_full_names
+                    .entry(full_name)
+                    .or_insert(var.signal_ref());
+            }
+        }
+        let mut scopes: Vec<wellen::ScopeRef> = hierarchy.scopes().collect();
+        scopes.reverse();
+        while let Some(scope_ref) = scopes.pop() {
+            let scope = &hierarchy[scope_ref];
+            let full_name = scope.full_name(hierarchy);
+            if index.find_scope_by_names(&split_path(&full_name)).is_none() {
+                index.scope_full_names.entry(full_name).or_insert(scope_ref);
+            }
+            let first_child = scopes.len();
+            scopes.extend(scope.scopes(hierarchy));
+            scopes[first_child..].reverse();
+        }
+
+        index
+    }
+
+    fn find_scope_by_names(&self, names: &[String]) -> Option<wellen::ScopeRef> {
+        let mut scope_ref = None;
+        for name in names {
+            scope_ref = Some(*self.scopes.get(&(scope_ref, index_key(name)))?);
+        }
+        scope_ref
+    }
+
+    /// Find a variable by its path, like `find_var_by_path`.
+    pub fn find_var(&self, path: &str) -> Option<wellen::VarRef> {
+        let names = split_path(path);
+        let (name, scope_names) = names.split_last()?;
+        let parent = if scope_names.is_empty() {
+            None
+        } else {
+            Some(self.find_scope_by_names(scope_names)?)
+        };
+        self.vars.get(&(parent, index_key(name))).copied()
+    }
+
+    /// Find a signal by its path or full name, like `find_signal_by_path`.
+    pub fn find_signal(
+        &self,
+        hierarchy: &wellen::Hierarchy,
+        path: &str,
+    ) -> Option<wellen::SignalRef> {
+        match self.find_var(path) {
+            Some(var_ref) => Some(hierarchy[var_ref].signal_ref()),
+            None => self.signal_full_names.get(path).copied(),
+        }
+    }
+
+    /// Find a scope by its path or full name, like `find_scope_by_path`.
+    pub fn find_scope(&self, path: &str) -> Option<wellen::ScopeRef> {
+        self.find_scope_by_names(&split_path(path))
+            .or_else(|| self.scope_full_names.get(path).copied())
+    }
+}
+
+/// Finds scopes and variables by path, with a `PathIndex` if there is one.
+#[derive(Clone, Copy)]
+pub(super) enum PathLookup<'a> {
+    Indexed(&'a PathIndex),
+    Search(&'a wellen::Hierarchy),
+}
+
+impl<'a> PathLookup<'a> {
+    /// Look paths up in `paths` when there is an index, and search `hierarchy` otherwise.
+    pub(super) fn new(hierarchy: &'a wellen::Hierarchy, paths: Option<&'a PathIndex>) -> Self {
+        match paths {
+            Some(index) => PathLookup::Indexed(index),
+            None => PathLookup::Search(hierarchy),
+        }
+    }
+
+    pub(super) fn find_var(&self, path: &str) -> Option<wellen::VarRef> {
+        match self {
+            PathLookup::Indexed(index) => index.find_var(path),
+            PathLookup::Search(hierarchy) => find_var_by_path(hierarchy, path),
+        }
+    }
+
+    pub(super) fn find_scope(&self, path: &str) -> Option<wellen::ScopeRef> {
+        match self {
+            PathLookup::Indexed(index) => index.find_scope(path),
+            PathLookup::Search(hierarchy) => find_scope_by_path(hierarchy, path),
+        }
+    }
+}
+
 /// Find a variable (VarRef) by its hierarchical path in waveform hierarchy.
 ///
+/// Paths are split into names as described in `split_path`, so that signals under
+/// generate blocks, escaped identifiers and names with unusual characters can be
+/// referenced, e.g. `top.gen_loop[3].u_core.valid` or ``top.`weird.name` ``.
+///
 /// # Arguments
 /// * `hierarchy` - The waveform hierarchy to search
 /// * `path` - The hierarchical path to signal (e.g., "top.module.signal")
 ///
 /// # Returns
-/// `Some(VarRef)` if signal is found, `None` otherwise.
+/// `Some(VarRef)` if signal is found, `None` otherwise. Use a `PathIndex` to look up
+/// many paths in a large hierarchy.
 pub fn find_var_by_path(hierarchy: &wellen::Hierarchy, path: &str) -> Option<wellen::VarRef> {
-    let parts: Vec<&str> = path.split('.').collect();
-    if parts.len() > 1 {
-        let path_parts = &parts[..parts.len() - 1];
-        let name = parts[parts.len() - 1];
-        hierarchy.lookup_var(path_parts, name)
+    let names = split_path(path);
+    let (name, scope_names) = names.split_last()?;
+    let matches = |var_ref: &wellen::VarRef| {
+        let var = &hierarchy[*var_ref];
+        name_matches(name, var.name(hierarchy), var.index())
+    };
+    if scope_names.is_empty() {
+        hierarchy.vars().find(matches)
     } else {
-        hierarchy.lookup_var(&[], path)
+        let scope_ref = find_scope_by_names(hierarchy, scope_names)?;
+        hierarchy[scope_ref].vars(hierarchy).find(matches)
     }
 }
 
 /// Find a signal by its hierarchical path in the waveform hierarchy.
 ///
+/// Accepts the path syntax of `find_var_by_path`, as well as the full name of the
+/// signal as reported by `list_signals`.
+///
 /// # Arguments
 /// * `hierarchy` - The waveform hierarchy to search
 /// * `path` - The hierarchical path to signal (e.g., "top.module.signal")
@@ -30,6 +279,9 @@ pub fn find_var_by_path(hierarchy: &wellen::Hierarchy, path: &str) -> Option<wel
 /// # Returns
 /// `Some(SignalRef)` if signal is found, `None` otherwise.
 pub fn find_signal_by_path(hierarchy: &wellen::Hierarchy, path: &str) -> Option<wellen::SignalRef> {
+    if let Some(var_ref) = find_var_by_path(hierarchy, path) {
+        return Some(hierarchy[var_ref].signal_ref());
+    }
     for var in hierarchy.iter_vars() {
         let signal_path = var.full_name(hierarchy);
         if signal_path == path {
@@ -41,6 +293,8 @@ pub fn find_signal_by_path(hierarchy: &wellen::Hierarchy, path: &str) -> Option<
 
 /// Find a scope by its hierarchical path in waveform hierarchy.
 ///
+/// Accepts the path syntax of `find_var_by_path`, as well as the full name of the scope.
+///
 /// # Arguments
 /// * `hierarchy` - The waveform hierarchy to search
 /// * `path` - The hierarchical path to scope (e.g., "top.module")
@@ -48,6 +302,9 @@ pub fn find_signal_by_path(hierarchy: &wellen::Hierarchy, path: &str) -> Option<
 /// # Returns
 /// `Some(ScopeRef)` if scope is found, `None` otherwise.
 pub fn find_scope_by_path(hierarchy: &wellen::Hierarchy, path: &str) -> Option<wellen::ScopeRef> {
+    if let Some(scope_ref) = find_scope_by_names(hierarchy, &split_path(path)) {
+        return Some(scope_ref);
+    }
     for scope_ref in hierarchy.scopes() {
         let scope = &hierarchy[scope_ref];
         let scope_path = scope.full_name(hierarchy);
@@ -82,6 +339,68 @@ fn find_scope_by_path_recursive(
     None
 }
 
+/// Resolve a path that may be relative to enclosing scopes.
+///
+/// The path is looked up in each of `scopes`, innermost (last) first, and on its own
+/// from the top of the hierarchy; it must match in exactly one of these places, and
+/// the path of that match is returned. A path starting with `$root.` is always taken
+/// from the top of the hierarchy. Without scopes, the path is returned unchanged.
+///
+/// # Arguments
+/// * `path` - The path as written, e.g. "core.valid"
+/// * `scopes` - Paths of the enclosing scopes, outermost first
+/// * `kind` - What the path refers to, for error messages (e.g., "Signal")
+/// * `lookup` - Finds the scope or variable at a full path
+pub(super) fn resolve_relative_path<T: PartialEq>(
+    path: &str,
+    scopes: &[String],
+    kind: &str,
+    lookup: impl Fn(&str) -> Option<T>,
+) -> Result<String, String> {
+    if let Some(absolute) = path.strip_prefix("$root.") {
+        return Ok(absolute.to_string());
+    }
+    if scopes.is_empty() {
+        return Ok(path.to_string());
+    }
+
+    let candidates = scopes
+        .iter()
+        .rev()
+        .map(|scope| format!("{}.{}", scope, path))
+        .chain(std::iter::once(path.to_string()));
+    let mut found: Vec<(String, T)> = Vec::new();
+    for candidate in candidates {
+        if let Some(target) = lookup(&candidate) {
+            // Nested scopes can reach the same target through different paths
+            if !found.iter().any(|(_, existing)| *existing == target) {
+                found.push((candidate, target));
+            }
+        }
+    }
+
+    match found.len() {
+        0 => Err(format!(
+            "{} not found: {} (relative to scope {})",
+            kind,
+            path,
+            scopes.join(", ")
+        )),
+        1 => Ok(found.remove(0).0),
+        _ => Err(format!(
+            "{} {} is ambiguous: it matches {}; use a longer path, or $root.{} for the path from the top of the hierarchy",
+            kind,
+            path,
+            found
+                .iter()
+                .map(|(candidate, _)| candidate.as_str())
+                .collect::<Vec<_>>()
+                .join(" and "),
+            path
+        )),
+    }
+}
+
 /// Collect signals from a scope and optionally its children recursively.
 pub(super) fn collect_signals_from_scope(
     hierarchy: &wellen::Hierarchy,
@@ -91,11 +410,12 @@ pub(super) fn collect_signals_from_scope(
 ) -> Vec<String> {
     let mut signals = Vec::new();
     let scope = &hierarchy[scope_ref];
+    let scope_path = scope.full_name(hierarchy);
 
     // Collect variables directly in this scope
     for var_ref in scope.vars(hierarchy) {
-        let var = &hierarchy[var_ref];
-        let path = var.full_name(hierarchy);
+        // The full name of the variable, without looking up its scope again
+        let path = format!("{}.{}", scope_path, hierarchy[var_ref].name(hierarchy));
 
         // Apply name pattern filter if provided
         if let Some(pattern) = name_pattern {
@@ -2,17 +2,34 @@
 //!
 //! This library provides utilities for working with waveform files.
 
+pub mod assertion;
 pub mod condition;
+pub mod derived;
 pub mod formatting;
 pub mod hierarchy;
+mod program;
+pub mod sequence;
 pub mod signal;
+pub mod store;
+pub mod value;
 
 // Re-export public functions
+pub use assertion::check_assertions;
+pub use assertion::{Counterexample, PropertyReport};
 pub use condition::find_conditional_events;
+pub use condition::find_conditional_events_with_options;
+#[cfg(feature = "test-utils")]
+pub use condition::{count_matching_samples, evaluate_condition_values, Evaluator};
+pub use condition::{ClockEdge, ConditionOptions, SamplingClock};
+pub use derived::{find_derived_signal_events, get_derived_signal_metadata};
+pub use derived::{read_derived_signal_values, DerivedSignal, DerivedSignals};
 pub use formatting::{format_signal_value, format_time};
 pub use hierarchy::find_scope_by_path;
 pub use hierarchy::find_signal_by_path;
+pub use hierarchy::PathIndex;
+pub use sequence::find_sequence_matches;
 pub use signal::find_signal_events;
-pub use signal::get_signal_metadata;
 pub use signal::list_signals;
 pub use signal::read_signal_values;
+pub use signal::{get_signal_metadata, get_signal_metadata_with_index, list_signals_with_index};
+pub use store::{WaveformBody, WaveformEntry, WaveformStore};
@@ -10,15 +10,16 @@ use rmcp::{
     ErrorData as McpError, ServerHandler, ServiceExt,
 };
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tokio_util::sync::CancellationToken;
 use tracing_subscriber::prelude::*;
 use waveform_mcp::{
-    find_conditional_events, find_signal_by_path, find_signal_events, get_signal_metadata,
-    list_signals, read_signal_values,
+    check_assertions, find_conditional_events_with_options, find_derived_signal_events,
+    find_sequence_matches, find_signal_events, get_derived_signal_metadata,
+    get_signal_metadata_with_index, list_signals_with_index, read_derived_signal_values,
+    read_signal_values, ClockEdge, ConditionOptions, SamplingClock, WaveformEntry, WaveformStore,
 };
 
 /// Command line arguments for the waveform MCP server
@@ -34,9 +35,6 @@ struct Args {
     bind_address: String,
 }
 
-// Waveform store - using RwLock for interior mutability
-type WaveformStore = Arc<RwLock<HashMap<String, wellen::simple::Waveform>>>;
-
 #[derive(Debug, Clone)]
 pub struct WaveformHandler {
     waveforms: WaveformStore,
@@ -125,12 +123,87 @@ pub struct FindConditionalEventsArgs {
     pub end_time_index: Option<usize>,
     #[serde(default = "default_find_conditional_events_limit")]
     pub limit: Option<isize>,
+    #[serde(default)]
+    pub clock: Option<String>,
+    #[serde(default)]
+    pub clock_edge: Option<String>,
+    #[serde(default)]
+    pub scope: Option<String>,
 }
 
 fn default_find_conditional_events_limit() -> Option<isize> {
     Some(100)
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct FindSequenceMatchesArgs {
+    pub waveform_id: String,
+    pub property: String,
+    #[serde(default = "default_start_time")]
+    pub start_time_index: Option<usize>,
+    #[serde(default = "default_end_time")]
+    pub end_time_index: Option<usize>,
+    #[serde(default = "default_find_conditional_events_limit")]
+    pub limit: Option<isize>,
+    #[serde(default)]
+    pub failures_only: bool,
+    #[serde(default)]
+    pub clock: Option<String>,
+    #[serde(default)]
+    pub clock_edge: Option<String>,
+    #[serde(default)]
+    pub scope: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct CheckAssertionsArgs {
+    pub waveform_id: String,
+    pub properties: Vec<String>,
+    #[serde(default = "default_start_time")]
+    pub start_time_index: Option<usize>,
+    #[serde(default = "default_end_time")]
+    pub end_time_index: Option<usize>,
+    #[serde(default = "default_max_counterexamples")]
+    pub max_counterexamples: Option<usize>,
+    #[serde(default)]
+    pub clock: Option<String>,
+    #[serde(default)]
+    pub clock_edge: Option<String>,
+    #[serde(default)]
+    pub scope: Option<String>,
+}
+
+fn default_max_counterexamples() -> Option<usize> {
+    Some(5)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct DefineSignalArgs {
+    pub waveform_id: String,
+    pub name: String,
+    pub expression: String,
+}
+
+/// Build the sampling clock from the optional `clock` and `clock_edge` tool arguments.
+fn sampling_clock(
+    clock: &Option<String>,
+    clock_edge: &Option<String>,
+) -> Result<Option<SamplingClock>, McpError> {
+    let Some(signal) = clock else {
+        return Ok(None);
+    };
+    let edge = match clock_edge {
+        Some(edge) => edge
+            .parse::<ClockEdge>()
+            .map_err(|e| McpError::invalid_params(e, None))?,
+        None => ClockEdge::Posedge,
+    };
+    Ok(Some(SamplingClock {
+        signal: signal.clone(),
+        edge,
+    }))
+}
+
 impl Default for WaveformHandler {
     fn default() -> Self {
         Self::new()
@@ -140,7 +213,7 @@ impl Default for WaveformHandler {
 #[tool_router]
 impl WaveformHandler {
     pub fn new() -> Self {
-        Self::with_store(Arc::new(RwLock::new(HashMap::new())))
+        Self::with_store(WaveformStore::default())
     }
 
     pub fn with_store(waveforms: WaveformStore) -> Self {
@@ -150,7 +223,26 @@ impl WaveformHandler {
         }
     }
 
-    #[tool(description = "Open a VCD or FST waveform file")]
+    /// An open waveform, whose body may still be loading.
+    async fn entry(&self, waveform_id: &str) -> Result<Arc<RwLock<WaveformEntry>>, McpError> {
+        self.waveforms.get(waveform_id).await.ok_or_else(|| {
+            McpError::invalid_params(format!("Waveform not found: {}", waveform_id), None)
+        })
+    }
+
+    /// An open waveform, once its signal values have been read in the background.
+    async fn loaded_entry(
+        &self,
+        waveform_id: &str,
+    ) -> Result<Arc<RwLock<WaveformEntry>>, McpError> {
+        self.waveforms.get_loaded(waveform_id).await.ok_or_else(|| {
+            McpError::invalid_params(format!("Waveform not found: {}", waveform_id), None)
+        })
+    }
+
+    #[tool(
+        description = "Open a VCD or FST waveform file. Only the header is read before replying, so signals can be listed right away; signal values are read in the background and tools that need them wait until they are loaded."
+    )]
     async fn open_waveform(
         &self,
         args: Parameters<OpenWaveformArgs>,
@@ -165,16 +257,6 @@ impl WaveformHandler {
             ))]));
         }
 
-        let waveform = match wellen::simple::read(&path) {
-            Ok(w) => w,
-            Err(e) => {
-                return Ok(CallToolResult::error(vec![Content::text(format!(
-                    "Failed to read waveform: {}",
-                    e
-                ))]));
-            }
-        };
-
         let alias = args.alias.clone().unwrap_or_else(|| {
             path.file_name()
                 .and_then(|n| n.to_str())
@@ -182,8 +264,12 @@ impl WaveformHandler {
                 .to_string()
         });
 
-        let mut waveforms = self.waveforms.write().await;
-        waveforms.insert(alias.clone(), waveform);
+        if let Err(e) = self.waveforms.open(&path, &alias).await {
+            return Ok(CallToolResult::error(vec![Content::text(format!(
+                "Failed to read waveform: {}",
+                e
+            ))]));
+        }
 
         Ok(CallToolResult::success(vec![Content::text(format!(
             "Waveform opened successfully with alias: {}",
@@ -199,17 +285,17 @@ impl WaveformHandler {
         args: Parameters<ListSignalsArgs>,
     ) -> Result<CallToolResult, McpError> {
         let args = &args.0;
-        let waveforms = self.waveforms.read().await;
-
-        let waveform = waveforms.get(&args.waveform_id).ok_or_else(|| {
-            McpError::invalid_params(format!("Waveform not found: {}", args.waveform_id), None)
-        })?;
-
-        let hierarchy = waveform.hierarchy();
+        let entry = self.entry(&args.waveform_id).await?;
+        let entry = entry.read().await;
+        let hierarchy = entry
+            .body
+            .hierarchy()
+            .map_err(|e| McpError::internal_error(e, None))?;
         let recursive = args.recursive.unwrap_or(true);
 
-        let signals = list_signals(
+        let signals = list_signals_with_index(
             hierarchy,
+            &entry.paths,
             args.name_pattern.as_deref(),
             args.hierarchy_prefix.as_deref(),
             recursive,
@@ -231,19 +317,13 @@ impl WaveformHandler {
         args: Parameters<ReadSignalArgs>,
     ) -> Result<CallToolResult, McpError> {
         let args = &args.0;
-        let mut waveforms = self.waveforms.write().await;
-
-        let waveform = waveforms.get_mut(&args.waveform_id).ok_or_else(|| {
-            McpError::invalid_params(format!("Waveform not found: {}", args.waveform_id), None)
-        })?;
-
-        let hierarchy = waveform.hierarchy();
-        let signal_ref = find_signal_by_path(hierarchy, &args.signal_path).ok_or_else(|| {
-            McpError::invalid_params(format!("Signal not found: {}", args.signal_path), None)
-        })?;
-
-        // Load the signal data
-        waveform.load_signals(&[signal_ref]);
+        let entry = self.loaded_entry(&args.waveform_id).await?;
+        let mut entry = entry.write().await;
+        let entry = &mut *entry;
+        let waveform = entry
+            .body
+            .waveform()
+            .map_err(|e| McpError::internal_error(e, None))?;
 
         // Determine which time indices to read
         let indices_to_read: Vec<usize> = if let Some(ref indices) = args.time_indices {
@@ -256,8 +336,27 @@ impl WaveformHandler {
             )]));
         };
 
-        let results = read_signal_values(waveform, signal_ref, &indices_to_read)
-            .map_err(|e| McpError::internal_error(e, None))?;
+        let results = if let Some(derived) = entry.derived.get(&args.signal_path) {
+            read_derived_signal_values(waveform, derived, &indices_to_read)
+                .map_err(|e| McpError::internal_error(e, None))?
+        } else {
+            let hierarchy = waveform.hierarchy();
+            let signal_ref = entry
+                .paths
+                .find_signal(hierarchy, &args.signal_path)
+                .ok_or_else(|| {
+                    McpError::invalid_params(
+                        format!("Signal not found: {}", args.signal_path),
+                        None,
+                    )
+                })?;
+
+            // Load the signal data
+            waveform.load_signals(&[signal_ref]);
+
+            read_signal_values(waveform, signal_ref, &indices_to_read)
+                .map_err(|e| McpError::internal_error(e, None))?
+        };
 
         Ok(CallToolResult::success(vec![Content::text(
             results.join("\n"),
@@ -272,16 +371,18 @@ impl WaveformHandler {
         args: Parameters<GetSignalInfoArgs>,
     ) -> Result<CallToolResult, McpError> {
         let args = &args.0;
-        let waveforms = self.waveforms.read().await;
-
-        let waveform = waveforms.get(&args.waveform_id).ok_or_else(|| {
-            McpError::invalid_params(format!("Waveform not found: {}", args.waveform_id), None)
-        })?;
-
-        let hierarchy = waveform.hierarchy();
+        let entry = self.entry(&args.waveform_id).await?;
+        let entry = entry.read().await;
+        let hierarchy = entry
+            .body
+            .hierarchy()
+            .map_err(|e| McpError::internal_error(e, None))?;
 
-        let info = get_signal_metadata(hierarchy, &args.signal_path)
-            .map_err(|e| McpError::invalid_params(e, None))?;
+        let info = match entry.derived.get(&args.signal_path) {
+            Some(derived) => get_derived_signal_metadata(derived),
+            None => get_signal_metadata_with_index(hierarchy, &entry.paths, &args.signal_path)
+                .map_err(|e| McpError::invalid_params(e, None))?,
+        };
 
         Ok(CallToolResult::success(vec![Content::text(info)]))
     }
@@ -294,19 +395,13 @@ impl WaveformHandler {
         args: Parameters<FindSignalEventsArgs>,
     ) -> Result<CallToolResult, McpError> {
         let args = &args.0;
-        let mut waveforms = self.waveforms.write().await;
-
-        let waveform = waveforms.get_mut(&args.waveform_id).ok_or_else(|| {
-            McpError::invalid_params(format!("Waveform not found: {}", args.waveform_id), None)
-        })?;
-
-        let hierarchy = waveform.hierarchy();
-        let signal_ref = find_signal_by_path(hierarchy, &args.signal_path).ok_or_else(|| {
-            McpError::invalid_params(format!("Signal not found: {}", args.signal_path), None)
-        })?;
-
-        // Load the signal data
-        waveform.load_signals(&[signal_ref]);
+        let entry = self.loaded_entry(&args.waveform_id).await?;
+        let mut entry = entry.write().await;
+        let entry = &mut *entry;
+        let waveform = entry
+            .body
+            .waveform()
+            .map_err(|e| McpError::internal_error(e, None))?;
 
         let time_table = waveform.time_table();
         let start_idx = args.start_time_index.unwrap_or(0);
@@ -315,8 +410,27 @@ impl WaveformHandler {
             .unwrap_or(time_table.len().saturating_sub(1));
         let limit = args.limit.unwrap_or(-1);
 
-        let events = find_signal_events(waveform, signal_ref, start_idx, end_idx, limit)
-            .map_err(|e| McpError::internal_error(e, None))?;
+        let events = if let Some(derived) = entry.derived.get(&args.signal_path) {
+            find_derived_signal_events(waveform, derived, start_idx, end_idx, limit)
+                .map_err(|e| McpError::internal_error(e, None))?
+        } else {
+            let hierarchy = waveform.hierarchy();
+            let signal_ref = entry
+                .paths
+                .find_signal(hierarchy, &args.signal_path)
+                .ok_or_else(|| {
+                    McpError::invalid_params(
+                        format!("Signal not found: {}", args.signal_path),
+                        None,
+                    )
+                })?;
+
+            // Load the signal data
+            waveform.load_signals(&[signal_ref]);
+
+            find_signal_events(waveform, signal_ref, start_idx, end_idx, limit)
+                .map_err(|e| McpError::internal_error(e, None))?
+        };
 
         Ok(CallToolResult::success(vec![Content::text(format!(
             "Found {} events for signal '{}' (time range: {} to {}):\n{}",
@@ -329,18 +443,20 @@ impl WaveformHandler {
     }
 
     #[tool(
-        description = "Find events where a condition is satisfied. Supports signal paths, bitwise operators (~, &, |, ^), boolean operators (&&, ||, !), comparison operators (==, !=), $past(), bit extraction, and Verilog-style literals. Bitwise operators: ~ (NOT), & (AND), | (OR), ^ (XOR). Bit extraction: signal[bit] or signal[msb:lsb]. $past(signal) reads the signal value from the previous time index. Operator precedence: ~, ! (highest), ==, !=, &, ^, |, &&, || (lowest). Examples: rising edge '!$past(TOP.signal) && TOP.signal', falling edge '$past(TOP.signal) && !TOP.signal', handshake cycles 'TOP.valid && TOP.ready', check bit 'TOP.flags & 4'b0001', bit extract 'TOP.data[7:0] == 8'hFF'. Optional: start_time_index, end_time_index, limit."
+        description = "Find events where a condition is satisfied. Supports signal paths (generate indices like TOP.gen[3].sig, escaped identifiers like TOP.\\bus.a ending at whitespace, and backtick-quoted names like TOP.`a.b` or TOP.`bits[0]`; names after the first may start with a digit, like TOP.1a, and a relative name that is a keyword (with, edge, posedge or negedge) must be quoted, like `edge`), bitwise operators (~, &, |, ^), boolean operators (&&, ||, !), comparison operators (==, !=, <, <=, >, >=; relational comparisons are unsigned unless both operands are signed), case equality (===, !==) and wildcard equality (==?, !=?), arithmetic operators (+, -, *, /, %; Verilog width and wrap-around rules), shift operators (<<, >>, <<<, >>>), concatenation {a, b} and replication {N{a}}, reduction operators (unary &, |, ^, ~&, ~|, ~^), conditional values (cond ? a : b; bits that differ are X when cond is unknown), real values (real signals and literals like 1.2 or 2.5e-3 with comparisons, + - * /, $abs(x) and $approx(a, b, tolerance), e.g. 'TOP.vout > 1.2' or '$approx(TOP.vout, 1.2, 0.05)'), strings (string signals and literals like \"RESET\" with comparisons and $contains(text, part), e.g. 'TOP.tb.phase == \"RESET\"'), time ($time and time literals like 10ns or 2.5us, e.g. '$time > 1.5us && TOP.err'), signed arithmetic ($signed(), $unsigned(), signed literals like 8'sd5, unary -; like Verilog an operation is signed only if all operands are signed), $past(), part-selects, and Verilog-style literals of any width (4'b0101, 12'o777, 3'd2, 128'hDEAD_BEEF; x, z or ? digits in binary/octal/hex), unsized literals (42, 'hFF) and fill literals ('0, '1, 'x, 'z) that take the width of the other operand. Bitwise operators: ~ (NOT), & (AND), | (OR), ^ (XOR). Part-selects: signal[bit], signal[msb:lsb], signal[base +: width] and signal[base -: width] on any expression, e.g. 'TOP.data[TOP.sel*8 +: 8]' or '(TOP.a ^ TOP.b)[3:0]'; indices may be expressions, signals use their declared range (e.g. [15:8] or [0:7]), and unknown or out-of-range bits read as X. Evaluation is four-state: X and Z propagate through operators and conditions that evaluate to X or Z count as false. $past(signal) reads the signal value from the previous time index, $past(signal, N) from N time indices earlier. $rose(expr)/$fell(expr) detect the least significant bit changing to 1/0, $stable(expr)/$changed(expr) detect whether the value changed. $countones(expr) counts bits set to 1, $onehot(expr)/$onehot0(expr) check that exactly/at most one bit is 1, $isunknown(expr) checks for X or Z bits. Operator precedence: [] part-select (highest), ~, !, unary -, unary reductions, *, /, %, +, -, <<, >>, <<<, >>>, <, <=, >, >=, ==, !=, ===, !==, ==?, !=?, &, ^, |, &&, ||, ?: (lowest). Examples: rising edge '$rose(TOP.signal)' or '!$past(TOP.signal) && TOP.signal', falling edge '$fell(TOP.signal)', handshake cycles 'TOP.valid && TOP.ready', check bit 'TOP.flags & 4'b0001', bit extract 'TOP.data[7:0] == 8'hFF', range check 'TOP.addr >= 8'h10 && TOP.addr < 8'h20', FIFO occupancy 'TOP.wr_ptr - TOP.rd_ptr == 8'd16', reassembled bus '{TOP.hi, TOP.lo} == 16'hBEEF', all-X bus 'TOP.data === 8'bx', conflicting grants '!$onehot0(TOP.grant)', parity error '^TOP.data != TOP.parity', negative threshold '$signed(TOP.acc) < -16'sd100'. To evaluate only at clock edges, prefix the condition with '@(posedge TOP.clk)' (or negedge/edge) or pass clock (and clock_edge: posedge, negedge or edge; default posedge); values are then sampled just before each edge and $past refers to previous clock edges. To shorten signal names, pass scope (e.g. 'TOP.soc.cpu0') or write 'with TOP.soc.cpu0 { valid && ready }': names are then looked up in the scope first and from the top of the hierarchy otherwise, a name found in both is an error, and '$root.' forces a path from the top. Signals defined with define_signal can be used by name. A signal with an enum type (see get_signal_info) can be compared against the names of its values, e.g. 'TOP.fsm.state == IDLE'. Optional: start_time_index, end_time_index, limit, clock, clock_edge, scope."
     )]
     async fn find_conditional_events(
         &self,
         args: Parameters<FindConditionalEventsArgs>,
     ) -> Result<CallToolResult, McpError> {
         let args = &args.0;
-        let mut waveforms = self.waveforms.write().await;
-
-        let waveform = waveforms.get_mut(&args.waveform_id).ok_or_else(|| {
-            McpError::invalid_params(format!("Waveform not found: {}", args.waveform_id), None)
-        })?;
+        let entry = self.loaded_entry(&args.waveform_id).await?;
+        let mut entry = entry.write().await;
+        let entry = &mut *entry;
+        let waveform = entry
+            .body
+            .waveform()
+            .map_err(|e| McpError::internal_error(e, None))?;
 
         let time_table = waveform.time_table();
         let start_idx = args.start_time_index.unwrap_or(0);
@@ -349,8 +465,22 @@ impl WaveformHandler {
             .unwrap_or(time_table.len().saturating_sub(1));
         let limit = args.limit.unwrap_or(-1);
 
-        let events = find_conditional_events(waveform, &args.condition, start_idx, end_idx, limit)
-            .map_err(|e| McpError::invalid_params(e, None))?;
+        let options = ConditionOptions {
+            clock: sampling_clock(&args.clock, &args.clock_edge)?,
+            scope: args.scope.clone(),
+            derived: entry.derived.clone(),
+            paths: Some(entry.paths.clone()),
+        };
+
+        let events = find_conditional_events_with_options(
+            waveform,
+            &args.condition,
+            start_idx,
+            end_idx,
+            limit,
+            &options,
+        )
+        .map_err(|e| McpError::invalid_params(e, None))?;
 
         Ok(CallToolResult::success(vec![Content::text(format!(
             "Found {} events for condition '{}' (time range: {} to {}):\n{}",
@@ -361,6 +491,136 @@ impl WaveformHandler {
             events.join("\n")
         ))]))
     }
+
+    #[tool(
+        description = "Find where a sequence or property matches, SystemVerilog assertion style. A property is a sequence of conditions (same syntax as find_conditional_events) separated by cycle delays: '##N' waits N cycles and '##[m:n]' waits between m and n cycles, and a sequence may start with a delay. 'a |-> b' (overlapping implication) requires sequence b to match starting in the cycle sequence a ends; 'a |=> b' (non-overlapping implication) starts b one cycle later. Every cycle in the time range starts a new attempt. Sequences report each start where they match ('Match'); implications report each start where the antecedent matches as 'Pass' or 'Fail'. With failures_only, only failing attempts are reported. A cycle is one time index, or one clock edge when the property is prefixed with '@(posedge TOP.clk)' (or negedge/edge) or clock is given (clock_edge: posedge, negedge or edge; default posedge). Examples: request not acknowledged within 1 to 4 cycles '@(posedge TOP.clk) TOP.req |-> ##[1:4] TOP.ack' with failures_only, write followed by a response two cycles later 'TOP.wr_en ##2 TOP.resp_valid', valid held until ready 'TOP.valid && !TOP.ready |=> TOP.valid'. Signal names may be relative to scope, as in find_conditional_events. Optional: start_time_index, end_time_index, limit, failures_only, clock, clock_edge, scope."
+    )]
+    async fn find_sequence_matches(
+        &self,
+        args: Parameters<FindSequenceMatchesArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let args = &args.0;
+        let entry = self.loaded_entry(&args.waveform_id).await?;
+        let mut entry = entry.write().await;
+        let entry = &mut *entry;
+        let waveform = entry
+            .body
+            .waveform()
+            .map_err(|e| McpError::internal_error(e, None))?;
+
+        let time_table = waveform.time_table();
+        let start_idx = args.start_time_index.unwrap_or(0);
+        let end_idx = args
+            .end_time_index
+            .unwrap_or(time_table.len().saturating_sub(1));
+        let limit = args.limit.unwrap_or(-1);
+
+        let options = ConditionOptions {
+            clock: sampling_clock(&args.clock, &args.clock_edge)?,
+            scope: args.scope.clone(),
+            derived: entry.derived.clone(),
+            paths: Some(entry.paths.clone()),
+        };
+
+        let matches = find_sequence_matches(
+            waveform,
+            &args.property,
+            start_idx,
+            end_idx,
+            limit,
+            args.failures_only,
+            &options,
+        )
+        .map_err(|e| McpError::invalid_params(e, None))?;
+
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "Found {} results for property '{}' (time range: {} to {}):\n{}",
+            matches.len(),
+            args.property,
+            start_idx,
+            end_idx,
+            matches.join("\n")
+        ))]))
+    }
+
+    #[tool(
+        description = "Check a list of properties against a waveform and report, per property, how many attempts passed and failed, with counterexample traces for the first failures. Properties use the find_sequence_matches syntax: a plain condition must hold at every cycle (e.g. '!(TOP.grant_a && TOP.grant_b)'), a sequence must match starting at every cycle, and an implication ('a |-> b', 'a |=> b') is checked wherever its antecedent matches. Each counterexample lists the values of every signal in the property at each cycle of the failing window. A cycle is one time index, or one clock edge when a property is prefixed with '@(posedge TOP.clk)' (or negedge/edge) or clock is given (clock_edge: posedge, negedge or edge; default posedge). Signal names may be relative to scope, as in find_conditional_events. Optional: start_time_index, end_time_index, max_counterexamples (default 5), clock, clock_edge, scope."
+    )]
+    async fn check_assertions(
+        &self,
+        args: Parameters<CheckAssertionsArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let args = &args.0;
+        let entry = self.loaded_entry(&args.waveform_id).await?;
+        let mut entry = entry.write().await;
+        let entry = &mut *entry;
+        let waveform = entry
+            .body
+            .waveform()
+            .map_err(|e| McpError::internal_error(e, None))?;
+
+        let time_table = waveform.time_table();
+        let start_idx = args.start_time_index.unwrap_or(0);
+        let end_idx = args
+            .end_time_index
+            .unwrap_or(time_table.len().saturating_sub(1));
+        let max_counterexamples = args.max_counterexamples.unwrap_or(5);
+
+        let options = ConditionOptions {
+            clock: sampling_clock(&args.clock, &args.clock_edge)?,
+            scope: args.scope.clone(),
+            derived: entry.derived.clone(),
+            paths: Some(entry.paths.clone()),
+        };
+
+        let reports = check_assertions(
+            waveform,
+            &args.properties,
+            start_idx,
+            end_idx,
+            max_counterexamples,
+            &options,
+        )
+        .map_err(|e| McpError::invalid_params(e, None))?;
+
+        let failing = reports.iter().filter(|report| report.failed > 0).count();
+        let reports: Vec<String> = reports.iter().map(|report| report.to_string()).collect();
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "Checked {} properties, {} failing (time range: {} to {}):\n{}",
+            reports.len(),
+            failing,
+            start_idx,
+            end_idx,
+            reports.join("\n")
+        ))]))
+    }
+
+    #[tool(
+        description = "Define a named derived signal for a waveform, e.g. name 'axi_aw_fire' with expression 'TOP.awvalid && TOP.awready'. The expression uses the find_conditional_events syntax without a clock, and may use derived signals defined before. The name can then be used like a signal in conditions, properties and assertions, and as signal_path in read_signal, get_signal_info and find_signal_events. Defining a name again replaces the definition; signals defined from the old definition keep it."
+    )]
+    async fn define_signal(
+        &self,
+        args: Parameters<DefineSignalArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let args = &args.0;
+        let entry = self.entry(&args.waveform_id).await?;
+        let mut entry = entry.write().await;
+        let entry = &mut *entry;
+        let hierarchy = entry
+            .body
+            .hierarchy()
+            .map_err(|e| McpError::internal_error(e, None))?;
+
+        let derived = entry
+            .derived
+            .define(hierarchy, &args.name, &args.expression)
+            .map_err(|e| McpError::invalid_params(e, None))?;
+
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "Defined derived signal {} := {} (width {})",
+            derived.name, derived.expression, derived.width
+        ))]))
+    }
 }
 
 #[tool_handler]
@@ -372,7 +632,7 @@ impl ServerHandler for WaveformHandler {
             server_info: Implementation::from_build_env(),
             instructions: Some(
                 "MCP server for reading VCD/FST waveform files using the wellen library. \
-                Available tools: open_waveform, list_signals, read_signal, get_signal_info, find_signal_events, find_conditional_events."
+                Available tools: open_waveform, list_signals, read_signal, get_signal_info, find_signal_events, find_conditional_events, find_sequence_matches, check_assertions, define_signal."
                     .to_string(),
             ),
         }
@@ -396,7 +656,7 @@ async fn main() -> anyhow::Result<()> {
         let ct = CancellationToken::new();
 
         // Create a shared waveform store for all HTTP sessions
-        let shared_waveforms: WaveformStore = Arc::new(RwLock::new(HashMap::new()));
+        let shared_waveforms = WaveformStore::default();
 
         let service = StreamableHttpService::new(
             move || Ok(WaveformHandler::with_store(shared_waveforms.clone())),
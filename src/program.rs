@@ -0,0 +1,892 @@
+//! Conditions compiled to a flat program for fast evaluation.
+//!
+//! `evaluate_condition` walks the condition tree at every sample, looking up signals by
+//! name and working out the width and signedness of every operand as it goes. A
+//! `Program` does that work once: signals are resolved to their data, operand sizes are
+//! fixed, constant subexpressions are folded, and the tree is lowered to a list of
+//! instructions for a stack machine. Values of at most 64 bits are kept in machine
+//! words, so most conditions are evaluated without big integer arithmetic.
+
+use super::{
+    condition::bit_offset, condition::check_indexed_width, condition::check_range_direction,
+    condition::comparison_type, condition::declared_range, condition::evaluate_operator,
+    condition::expression_signed, condition::expression_type, condition::expression_width,
+    condition::literal_to_value, condition::part_select_width, condition::replication_width,
+    condition::self_determined_sizing, condition::signal_value_to_value, condition::Condition,
+    condition::EvalContext, condition::Literal, condition::Selection, condition::ValueType,
+    value::ArithOp, value::CompareOp, value::SampledFunction, value::ShiftOp, value::SmallValue,
+    value::Value,
+};
+
+/// A value on the stack of a running program.
+#[derive(Debug, Clone)]
+enum Slot {
+    Small(SmallValue),
+    Big(Value),
+}
+
+impl Slot {
+    /// Wrap a value, using a machine word if it is at most 64 bits wide.
+    fn from_value(value: Value) -> Self {
+        match SmallValue::from_value(&value) {
+            Some(small) => Slot::Small(small),
+            None => Slot::Big(value),
+        }
+    }
+
+    fn into_value(self) -> Value {
+        match self {
+            Slot::Small(value) => value.to_value(),
+            Slot::Big(value) => value,
+        }
+    }
+
+    fn width(&self) -> u32 {
+        match self {
+            Slot::Small(value) => value.width,
+            Slot::Big(value) => value.width,
+        }
+    }
+
+    fn truth(&self) -> Option<bool> {
+        match self {
+            Slot::Small(value) => value.truth(),
+            Slot::Big(value) => value.truth(),
+        }
+    }
+
+    fn has_unknown(&self) -> bool {
+        match self {
+            Slot::Small(value) => value.has_unknown(),
+            Slot::Big(value) => value.has_unknown(),
+        }
+    }
+
+    fn count_ones(&self) -> u64 {
+        match self {
+            Slot::Small(value) => value.count_ones(),
+            Slot::Big(value) => value.count_ones(),
+        }
+    }
+
+    /// Apply an operation whose result is `width` bits wide, with machine words if possible.
+    fn map(
+        self,
+        width: u32,
+        small: impl FnOnce(SmallValue) -> SmallValue,
+        big: impl FnOnce(Value) -> Value,
+    ) -> Slot {
+        match self {
+            Slot::Small(value) if width <= 64 => Slot::Small(small(value)),
+            slot => Slot::from_value(big(slot.into_value())),
+        }
+    }
+
+    /// Apply an operation on two values whose result is `width` bits wide.
+    fn combine(
+        self,
+        other: Slot,
+        width: u32,
+        small: impl FnOnce(&SmallValue, &SmallValue) -> SmallValue,
+        big: impl FnOnce(&Value, &Value) -> Value,
+    ) -> Slot {
+        match (self, other) {
+            (Slot::Small(left), Slot::Small(right)) if width <= 64 => {
+                Slot::Small(small(&left, &right))
+            }
+            (left, right) => Slot::from_value(big(&left.into_value(), &right.into_value())),
+        }
+    }
+
+    /// The value of a bit index or part-select base, or `None` if it has X or Z bits.
+    fn index(self, signed: bool) -> Option<i64> {
+        match self {
+            Slot::Small(value) if value.has_unknown() => None,
+            // Indices too large for an i64 are far out of range; treat them like X
+            Slot::Small(value) if signed => i64::try_from(value.to_signed()).ok(),
+            Slot::Small(value) => i64::try_from(value.value).ok(),
+            Slot::Big(value) if value.has_unknown() => None,
+            Slot::Big(value) if signed => num_traits::ToPrimitive::to_i64(&value.to_signed()),
+            Slot::Big(value) => num_traits::ToPrimitive::to_i64(&value.value),
+        }
+    }
+}
+
+/// One step of a program. Operands are popped from the stack and results pushed.
+#[derive(Debug, Clone)]
+enum Instruction<'a> {
+    /// Push a literal or the value of a constant subexpression
+    Constant(Slot),
+    /// Push the value of a signal `offset` samples before the current one
+    Signal {
+        path: &'a str,
+        signal: &'a wellen::Signal,
+        width: u32,
+        offset: usize,
+    },
+    /// Push `$time` of the sample `offset` samples before the current one
+    Time {
+        offset: usize,
+    },
+    /// Evaluate the top-level operator of an expression with `evaluate_operator`, used
+    /// for real and string operations
+    Interpret {
+        condition: &'a Condition,
+        width: u32,
+        signed: bool,
+        offset: usize,
+    },
+    /// Replace the value by 0 if the sample `offset` samples before the current one
+    /// does not exist, as `$past` does
+    Past {
+        offset: usize,
+    },
+    SignExtend(u32),
+    LogicalAnd,
+    LogicalOr,
+    LogicalNot,
+    BitwiseAnd,
+    BitwiseOr,
+    BitwiseXor,
+    BitwiseNot(u32),
+    Negate(u32),
+    Arithmetic {
+        op: ArithOp,
+        width: u32,
+        signed: bool,
+    },
+    Shift {
+        op: ShiftOp,
+        width: u32,
+        signed: bool,
+    },
+    Compare {
+        op: CompareOp,
+        signed: bool,
+    },
+    /// Concatenate the given number of values, the first pushed most significant
+    Concat(usize),
+    Replicate(u32),
+    /// Select `num_bits` bits starting at bit `offset`
+    SelectRange {
+        offset: i64,
+        num_bits: u32,
+    },
+    /// Select the bit at the index on top of the stack
+    SelectBit {
+        declared: (i64, i64),
+        signed: bool,
+    },
+    /// Select `width` bits from the base on top of the stack, i.e. `[base +: width]`
+    SelectIndexed {
+        declared: (i64, i64),
+        width: u32,
+        ascending: bool,
+        signed: bool,
+    },
+    /// Compare the current value with the value at the previous sample, pushed last
+    Sampled(SampledFunction),
+    ReduceAnd,
+    ReduceOr,
+    ReduceXor,
+    CountOnes,
+    OneHot,
+    OneHot0,
+    IsUnknown,
+    /// Pop the condition of a `?:` and skip the first value if it is false
+    Branch {
+        skip: usize,
+    },
+    /// Skip the second value of a `?:` if its condition is true
+    SkipElse {
+        skip: usize,
+    },
+    /// Pick the value of a `?:`, or merge both values if the condition is unknown
+    Merge {
+        width: u32,
+        signed: bool,
+    },
+}
+
+impl Instruction<'_> {
+    /// Whether the instruction gives the same result at every sample.
+    fn is_constant(&self) -> bool {
+        !matches!(
+            self,
+            Instruction::Signal { .. }
+                | Instruction::Time { .. }
+                | Instruction::Interpret { .. }
+                | Instruction::Past { .. }
+        )
+    }
+}
+
+/// A condition compiled for repeated evaluation against one waveform.
+#[derive(Debug, Clone)]
+pub(super) struct Program<'a> {
+    instructions: Vec<Instruction<'a>>,
+}
+
+impl<'a> Program<'a> {
+    /// Compile a condition whose signals are loaded in `ctx`.
+    ///
+    /// Errors that `evaluate_condition` reports at every sample, such as a part-select
+    /// in the wrong direction, are reported here instead.
+    pub(super) fn compile(condition: &'a Condition, ctx: &EvalContext<'a>) -> Result<Self, String> {
+        let mut compiler = Compiler {
+            ctx,
+            instructions: Vec::new(),
+        };
+        compiler.compile_self_determined(condition, 0)?;
+        Ok(Program {
+            instructions: compiler.instructions,
+        })
+    }
+
+    /// Evaluate the program at a sample, giving the same value as `evaluate_condition`.
+    pub(super) fn evaluate(&self, ctx: &EvalContext, sample_idx: usize) -> Result<Value, String> {
+        Ok(run(&self.instructions, ctx, sample_idx)?.into_value())
+    }
+
+    /// Returns true only if the value at a sample is known to be non-zero.
+    pub(super) fn holds(&self, ctx: &EvalContext, sample_idx: usize) -> Result<bool, String> {
+        Ok(run(&self.instructions, ctx, sample_idx)?.truth() == Some(true))
+    }
+}
+
+struct Compiler<'c, 'a> {
+    ctx: &'c EvalContext<'a>,
+    instructions: Vec<Instruction<'a>>,
+}
+
+impl<'a> Compiler<'_, 'a> {
+    fn push(&mut self, instruction: Instruction<'a>) {
+        self.instructions.push(instruction);
+    }
+
+    fn compile_self_determined(
+        &mut self,
+        condition: &'a Condition,
+        offset: usize,
+    ) -> Result<(), String> {
+        let (width, signed) = self_determined_sizing(condition, self.ctx)?;
+        self.compile(condition, width, signed, offset)
+    }
+
+    /// Compile an expression in a context, like `evaluate_condition_with_width`.
+    ///
+    /// The expression is evaluated `offset` samples before the current one, which is
+    /// how `$past` is compiled.
+    fn compile(
+        &mut self,
+        condition: &'a Condition,
+        width: u32,
+        signed: bool,
+        offset: usize,
+    ) -> Result<(), String> {
+        let start = self.instructions.len();
+        self.compile_operator(condition, width, signed, offset)?;
+        if signed {
+            self.push(Instruction::SignExtend(width));
+        }
+        self.fold_constant(start)
+    }
+
+    /// Replace the instructions from `start` by their value if they do not depend on
+    /// the sample.
+    fn fold_constant(&mut self, start: usize) -> Result<(), String> {
+        let code = &self.instructions[start..];
+        if matches!(code, [Instruction::Constant(_)]) || !code.iter().all(Instruction::is_constant)
+        {
+            return Ok(());
+        }
+        let value = run(code, self.ctx, 0)?;
+        self.instructions.truncate(start);
+        self.push(Instruction::Constant(value));
+        Ok(())
+    }
+
+    /// Compile the top-level operator of an expression, like `evaluate_operator`.
+    fn compile_operator(
+        &mut self,
+        condition: &'a Condition,
+        width: u32,
+        signed: bool,
+        offset: usize,
+    ) -> Result<(), String> {
+        let hierarchy = self.ctx.waveform.hierarchy();
+        let signal_cache = self.ctx.signal_cache;
+        let interpret = Instruction::Interpret {
+            condition,
+            width,
+            signed,
+            offset,
+        };
+        match condition {
+            Condition::And(left, right) | Condition::Or(left, right) => {
+                self.compile_self_determined(left, offset)?;
+                self.compile_self_determined(right, offset)?;
+                self.push(match condition {
+                    Condition::And(_, _) => Instruction::LogicalAnd,
+                    _ => Instruction::LogicalOr,
+                });
+            }
+            Condition::BitwiseAnd(left, right)
+            | Condition::BitwiseOr(left, right)
+            | Condition::BitwiseXor(left, right) => {
+                self.compile(left, width, signed, offset)?;
+                self.compile(right, width, signed, offset)?;
+                self.push(match condition {
+                    Condition::BitwiseAnd(_, _) => Instruction::BitwiseAnd,
+                    Condition::BitwiseOr(_, _) => Instruction::BitwiseOr,
+                    _ => Instruction::BitwiseXor,
+                });
+            }
+            Condition::Ternary(cond, if_true, if_false) => {
+                // The condition is self-determined, the values are context-determined
+                self.compile_self_determined(cond, offset)?;
+                let branch = self.instructions.len();
+                self.push(Instruction::Branch { skip: 0 });
+                self.compile(if_true, width, signed, offset)?;
+                let skip_else = self.instructions.len();
+                self.push(Instruction::SkipElse { skip: 0 });
+                self.compile(if_false, width, signed, offset)?;
+                self.instructions[branch] = Instruction::Branch {
+                    skip: skip_else - branch,
+                };
+                self.instructions[skip_else] = Instruction::SkipElse {
+                    skip: self.instructions.len() - skip_else - 1,
+                };
+                self.push(Instruction::Merge { width, signed });
+            }
+            Condition::Not(expr) => {
+                self.compile_self_determined(expr, offset)?;
+                self.push(Instruction::LogicalNot);
+            }
+            Condition::BitwiseNot(expr) => {
+                self.compile(expr, width, signed, offset)?;
+                self.push(Instruction::BitwiseNot(width));
+            }
+            Condition::Neg(expr) => {
+                self.compile(expr, width, signed, offset)?;
+                self.push(Instruction::Negate(width));
+            }
+            Condition::Signed(expr) | Condition::Unsigned(expr) => {
+                self.compile_self_determined(expr, offset)?;
+            }
+            Condition::Scoped(_, expr) => self.compile(expr, width, signed, offset)?,
+            Condition::Add(left, right)
+            | Condition::Sub(left, right)
+            | Condition::Mul(left, right)
+            | Condition::Div(left, right)
+            | Condition::Mod(left, right) => {
+                self.compile(left, width, signed, offset)?;
+                self.compile(right, width, signed, offset)?;
+                let op = condition.arith_op().ok_or("Not an arithmetic operator")?;
+                self.push(Instruction::Arithmetic { op, width, signed });
+            }
+            Condition::ShiftLeft(left, right)
+            | Condition::ShiftRight(left, right)
+            | Condition::ArithShiftRight(left, right) => {
+                self.compile(left, width, signed, offset)?;
+                self.compile_self_determined(right, offset)?;
+                let op = condition.shift_op().ok_or("Not a shift operator")?;
+                self.push(Instruction::Shift { op, width, signed });
+            }
+            Condition::Concat(items) => {
+                for item in items {
+                    self.compile_self_determined(item, offset)?;
+                }
+                self.push(Instruction::Concat(items.len()));
+            }
+            Condition::Replicate(count, items) => {
+                if *count == 0 {
+                    return Err("Replication count must be greater than zero".to_string());
+                }
+                for item in items {
+                    self.compile_self_determined(item, offset)?;
+                }
+                self.push(Instruction::Concat(items.len()));
+                self.push(Instruction::Replicate(*count));
+            }
+            Condition::Signal(path) => {
+                let var_ref = signal_cache
+                    .get(path)
+                    .ok_or_else(|| format!("Signal not found in cache: {}", path))?;
+                let width = hierarchy[*var_ref]
+                    .length()
+                    .ok_or_else(|| format!("Signal {} has no width", path))?;
+                let signal = self
+                    .ctx
+                    .waveform
+                    .get_signal(hierarchy[*var_ref].signal_ref())
+                    .ok_or_else(|| format!("Signal not found in waveform: {}", path))?;
+                self.push(Instruction::Signal {
+                    path,
+                    signal,
+                    width,
+                    offset,
+                });
+            }
+            Condition::Select(expr, selection) => {
+                // The selected expression is self-determined
+                self.compile_self_determined(expr, offset)?;
+                let declared = declared_range(expr, hierarchy, signal_cache)?;
+                match selection {
+                    Selection::Bit(index) => {
+                        self.compile_self_determined(index, offset)?;
+                        self.push(Instruction::SelectBit {
+                            declared,
+                            signed: expression_signed(index),
+                        });
+                    }
+                    Selection::Range(msb, lsb) => {
+                        check_range_direction(*msb, *lsb, declared)?;
+                        let first = bit_offset(*msb as i64, declared);
+                        let last = bit_offset(*lsb as i64, declared);
+                        let num_bits = part_select_width(first, last)?;
+                        self.push(Instruction::SelectRange {
+                            offset: first.min(last),
+                            num_bits,
+                        });
+                    }
+                    Selection::Indexed {
+                        base,
+                        width,
+                        ascending,
+                    } => {
+                        check_indexed_width(*width)?;
+                        self.compile_self_determined(base, offset)?;
+                        self.push(Instruction::SelectIndexed {
+                            declared,
+                            width: *width,
+                            ascending: *ascending,
+                            signed: expression_signed(base),
+                        });
+                    }
+                }
+            }
+            Condition::Eq(left, right)
+            | Condition::Neq(left, right)
+            | Condition::CaseEq(left, right)
+            | Condition::CaseNeq(left, right)
+            | Condition::WildcardEq(left, right)
+            | Condition::WildcardNeq(left, right)
+            | Condition::Lt(left, right)
+            | Condition::Le(left, right)
+            | Condition::Gt(left, right)
+            | Condition::Ge(left, right) => {
+                if comparison_type(left, right, hierarchy, signal_cache)? != ValueType::Bits {
+                    self.push(interpret);
+                    return Ok(());
+                }
+                // Both operands are sized to the wider of their self-determined widths, and
+                // compared as signed only if both are signed
+                let operand_width = expression_width(left, hierarchy, signal_cache)?
+                    .max(expression_width(right, hierarchy, signal_cache)?);
+                let operand_signed = expression_signed(left) && expression_signed(right);
+                self.compile(left, operand_width, operand_signed, offset)?;
+                self.compile(right, operand_width, operand_signed, offset)?;
+                let op = condition.compare_op().ok_or("Not a comparison operator")?;
+                self.push(Instruction::Compare {
+                    op,
+                    signed: operand_signed,
+                });
+            }
+            Condition::Literal(Literal::Fill(bit)) => {
+                // Fill literals repeat their bit over the whole context
+                let bits = bit.to_string().repeat(width.max(1) as usize);
+                self.push(Instruction::Constant(Slot::from_value(
+                    Value::from_bit_string(&bits),
+                )));
+            }
+            Condition::Literal(literal) => {
+                let value = literal_to_value(literal)?;
+                self.push(Instruction::Constant(Slot::from_value(value)));
+            }
+            Condition::Time => self.push(Instruction::Time { offset }),
+            Condition::Past(expr, depth) => {
+                if *depth == 0 {
+                    return Err("$past depth must be at least 1".to_string());
+                }
+                let past_offset = offset + *depth as usize;
+                self.compile(expr, width, signed, past_offset)?;
+                self.push(Instruction::Past {
+                    offset: past_offset,
+                });
+            }
+            Condition::Stable(expr) | Condition::Changed(expr)
+                if expression_type(expr, hierarchy, signal_cache)? != ValueType::Bits =>
+            {
+                self.push(interpret);
+            }
+            Condition::Rose(expr)
+            | Condition::Fell(expr)
+            | Condition::Stable(expr)
+            | Condition::Changed(expr) => {
+                let (expr_width, expr_signed) = self_determined_sizing(expr, self.ctx)?;
+                self.compile(expr, expr_width, expr_signed, offset)?;
+                self.compile(expr, expr_width, expr_signed, offset + 1)?;
+                self.push(Instruction::Past { offset: offset + 1 });
+                let function = condition
+                    .sampled_function()
+                    .ok_or("Not a sampled value function")?;
+                self.push(Instruction::Sampled(function));
+            }
+            Condition::ReduceAnd(expr)
+            | Condition::ReduceOr(expr)
+            | Condition::ReduceXor(expr)
+            | Condition::CountOnes(expr)
+            | Condition::OneHot(expr)
+            | Condition::OneHot0(expr)
+            | Condition::IsUnknown(expr) => {
+                self.compile_self_determined(expr, offset)?;
+                self.push(match condition {
+                    Condition::ReduceAnd(_) => Instruction::ReduceAnd,
+                    Condition::ReduceOr(_) => Instruction::ReduceOr,
+                    Condition::ReduceXor(_) => Instruction::ReduceXor,
+                    Condition::CountOnes(_) => Instruction::CountOnes,
+                    Condition::OneHot(_) => Instruction::OneHot,
+                    Condition::OneHot0(_) => Instruction::OneHot0,
+                    _ => Instruction::IsUnknown,
+                });
+            }
+            Condition::Abs(_) => return Err(
+                "$abs returns a real value, which can only be compared or used in real arithmetic"
+                    .to_string(),
+            ),
+            Condition::Approx(_, _, _) | Condition::Contains(_, _) => self.push(interpret),
+        }
+        Ok(())
+    }
+}
+
+/// Run instructions at a sample and return the value they leave on the stack.
+fn run(instructions: &[Instruction], ctx: &EvalContext, sample_idx: usize) -> Result<Slot, String> {
+    let mut stack: Vec<Slot> = Vec::new();
+    // Truth values of the conditions of the `?:` being evaluated
+    let mut branches: Vec<Option<bool>> = Vec::new();
+    let pop = |stack: &mut Vec<Slot>| stack.pop().ok_or("Program stack is empty".to_string());
+    // Values that are not used because they are before the first sample, see `Past`
+    let unused = || Slot::Small(SmallValue::from_bool(false));
+
+    let mut pc = 0;
+    while let Some(instruction) = instructions.get(pc) {
+        let value = match instruction {
+            Instruction::Constant(value) => value.clone(),
+            Instruction::Signal {
+                path,
+                signal,
+                width,
+                offset,
+            } => match sample_idx.checked_sub(*offset) {
+                Some(sample) => read_signal(path, signal, *width, ctx, sample)?,
+                None => unused(),
+            },
+            Instruction::Time { offset } => match sample_idx.checked_sub(*offset) {
+                // Clocked samples are reported at the clock edge, so that is their time
+                Some(sample) => Slot::Small(SmallValue::known(
+                    ctx.waveform.time_table()[ctx.report_idx(sample)],
+                    64,
+                )),
+                None => unused(),
+            },
+            Instruction::Interpret {
+                condition,
+                width,
+                signed,
+                offset,
+            } => match sample_idx.checked_sub(*offset) {
+                Some(sample) => {
+                    Slot::from_value(evaluate_operator(condition, ctx, sample, *width, *signed)?)
+                }
+                None => unused(),
+            },
+            Instruction::Past { offset } => {
+                let value = pop(&mut stack)?;
+                if sample_idx < *offset {
+                    // There is no past value before the first sample
+                    unused()
+                } else {
+                    value
+                }
+            }
+            Instruction::SignExtend(width) => pop(&mut stack)?.map(
+                *width,
+                |value| value.sign_extend(*width),
+                |value| value.sign_extend(*width),
+            ),
+            Instruction::LogicalAnd | Instruction::LogicalOr => {
+                let right = pop(&mut stack)?.truth();
+                let left = pop(&mut stack)?.truth();
+                // A known false operand of && (or true operand of ||) decides the result
+                // even if the other is unknown
+                let decisive = matches!(instruction, Instruction::LogicalOr);
+                Slot::Small(SmallValue::from_truth(match (left, right) {
+                    (Some(value), _) | (_, Some(value)) if value == decisive => Some(decisive),
+                    (Some(_), Some(_)) => Some(!decisive),
+                    _ => None,
+                }))
+            }
+            Instruction::LogicalNot => {
+                let truth = pop(&mut stack)?.truth();
+                Slot::Small(SmallValue::from_truth(truth.map(|truth| !truth)))
+            }
+            Instruction::BitwiseAnd | Instruction::BitwiseOr | Instruction::BitwiseXor => {
+                let right = pop(&mut stack)?;
+                let left = pop(&mut stack)?;
+                let width = left.width().max(right.width());
+                match instruction {
+                    Instruction::BitwiseAnd => {
+                        left.combine(right, width, SmallValue::bitand, Value::bitand)
+                    }
+                    Instruction::BitwiseOr => {
+                        left.combine(right, width, SmallValue::bitor, Value::bitor)
+                    }
+                    _ => left.combine(right, width, SmallValue::bitxor, Value::bitxor),
+                }
+            }
+            Instruction::BitwiseNot(width) => {
+                let value = pop(&mut stack)?;
+                let result_width = value.width().max(*width);
+                value.map(
+                    result_width,
+                    |value| value.bitnot(*width),
+                    |value| value.bitnot(*width),
+                )
+            }
+            Instruction::Negate(width) => {
+                let value = pop(&mut stack)?;
+                let result_width = value.width().max(*width);
+                value.map(
+                    result_width,
+                    |value| value.negate(*width),
+                    |value| value.negate(*width),
+                )
+            }
+            Instruction::Arithmetic { op, width, signed } => {
+                let right = pop(&mut stack)?;
+                let left = pop(&mut stack)?;
+                let result_width = left.width().max(right.width()).max(*width);
+                left.combine(
+                    right,
+                    result_width,
+                    |left, right| left.arithmetic(*op, right, *width, *signed),
+                    |left, right| left.arithmetic(*op, right, *width, *signed),
+                )
+            }
+            Instruction::Shift { op, width, signed } => {
+                let amount = match pop(&mut stack)? {
+                    Slot::Small(amount) => amount.shift_amount(),
+                    Slot::Big(amount) => amount.shift_amount(),
+                };
+                let value = pop(&mut stack)?;
+                let result_width = value.width().max(*width);
+                value.map(
+                    result_width,
+                    |value| value.shift(*op, amount, *width, *signed),
+                    |value| value.shift(*op, amount, *width, *signed),
+                )
+            }
+            Instruction::Compare { op, signed } => {
+                let right = pop(&mut stack)?;
+                let left = pop(&mut stack)?;
+                let result = match (left, right) {
+                    (Slot::Small(left), Slot::Small(right)) => left.compare(*op, &right, *signed),
+                    (left, right) => left.into_value().compare(*op, &right.into_value(), *signed),
+                };
+                Slot::Small(SmallValue::from_truth(result))
+            }
+            Instruction::Concat(count) => {
+                let items = stack.split_off(
+                    stack
+                        .len()
+                        .checked_sub(*count)
+                        .ok_or("Program stack is empty")?,
+                );
+                let mut value = Slot::Small(SmallValue::known(0, 0));
+                for item in items {
+                    let width = value.width() + item.width();
+                    value = value.combine(item, width, SmallValue::concat, Value::concat);
+                }
+                value
+            }
+            Instruction::Replicate(count) => {
+                let item = pop(&mut stack)?;
+                let width = replication_width(*count, item.width())?;
+                item.map(
+                    width,
+                    |value| value.replicate(*count),
+                    |value| value.replicate(*count),
+                )
+            }
+            Instruction::SelectRange { offset, num_bits } => pop(&mut stack)?.map(
+                *num_bits,
+                |value| value.select(*offset, *num_bits),
+                |value| value.select(*offset, *num_bits),
+            ),
+            Instruction::SelectBit { declared, signed } => {
+                let index = pop(&mut stack)?.index(*signed);
+                let value = pop(&mut stack)?;
+                match index {
+                    Some(index) => select(value, (index, index), *declared)?,
+                    // An unknown index selects X
+                    None => Slot::Small(SmallValue::all_x(1)),
+                }
+            }
+            Instruction::SelectIndexed {
+                declared,
+                width,
+                ascending,
+                signed,
+            } => {
+                let base = pop(&mut stack)?.index(*signed);
+                let value = pop(&mut stack)?;
+                let span = *width as i64 - 1;
+                match base {
+                    Some(base) if *ascending => {
+                        select(value, (base, base.saturating_add(span)), *declared)?
+                    }
+                    Some(base) => select(value, (base.saturating_sub(span), base), *declared)?,
+                    None => Slot::from_value(Value::all_x(*width)),
+                }
+            }
+            Instruction::Sampled(function) => {
+                let previous = pop(&mut stack)?;
+                let current = pop(&mut stack)?;
+                let result = match (current, previous) {
+                    (Slot::Small(current), Slot::Small(previous)) => {
+                        current.sampled_function(*function, &previous)
+                    }
+                    (current, previous) => current
+                        .into_value()
+                        .sampled_function(*function, &previous.into_value()),
+                };
+                Slot::Small(SmallValue::from_bool(result))
+            }
+            Instruction::ReduceAnd | Instruction::ReduceOr | Instruction::ReduceXor => {
+                let value = pop(&mut stack)?;
+                match (instruction, value) {
+                    (Instruction::ReduceAnd, Slot::Small(value)) => Slot::Small(value.reduce_and()),
+                    (Instruction::ReduceOr, Slot::Small(value)) => Slot::Small(value.reduce_or()),
+                    (_, Slot::Small(value)) => Slot::Small(value.reduce_xor()),
+                    (Instruction::ReduceAnd, Slot::Big(value)) => {
+                        Slot::from_value(value.reduce_and())
+                    }
+                    (Instruction::ReduceOr, Slot::Big(value)) => {
+                        Slot::from_value(value.reduce_or())
+                    }
+                    (_, Slot::Big(value)) => Slot::from_value(value.reduce_xor()),
+                }
+            }
+            Instruction::CountOnes => {
+                // Like SystemVerilog, $countones returns a 32-bit int
+                let count = pop(&mut stack)?.count_ones();
+                Slot::Small(SmallValue::known(count, 32))
+            }
+            Instruction::OneHot | Instruction::OneHot0 | Instruction::IsUnknown => {
+                let value = pop(&mut stack)?;
+                // X and Z bits are not counted as ones
+                let result = match instruction {
+                    Instruction::OneHot => value.count_ones() == 1,
+                    Instruction::OneHot0 => value.count_ones() <= 1,
+                    _ => value.has_unknown(),
+                };
+                Slot::Small(SmallValue::from_bool(result))
+            }
+            Instruction::Branch { skip } => {
+                let truth = pop(&mut stack)?.truth();
+                branches.push(truth);
+                if truth == Some(false) {
+                    pc += skip;
+                }
+                pc += 1;
+                continue;
+            }
+            Instruction::SkipElse { skip } => {
+                if branches.last() == Some(&Some(true)) {
+                    pc += skip;
+                }
+                pc += 1;
+                continue;
+            }
+            Instruction::Merge { width, signed } => {
+                let value = match branches.pop().ok_or("Program has no branch to merge")? {
+                    Some(_) => pop(&mut stack)?,
+                    None => {
+                        let if_false = pop(&mut stack)?;
+                        let if_true = pop(&mut stack)?;
+                        let merged_width = if_true.width().max(if_false.width());
+                        if_true.combine(if_false, merged_width, SmallValue::merge, Value::merge)
+                    }
+                };
+                // Both values have the width of the wider one; signed values are extended
+                // by the `SignExtend` that follows
+                if *signed {
+                    value
+                } else {
+                    let resized = value.width().max(*width);
+                    value.map(
+                        resized,
+                        |value| value.resize(resized),
+                        |value| value.resize(resized),
+                    )
+                }
+            }
+        };
+        stack.push(value);
+        pc += 1;
+    }
+    pop(&mut stack)
+}
+
+/// Select the bits from index `first` to index `last` of a value declared `declared`.
+fn select(value: Slot, (first, last): (i64, i64), declared: (i64, i64)) -> Result<Slot, String> {
+    let (first, last) = (bit_offset(first, declared), bit_offset(last, declared));
+    let num_bits = part_select_width(first, last)?;
+    Ok(value.map(
+        num_bits,
+        |value| value.select(first.min(last), num_bits),
+        |value| value.select(first.min(last), num_bits),
+    ))
+}
+
+/// Read the value of a signal at a sample, like `read_signal_value`.
+fn read_signal(
+    path: &str,
+    signal: &wellen::Signal,
+    width: u32,
+    ctx: &EvalContext,
+    sample_idx: usize,
+) -> Result<Slot, String> {
+    let time_idx = ctx.time_idx(sample_idx);
+    let time_table_idx: wellen::TimeTableIdx = time_idx
+        .try_into()
+        .map_err(|_| format!("Time index {} too large", time_idx))?;
+    let offset = signal
+        .get_offset(time_table_idx)
+        .ok_or_else(|| format!("No data for signal {} at time index {}", path, time_idx))?;
+
+    Ok(match signal.get_value_at(&offset, 0) {
+        // Two-state values are packed eight bits per byte, most significant byte first
+        wellen::SignalValue::Binary(data, _) if width <= 64 && data.len() <= 8 => {
+            let value = data
+                .iter()
+                .fold(0u64, |value, &byte| value << 8 | u64::from(byte));
+            Slot::Small(SmallValue::known(value, width))
+        }
+        // Four-state values are packed four bits per byte as 0, 1, X or Z
+        wellen::SignalValue::FourValue(data, bits) if bits <= 64 => {
+            let (mut value, mut unknown) = (0u64, 0u64);
+            for byte in data {
+                for shift in [6, 4, 2, 0] {
+                    let state = (byte >> shift) & 3;
+                    value = value << 1 | u64::from(state == 1 || state == 2);
+                    unknown = unknown << 1 | u64::from(state >= 2);
+                }
+            }
+            Slot::Small(SmallValue::new(value, unknown, bits))
+        }
+        signal_value => Slot::from_value(signal_value_to_value(signal_value, width)?),
+    })
+}
@@ -0,0 +1,394 @@
+//! Sequence and property matching for assertion-style searches.
+
+use super::{
+    condition::load_condition_signals, condition::resolve_clock, condition::resolve_signal_scopes,
+    condition::Condition, condition::ConditionError, condition::ConditionOptions,
+    condition::EvalContext, condition::SamplingClock, formatting::format_time, program::Program,
+};
+use std::collections::BTreeSet;
+
+/// One boolean expression of a sequence, with the cycle delay before it.
+#[derive(Debug, Clone)]
+pub(super) struct SequenceElement {
+    /// Minimum and maximum number of cycles after the previous element
+    pub(super) delay: (u32, u32),
+    pub(super) condition: Condition,
+}
+
+/// Boolean expressions separated by cycle delays, e.g. `a ##1 b ##[1:3] c`.
+///
+/// The delay of the first element is a leading delay such as `##1 b`, measured from
+/// the cycle the sequence starts in.
+#[derive(Debug, Clone)]
+pub(super) struct Sequence {
+    pub(super) elements: Vec<SequenceElement>,
+}
+
+/// A sequence to match, or an implication between two sequences.
+#[derive(Debug, Clone)]
+pub(super) enum Property {
+    Sequence(Sequence),
+    Implication {
+        antecedent: Sequence,
+        consequent: Sequence,
+        overlapping: bool, // `|->` when true, `|=>` when false
+    },
+}
+
+/// A parsed property with its optional `@(edge signal)` sampling clock.
+#[derive(Debug, Clone)]
+pub(super) struct SampledProperty {
+    pub(super) clock: Option<SamplingClock>,
+    pub(super) property: Property,
+}
+
+impl Property {
+    /// All sequences of the property, in order.
+    pub(super) fn sequences(&self) -> Vec<&Sequence> {
+        match self {
+            Property::Sequence(sequence) => vec![sequence],
+            Property::Implication {
+                antecedent,
+                consequent,
+                ..
+            } => vec![antecedent, consequent],
+        }
+    }
+
+    /// All boolean expressions of the property, in order.
+    pub(super) fn conditions(&self) -> Vec<&Condition> {
+        self.sequences()
+            .into_iter()
+            .flat_map(|sequence| sequence.elements.iter())
+            .map(|element| &element.condition)
+            .collect()
+    }
+
+    /// All boolean expressions of the property, in order, for modification.
+    pub(super) fn conditions_mut(&mut self) -> Vec<&mut Condition> {
+        let sequences = match self {
+            Property::Sequence(sequence) => vec![sequence],
+            Property::Implication {
+                antecedent,
+                consequent,
+                ..
+            } => vec![antecedent, consequent],
+        };
+        sequences
+            .into_iter()
+            .flat_map(|sequence| sequence.elements.iter_mut())
+            .map(|element| &mut element.condition)
+            .collect()
+    }
+}
+
+/// A sequence with the expression of every element compiled to a `Program`.
+pub(super) struct CompiledSequence<'a> {
+    /// The cycle delay before each element, and its program
+    elements: Vec<((u32, u32), Program<'a>)>,
+}
+
+impl<'a> CompiledSequence<'a> {
+    fn compile(sequence: &'a Sequence, ctx: &EvalContext<'a>) -> Result<Self, String> {
+        let elements = sequence
+            .elements
+            .iter()
+            .map(|element| Ok((element.delay, Program::compile(&element.condition, ctx)?)))
+            .collect::<Result<_, String>>()?;
+        Ok(CompiledSequence { elements })
+    }
+}
+
+/// A property with the expressions of its sequences compiled, once per search.
+pub(super) enum CompiledProperty<'a> {
+    Sequence(CompiledSequence<'a>),
+    Implication {
+        antecedent: CompiledSequence<'a>,
+        consequent: CompiledSequence<'a>,
+        overlapping: bool,
+    },
+}
+
+impl<'a> CompiledProperty<'a> {
+    /// Compile the expressions of a property whose signals are loaded in `ctx`.
+    pub(super) fn compile(property: &'a Property, ctx: &EvalContext<'a>) -> Result<Self, String> {
+        Ok(match property {
+            Property::Sequence(sequence) => {
+                CompiledProperty::Sequence(CompiledSequence::compile(sequence, ctx)?)
+            }
+            Property::Implication {
+                antecedent,
+                consequent,
+                overlapping,
+            } => CompiledProperty::Implication {
+                antecedent: CompiledSequence::compile(antecedent, ctx)?,
+                consequent: CompiledSequence::compile(consequent, ctx)?,
+                overlapping: *overlapping,
+            },
+        })
+    }
+}
+
+/// Parse a property string such as `TOP.req |-> ##[1:4] TOP.ack`.
+///
+/// Supports everything accepted in conditions, plus:
+/// - `##N` to wait N cycles between two expressions
+/// - `##[m:n]` to wait between m and n cycles
+/// - A leading `##N` or `##[m:n]` to delay the start of a sequence
+/// - `|->` for overlapping implication, where the consequent starts in the cycle the
+///   antecedent ends
+/// - `|=>` for non-overlapping implication, where the consequent starts one cycle later
+///
+/// A cycle is one clock edge when a sampling clock is given, and one time index otherwise.
+pub(super) fn parse_property(property: &str) -> Result<SampledProperty, ConditionError> {
+    let parser = crate::condition::condition::SampledPropertyParser::new();
+    parser
+        .parse(property)
+        .map_err(|e| ConditionError::from_parse_error(property, e))
+}
+
+/// Check the bounds of a `##[m:n]` cycle delay.
+pub(super) fn cycle_delay_range(min: u32, max: u32) -> Result<(u32, u32), String> {
+    if min > max {
+        return Err(format!(
+            "Invalid cycle delay range ##[{}:{}]: minimum is greater than maximum",
+            min, max
+        ));
+    }
+    Ok((min, max))
+}
+
+/// Outcome of matching a sequence from one start sample.
+struct SequenceMatch {
+    /// Samples at which the sequence ends, in ascending order
+    ends: Vec<usize>,
+    /// The latest sample an expression was evaluated at
+    last_checked: usize,
+    /// Some threads of the sequence would continue past the last sample
+    incomplete: bool,
+}
+
+/// Match a sequence starting at `start_sample`, following all delay ranges at once.
+fn match_sequence(
+    sequence: &CompiledSequence,
+    ctx: &EvalContext,
+    start_sample: usize,
+) -> Result<SequenceMatch, String> {
+    let num_samples = ctx.num_samples();
+    let mut frontier = vec![start_sample];
+    let mut last_checked = start_sample;
+    let mut incomplete = false;
+
+    for ((min, max), program) in &sequence.elements {
+        // Threads that reach the same sample share one evaluation
+        let mut candidates = BTreeSet::new();
+        for &position in &frontier {
+            for delay in *min..=*max {
+                let sample = position + delay as usize;
+                if sample >= num_samples {
+                    incomplete = true;
+                    break;
+                }
+                candidates.insert(sample);
+            }
+        }
+
+        frontier.clear();
+        for sample in candidates {
+            last_checked = last_checked.max(sample);
+            // Expressions that are X or Z count as false
+            if program.holds(ctx, sample)? {
+                frontier.push(sample);
+            }
+        }
+        if frontier.is_empty() {
+            break;
+        }
+    }
+
+    Ok(SequenceMatch {
+        ends: frontier,
+        last_checked,
+        incomplete,
+    })
+}
+
+/// One evaluation attempt of a property, as sample indices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Attempt {
+    pub(super) start: usize,
+    pub(super) end: usize,
+    pub(super) passed: bool,
+}
+
+/// Check a property starting at one sample.
+///
+/// A sequence passes at its earliest match and fails if it cannot match. An
+/// implication passes once the consequent matches after every match of the
+/// antecedent, and fails at the first antecedent match the consequent cannot follow.
+///
+/// Returns `None` when the implication is vacuous because the antecedent does not
+/// match, or when the outcome depends on samples past the end of the waveform.
+pub(super) fn check_attempt(
+    property: &CompiledProperty,
+    ctx: &EvalContext,
+    start_sample: usize,
+) -> Result<Option<Attempt>, String> {
+    match property {
+        CompiledProperty::Sequence(sequence) => {
+            let matched = match_sequence(sequence, ctx, start_sample)?;
+            Ok(match matched.ends.first() {
+                Some(&end) => Some(Attempt {
+                    start: start_sample,
+                    end,
+                    passed: true,
+                }),
+                None if matched.incomplete => None,
+                None => Some(Attempt {
+                    start: start_sample,
+                    end: matched.last_checked,
+                    passed: false,
+                }),
+            })
+        }
+        CompiledProperty::Implication {
+            antecedent,
+            consequent,
+            overlapping,
+        } => {
+            let triggers = match_sequence(antecedent, ctx, start_sample)?;
+            let mut end: Option<usize> = None;
+            let mut incomplete = false;
+            for &trigger_end in &triggers.ends {
+                let consequent_start = if *overlapping {
+                    trigger_end
+                } else {
+                    trigger_end + 1
+                };
+                if consequent_start >= ctx.num_samples() {
+                    incomplete = true;
+                    continue;
+                }
+
+                let matched = match_sequence(consequent, ctx, consequent_start)?;
+                match matched.ends.first() {
+                    Some(&consequent_end) => {
+                        end = Some(end.map_or(consequent_end, |end| end.max(consequent_end)))
+                    }
+                    None if matched.incomplete => incomplete = true,
+                    None => {
+                        return Ok(Some(Attempt {
+                            start: start_sample,
+                            end: matched.last_checked,
+                            passed: false,
+                        }))
+                    }
+                }
+            }
+            Ok(end.filter(|_| !incomplete).map(|end| Attempt {
+                start: start_sample,
+                end,
+                passed: true,
+            }))
+        }
+    }
+}
+
+/// Find where a sequence or property matches.
+///
+/// Every sample in the time range starts a new attempt. For a sequence, each start
+/// where it matches is reported with the time index of its earliest match. For an
+/// implication, each start where the antecedent matches is reported as passing or
+/// failing, ending where the consequent matched or where it was found to fail.
+/// Attempts whose outcome depends on samples past the end of the waveform are not
+/// reported.
+///
+/// When a sampling clock is given, either in `options` or as an `@(posedge sig)`
+/// prefix of the property, cycle delays count clock edges and values are sampled
+/// just before each edge; otherwise every time index is a cycle.
+///
+/// # Arguments
+/// * `waveform` - The waveform to read from
+/// * `property` - The sequence or property (e.g., "TOP.req |-> ##[1:4] TOP.ack")
+/// * `start_idx` - Starting time index of attempts (inclusive)
+/// * `end_idx` - Ending time index of attempts (inclusive)
+/// * `limit` - Maximum number of results to return. Use -1 for unlimited.
+/// * `failures_only` - Report only failing attempts; for a sequence, the starts where it does not match
+/// * `options` - Additional search options
+///
+/// # Returns
+/// A vector of formatted match strings, or an error if the operation fails.
+pub fn find_sequence_matches(
+    waveform: &mut wellen::simple::Waveform,
+    property: &str,
+    start_idx: usize,
+    end_idx: usize,
+    limit: isize,
+    failures_only: bool,
+    options: &ConditionOptions,
+) -> Result<Vec<String>, String> {
+    // Get timescale before any mutable operations
+    let timescale = waveform.hierarchy().timescale();
+
+    let parsed = parse_property(property).map_err(|e| e.to_string())?;
+    let mut clock = resolve_clock(parsed.clock, options)?;
+    let mut property = parsed.property;
+    resolve_signal_scopes(
+        waveform.hierarchy(),
+        property.conditions_mut(),
+        clock.as_mut(),
+        options,
+    )?;
+
+    let loaded = load_condition_signals(
+        waveform,
+        &property.conditions(),
+        clock.as_ref(),
+        options.paths.as_deref(),
+    )?;
+    let waveform = &*waveform;
+    let ctx = loaded.context(waveform);
+    let time_table = waveform.time_table();
+    let compiled = CompiledProperty::compile(&property, &ctx)?;
+
+    let mut results = Vec::new();
+    for sample_idx in ctx.first_sample_from(start_idx)..ctx.num_samples() {
+        if ctx.report_idx(sample_idx) > end_idx {
+            break;
+        }
+        if limit >= 0 && results.len() >= limit as usize {
+            break;
+        }
+
+        let Some(attempt) = check_attempt(&compiled, &ctx, sample_idx)? else {
+            continue;
+        };
+        // A sequence that does not match is only interesting when looking for failures
+        let reported = match &property {
+            _ if failures_only => !attempt.passed,
+            Property::Sequence(_) => attempt.passed,
+            Property::Implication { .. } => true,
+        };
+        if !reported {
+            continue;
+        }
+        let label = match (&property, attempt.passed) {
+            (Property::Sequence(_), true) => "Match",
+            (Property::Implication { .. }, true) => "Pass",
+            (_, false) => "Fail",
+        };
+
+        let start = ctx.report_idx(attempt.start);
+        let end = ctx.report_idx(attempt.end);
+        results.push(format!(
+            "{} from time index {} ({}) to time index {} ({})",
+            label,
+            start,
+            format_time(time_table[start], timescale.as_ref()),
+            end,
+            format_time(time_table[end], timescale.as_ref())
+        ));
+    }
+
+    Ok(results)
+}
@@ -1,10 +1,11 @@
 //! Signal reading and querying utilities.
 
+use rayon::prelude::*;
 use wellen;
 
 use super::{
     formatting::format_signal_value, formatting::format_time,
-    hierarchy::collect_signals_from_scope, hierarchy::find_scope_by_path,
+    hierarchy::collect_signals_from_scope, hierarchy::PathIndex, hierarchy::PathLookup,
 };
 
 /// List signals in a waveform hierarchy with optional filtering.
@@ -24,12 +25,51 @@ pub fn list_signals(
     hierarchy_prefix: Option<&str>,
     recursive: bool,
     limit: Option<isize>,
+) -> Vec<String> {
+    let paths = PathLookup::Search(hierarchy);
+    list_signals_in(
+        hierarchy,
+        paths,
+        name_pattern,
+        hierarchy_prefix,
+        recursive,
+        limit,
+    )
+}
+
+/// Like `list_signals`, finding `hierarchy_prefix` in an index of the paths in the hierarchy.
+pub fn list_signals_with_index(
+    hierarchy: &wellen::Hierarchy,
+    paths: &PathIndex,
+    name_pattern: Option<&str>,
+    hierarchy_prefix: Option<&str>,
+    recursive: bool,
+    limit: Option<isize>,
+) -> Vec<String> {
+    let paths = PathLookup::Indexed(paths);
+    list_signals_in(
+        hierarchy,
+        paths,
+        name_pattern,
+        hierarchy_prefix,
+        recursive,
+        limit,
+    )
+}
+
+fn list_signals_in(
+    hierarchy: &wellen::Hierarchy,
+    paths: PathLookup,
+    name_pattern: Option<&str>,
+    hierarchy_prefix: Option<&str>,
+    recursive: bool,
+    limit: Option<isize>,
 ) -> Vec<String> {
     let mut signals = Vec::new();
 
     if let Some(prefix) = hierarchy_prefix {
         // Find scope by path
-        if let Some(scope_ref) = find_scope_by_path(hierarchy, prefix) {
+        if let Some(scope_ref) = paths.find_scope(prefix) {
             // Collect signals from this scope (and children if recursive)
             signals = collect_signals_from_scope(hierarchy, scope_ref, recursive, name_pattern);
         }
@@ -123,20 +163,28 @@ pub fn read_signal_values(
 pub fn get_signal_metadata(
     hierarchy: &wellen::Hierarchy,
     signal_path: &str,
+) -> Result<String, String> {
+    signal_metadata(hierarchy, PathLookup::Search(hierarchy), signal_path)
+}
+
+/// Like `get_signal_metadata`, finding the signal in an index of the paths in the hierarchy.
+pub fn get_signal_metadata_with_index(
+    hierarchy: &wellen::Hierarchy,
+    paths: &PathIndex,
+    signal_path: &str,
+) -> Result<String, String> {
+    signal_metadata(hierarchy, PathLookup::Indexed(paths), signal_path)
+}
+
+fn signal_metadata(
+    hierarchy: &wellen::Hierarchy,
+    paths: PathLookup,
+    signal_path: &str,
 ) -> Result<String, String> {
     // Find VarRef from path
-    let parts: Vec<&str> = signal_path.split('.').collect();
-    let var_ref = if parts.len() > 1 {
-        let path = &parts[..parts.len() - 1];
-        let name = parts[parts.len() - 1];
-        hierarchy
-            .lookup_var(path, name)
-            .ok_or_else(|| format!("Signal not found: {}", signal_path))?
-    } else {
-        hierarchy
-            .lookup_var(&[], signal_path)
-            .ok_or_else(|| format!("Signal not found: {}", signal_path))?
-    };
+    let var_ref = paths
+        .find_var(signal_path)
+        .ok_or_else(|| format!("Signal not found: {}", signal_path))?;
 
     let var = &hierarchy[var_ref];
 
@@ -150,7 +198,7 @@ pub fn get_signal_metadata(
         None => "N/A".to_string(),
     };
 
-    let info = format!(
+    let mut info = format!(
         "Signal: {}\nType: {:?}\nWidth: {}\nIndex: {}",
         signal_path,
         var.var_type(),
@@ -158,11 +206,22 @@ pub fn get_signal_metadata(
         index_info
     );
 
+    // Conditions may use the names of enum values
+    if let Some((enum_name, values)) = var.enum_type(hierarchy) {
+        let values: Vec<String> = values
+            .iter()
+            .map(|(bits, name)| format!("{} = {}", name, bits))
+            .collect();
+        info.push_str(&format!("\nEnum: {} ({})", enum_name, values.join(", ")));
+    }
+
     Ok(info)
 }
 
 /// Find events (changes) of a signal within a time range.
 ///
+/// Events are formatted in parallel for long time ranges and returned in time order.
+///
 /// # Arguments
 /// * `waveform` - The waveform to read from (must have signal loaded)
 /// * `signal_ref` - The signal reference to analyze
@@ -186,30 +245,37 @@ pub fn find_signal_events(
         .get_signal(signal_ref)
         .ok_or("Signal not found after loading")?;
 
-    let mut events = Vec::new();
-
-    for (time_idx, signal_value) in signal.iter_changes() {
-        let time_idx = time_idx as usize;
-
-        // Check if within time range
-        if time_idx < start_idx || time_idx > end_idx {
-            continue;
-        }
-
-        // Check limit (unless unlimited with -1)
-        if limit >= 0 && events.len() >= limit as usize {
-            break;
-        }
-
-        let time_value = time_table[time_idx];
-        let formatted_time = format_time(time_value, timescale.as_ref());
-        let value_str = format_signal_value(signal_value);
-
-        events.push(format!(
-            "Time index {} ({}): {}",
-            time_idx, formatted_time, value_str
-        ));
+    // Changes within the time range, up to the limit
+    let time_indices = signal.time_indices();
+    let first = time_indices.partition_point(|&time_idx| (time_idx as usize) < start_idx);
+    let mut end = time_indices.partition_point(|&time_idx| (time_idx as usize) <= end_idx);
+    if limit >= 0 {
+        end = end.min(first.saturating_add(limit as usize));
     }
 
-    Ok(events)
+    // Formatting the values is most of the work, so changes are formatted in parallel
+    (first..end.max(first))
+        .into_par_iter()
+        .with_min_len(SIGNAL_EVENTS_PER_TASK)
+        .map(|change| {
+            let time_idx = time_indices[change];
+            let offset = signal
+                .get_offset(time_idx)
+                .ok_or_else(|| format!("No data for signal at time index {}", time_idx))?;
+            // Changes in delta cycles of the same time index follow each other
+            let element = u16::try_from(change - offset.start)
+                .map_err(|_| format!("Too many changes at time index {}", time_idx))?;
+            let value_str = format_signal_value(signal.get_value_at(&offset, element));
+
+            let time_value = time_table[time_idx as usize];
+            let formatted_time = format_time(time_value, timescale.as_ref());
+            Ok(format!(
+                "Time index {} ({}): {}",
+                time_idx, formatted_time, value_str
+            ))
+        })
+        .collect()
 }
+
+/// Minimum number of signal changes formatted by one task of `find_signal_events`.
+const SIGNAL_EVENTS_PER_TASK: usize = 1024;
@@ -0,0 +1,142 @@
+//! Open waveforms, whose signal values are read in the background.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use tokio::sync::{watch, RwLock};
+
+use super::{derived::DerivedSignals, hierarchy::PathIndex};
+
+/// An open waveform together with the derived signals defined for it.
+#[derive(Debug)]
+pub struct WaveformEntry {
+    pub body: WaveformBody,
+    pub derived: DerivedSignals,
+    /// Index of the paths in the waveform, built when it is opened
+    pub paths: Arc<PathIndex>,
+}
+
+/// The signal values of an open waveform, which are read in the background.
+pub enum WaveformBody {
+    /// Only the header has been read; `loaded` is closed once the body is read
+    Loading {
+        hierarchy: wellen::Hierarchy,
+        loaded: watch::Receiver<()>,
+    },
+    Ready(wellen::simple::Waveform),
+    Failed(String),
+}
+
+impl std::fmt::Debug for WaveformBody {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WaveformBody::Loading { .. } => write!(f, "Loading"),
+            WaveformBody::Ready(waveform) => waveform.fmt(f),
+            WaveformBody::Failed(error) => write!(f, "Failed({:?})", error),
+        }
+    }
+}
+
+impl WaveformBody {
+    /// The hierarchy, which can be used while the signal values are loading.
+    pub fn hierarchy(&self) -> Result<&wellen::Hierarchy, String> {
+        match self {
+            WaveformBody::Loading { hierarchy, .. } => Ok(hierarchy),
+            WaveformBody::Ready(waveform) => Ok(waveform.hierarchy()),
+            WaveformBody::Failed(error) => Err(format!("Failed to read waveform: {}", error)),
+        }
+    }
+
+    /// The waveform with its signal values, see `WaveformStore::get_loaded`.
+    pub fn waveform(&mut self) -> Result<&mut wellen::simple::Waveform, String> {
+        match self {
+            WaveformBody::Loading { .. } => Err("Waveform is still loading".to_string()),
+            WaveformBody::Ready(waveform) => Ok(waveform),
+            WaveformBody::Failed(error) => Err(format!("Failed to read waveform: {}", error)),
+        }
+    }
+}
+
+/// Open waveforms by alias.
+///
+/// Every waveform has its own lock, so a long search in one waveform does not hold
+/// up other waveforms, or a waveform whose body is being stored.
+#[derive(Debug, Clone, Default)]
+pub struct WaveformStore {
+    waveforms: Arc<RwLock<HashMap<String, Arc<RwLock<WaveformEntry>>>>>,
+}
+
+impl WaveformStore {
+    /// Open a waveform file under an alias, replacing any waveform with that alias.
+    ///
+    /// Only the header is read before returning, so the hierarchy can be used right
+    /// away. The signal values are read by a background task.
+    ///
+    /// # Arguments
+    /// * `path` - Path to the waveform file
+    /// * `alias` - Name to find the waveform by
+    ///
+    /// # Returns
+    /// Nothing, or an error if the header cannot be read.
+    pub async fn open(&self, path: &Path, alias: &str) -> Result<(), String> {
+        let header_path = path.to_path_buf();
+        let hierarchy = tokio::task::spawn_blocking(move || {
+            wellen::viewers::read_header_from_file(&header_path, &wellen::LoadOptions::default())
+                .map(|header| header.hierarchy)
+        })
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())?;
+
+        let paths = Arc::new(PathIndex::new(&hierarchy));
+        let (loader, loaded) = watch::channel(());
+        let entry = Arc::new(RwLock::new(WaveformEntry {
+            body: WaveformBody::Loading { hierarchy, loaded },
+            derived: DerivedSignals::with_paths(paths.clone()),
+            paths,
+        }));
+        self.waveforms
+            .write()
+            .await
+            .insert(alias.to_string(), entry.clone());
+
+        // A `simple::Waveform` can only be built by reading the whole file, so the
+        // header is read again with the body; it is small next to the body.
+        let path = path.to_path_buf();
+        tokio::spawn(async move {
+            let body = match tokio::task::spawn_blocking(move || wellen::simple::read(&path)).await
+            {
+                Ok(Ok(waveform)) => WaveformBody::Ready(waveform),
+                Ok(Err(e)) => WaveformBody::Failed(e.to_string()),
+                Err(e) => WaveformBody::Failed(e.to_string()),
+            };
+            // The entry may have been replaced meanwhile; then nobody finds it anymore
+            entry.write().await.body = body;
+            drop(loader);
+        });
+        Ok(())
+    }
+
+    /// The waveform opened under an alias, whose body may still be loading.
+    pub async fn get(&self, alias: &str) -> Option<Arc<RwLock<WaveformEntry>>> {
+        self.waveforms.read().await.get(alias).cloned()
+    }
+
+    /// The waveform opened under an alias, once its body has been read or has failed to.
+    pub async fn get_loaded(&self, alias: &str) -> Option<Arc<RwLock<WaveformEntry>>> {
+        loop {
+            let entry = self.get(alias).await?;
+            let loading = match &entry.read().await.body {
+                WaveformBody::Loading { loaded, .. } => Some(loaded.clone()),
+                _ => None,
+            };
+            let Some(mut loaded) = loading else {
+                return Some(entry);
+            };
+            // The loader never sends, it drops the sender after storing the body. When
+            // the alias was opened again meanwhile, the new waveform is waited for.
+            let _ = loaded.changed().await;
+        }
+    }
+}
@@ -0,0 +1,999 @@
+//! Four-state values used by condition evaluation.
+
+use num_bigint::{BigInt, BigUint};
+use num_traits::{One, ToPrimitive, Zero};
+
+/// Arithmetic operators: `+`, `-`, `*`, `/` and `%`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum ArithOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+}
+
+/// Shift operators: `<<` (and `<<<`), `>>` and `>>>`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum ShiftOp {
+    Left,
+    Right,
+    ArithRight,
+}
+
+/// Equality and relational operators.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum CompareOp {
+    Eq,
+    Neq,
+    CaseEq,
+    CaseNeq,
+    WildcardEq,
+    WildcardNeq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// Sampled value functions that compare a value with its value at the previous sample.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum SampledFunction {
+    Rose,
+    Fell,
+    Stable,
+    Changed,
+}
+
+/// A four-state bit vector where every bit is 0, 1, X or Z.
+///
+/// Bits are stored in two planes using the Verilog VPI `aval`/`bval` encoding:
+/// a bit set in `unknown` is X when the matching `value` bit is 1 and Z when it
+/// is 0. Bits at or above `width` are always zero in both planes.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Value {
+    pub(super) value: BigUint,
+    pub(super) unknown: BigUint,
+    pub(super) width: u32,
+}
+
+impl Value {
+    /// Create a fully known value, truncated to `width` bits.
+    pub(super) fn known(value: BigUint, width: u32) -> Self {
+        Value {
+            value: value & width_mask(width),
+            unknown: BigUint::zero(),
+            width,
+        }
+    }
+
+    /// Create a known 1-bit value.
+    pub(super) fn from_bool(value: bool) -> Self {
+        Value::known(BigUint::from(value as u32), 1)
+    }
+
+    /// Create a 1-bit value that is X when the truth value is unknown.
+    pub(super) fn from_truth(truth: Option<bool>) -> Self {
+        match truth {
+            Some(value) => Value::from_bool(value),
+            None => Value::all_x(1),
+        }
+    }
+
+    /// Create a value where every bit is X.
+    pub(super) fn all_x(width: u32) -> Self {
+        Value {
+            value: width_mask(width),
+            unknown: width_mask(width),
+            width,
+        }
+    }
+
+    /// Parse a bit string with the most significant bit first.
+    ///
+    /// Accepts the nine-state characters used by wellen: `h` and `l` are treated
+    /// as weak 1 and 0, `z` as Z, and `x`, `u`, `w` and `-` as X.
+    pub(super) fn from_bit_string(bits: &str) -> Self {
+        let mut value = BigUint::zero();
+        let mut unknown = BigUint::zero();
+        for (i, c) in bits.chars().rev().enumerate() {
+            let i = i as u64;
+            match c {
+                '0' | 'l' | 'L' => {}
+                '1' | 'h' | 'H' => value.set_bit(i, true),
+                'z' | 'Z' => unknown.set_bit(i, true),
+                _ => {
+                    value.set_bit(i, true);
+                    unknown.set_bit(i, true);
+                }
+            }
+        }
+        Value {
+            value,
+            unknown,
+            width: bits.chars().count() as u32,
+        }
+    }
+
+    /// Create a known value from a signed integer, wrapped to `width` bits in two's complement.
+    pub(super) fn from_signed(value: BigInt, width: u32) -> Self {
+        let modulus = BigInt::one() << width;
+        let wrapped = ((value % &modulus) + &modulus) % modulus;
+        Value::known(wrapped.into_parts().1, width)
+    }
+
+    /// The known bits interpreted as a two's complement signed integer.
+    pub(super) fn to_signed(&self) -> BigInt {
+        let value = BigInt::from(self.value.clone());
+        if self.width > 0 && self.value.bit(u64::from(self.width - 1)) {
+            value - (BigInt::one() << self.width)
+        } else {
+            value
+        }
+    }
+
+    /// Returns true if any bit is X or Z.
+    pub(super) fn has_unknown(&self) -> bool {
+        !self.unknown.is_zero()
+    }
+
+    /// The value interpreted as a condition.
+    ///
+    /// Returns `Some(true)` if any bit is a known 1, `Some(false)` if all bits are
+    /// known 0, and `None` if the result depends on X or Z bits.
+    pub(super) fn truth(&self) -> Option<bool> {
+        if !self.ones().is_zero() {
+            Some(true)
+        } else if self.has_unknown() {
+            None
+        } else {
+            Some(false)
+        }
+    }
+
+    /// Number of bits that are known to be 1; X and Z bits are not counted.
+    pub(super) fn count_ones(&self) -> u64 {
+        self.ones().count_ones()
+    }
+
+    /// Bits that are known to be 1.
+    fn ones(&self) -> BigUint {
+        &self.value ^ (&self.value & &self.unknown)
+    }
+
+    /// Bits that are known to be 0 within `width` bits.
+    fn zeros(&self, width: u32) -> BigUint {
+        width_mask(width) ^ ((&self.value | &self.unknown) & width_mask(width))
+    }
+
+    /// Build a value from the sets of known-one and known-zero bits; all other bits become X.
+    fn from_ones_and_zeros(ones: BigUint, zeros: BigUint, width: u32) -> Self {
+        let unknown = width_mask(width) ^ (&ones | &zeros);
+        Value {
+            value: ones | &unknown,
+            unknown,
+            width,
+        }
+    }
+
+    /// Zero-extend or truncate the value to `width` bits.
+    pub(super) fn resize(&self, width: u32) -> Self {
+        let mask = width_mask(width);
+        Value {
+            value: &self.value & &mask,
+            unknown: &self.unknown & mask,
+            width,
+        }
+    }
+
+    /// Sign-extend the value to `width` bits by copying its most significant bit, which
+    /// may be X or Z. Values that are already at least `width` bits wide are unchanged.
+    pub(super) fn sign_extend(self, width: u32) -> Self {
+        if width <= self.width || self.width == 0 {
+            return self;
+        }
+        let extension = width_mask(width) ^ width_mask(self.width);
+        let top = u64::from(self.width - 1);
+        let Value {
+            mut value,
+            mut unknown,
+            ..
+        } = self;
+        if value.bit(top) {
+            value |= &extension;
+        }
+        if unknown.bit(top) {
+            unknown |= extension;
+        }
+        Value {
+            value,
+            unknown,
+            width,
+        }
+    }
+
+    /// Bitwise AND: a known 0 on either side wins over X or Z.
+    pub(super) fn bitand(&self, other: &Value) -> Self {
+        let width = self.width.max(other.width);
+        Value::from_ones_and_zeros(
+            self.ones() & other.ones(),
+            self.zeros(width) | other.zeros(width),
+            width,
+        )
+    }
+
+    /// Bitwise OR: a known 1 on either side wins over X or Z.
+    pub(super) fn bitor(&self, other: &Value) -> Self {
+        let width = self.width.max(other.width);
+        Value::from_ones_and_zeros(
+            self.ones() | other.ones(),
+            self.zeros(width) & other.zeros(width),
+            width,
+        )
+    }
+
+    /// Bitwise XOR: any X or Z input bit produces an X output bit.
+    pub(super) fn bitxor(&self, other: &Value) -> Self {
+        let width = self.width.max(other.width);
+        let unknown = &self.unknown | &other.unknown;
+        let known = width_mask(width) ^ &unknown;
+        Value {
+            value: ((&self.value ^ &other.value) & known) | &unknown,
+            unknown,
+            width,
+        }
+    }
+
+    /// Bitwise NOT of the value extended to `width` bits; X and Z bits become X.
+    pub(super) fn bitnot(&self, width: u32) -> Self {
+        let width = self.width.max(width);
+        Value::from_ones_and_zeros(self.zeros(width), self.ones(), width)
+    }
+
+    /// Extract `num_bits` bits starting at bit `lsb`.
+    pub(super) fn slice(&self, lsb: u32, num_bits: u32) -> Self {
+        let mask = width_mask(num_bits);
+        Value {
+            value: (&self.value >> lsb) & &mask,
+            unknown: (&self.unknown >> lsb) & mask,
+            width: num_bits,
+        }
+    }
+
+    /// Combine the two values of a `?:` whose condition is X or Z, as in Verilog.
+    ///
+    /// Bits that have the same known value in both are kept and all other bits are X.
+    pub(super) fn merge(&self, other: &Value) -> Self {
+        let width = self.width.max(other.width);
+        let (a, b) = (self.resize(width), other.resize(width));
+        let differ = (&a.value ^ &b.value) | &a.unknown | &b.unknown;
+        Value {
+            value: a.value | &differ,
+            unknown: differ,
+            width,
+        }
+    }
+
+    /// Select `num_bits` bits starting at bit `offset`, like a Verilog part-select.
+    ///
+    /// The offset may be negative, and bits outside the value read as X.
+    pub(super) fn select(&self, offset: i64, num_bits: u32) -> Self {
+        let width = self.width as i64;
+        let start = offset.clamp(0, width);
+        let end = offset.saturating_add(num_bits as i64).clamp(start, width);
+        let below = start.saturating_sub(offset).clamp(0, num_bits as i64) as u32;
+        let inside = (end - start) as u32;
+        Value::all_x(num_bits - below - inside)
+            .concat(&self.slice(start as u32, inside))
+            .concat(&Value::all_x(below))
+    }
+
+    /// Concatenate `low` below this value.
+    pub(super) fn concat(&self, low: &Value) -> Self {
+        Value {
+            value: (&self.value << low.width) | &low.value,
+            unknown: (&self.unknown << low.width) | &low.unknown,
+            width: self.width + low.width,
+        }
+    }
+
+    /// Concatenate `count` copies of this value, like a Verilog replication.
+    pub(super) fn replicate(&self, count: u32) -> Self {
+        // Doubling the copies keeps the work linear in the width of the result
+        let mut result = Value::known(BigUint::zero(), 0);
+        let mut copies = self.clone();
+        let mut count = count;
+        while count > 0 {
+            if count & 1 == 1 {
+                result = result.concat(&copies);
+            }
+            count >>= 1;
+            if count > 0 {
+                copies = copies.concat(&copies);
+            }
+        }
+        result
+    }
+
+    /// Shift left by `amount` bits, keeping the value `width` bits wide.
+    pub(super) fn shift_left(&self, amount: u32, width: u32) -> Self {
+        let width = self.width.max(width);
+        let mask = width_mask(width);
+        Value {
+            value: (&self.value << amount) & &mask,
+            unknown: (&self.unknown << amount) & mask,
+            width,
+        }
+    }
+
+    /// Logical shift right by `amount` bits, keeping the value `width` bits wide.
+    pub(super) fn shift_right(&self, amount: u32, width: u32) -> Self {
+        Value {
+            value: &self.value >> amount,
+            unknown: &self.unknown >> amount,
+            width: self.width.max(width),
+        }
+    }
+
+    /// Arithmetic shift right by `amount` bits, filling with the most significant bit of
+    /// the value sign-extended to `width` bits.
+    pub(super) fn shift_right_arith(&self, amount: u32, width: u32) -> Self {
+        let extended = self.clone().sign_extend(width);
+        if extended.width == 0 {
+            return extended;
+        }
+        let width = extended.width;
+        let amount = amount.min(width);
+        let fill = width_mask(width) ^ width_mask(width - amount);
+        let top = u64::from(width - 1);
+        let mut value = &extended.value >> amount;
+        let mut unknown = &extended.unknown >> amount;
+        if extended.value.bit(top) {
+            value |= &fill;
+        }
+        if extended.unknown.bit(top) {
+            unknown |= fill;
+        }
+        Value {
+            value,
+            unknown,
+            width,
+        }
+    }
+
+    /// Reduction AND (`&`): 0 if any bit is a known 0, otherwise X if any bit is X or Z.
+    pub(super) fn reduce_and(&self) -> Self {
+        if !self.zeros(self.width).is_zero() {
+            Value::from_bool(false)
+        } else if self.has_unknown() {
+            Value::all_x(1)
+        } else {
+            Value::from_bool(true)
+        }
+    }
+
+    /// Reduction OR (`|`): 1 if any bit is a known 1, otherwise X if any bit is X or Z.
+    pub(super) fn reduce_or(&self) -> Self {
+        Value::from_truth(self.truth())
+    }
+
+    /// Reduction XOR (`^`): the parity of the bits, or X if any bit is X or Z.
+    pub(super) fn reduce_xor(&self) -> Self {
+        if self.has_unknown() {
+            Value::all_x(1)
+        } else {
+            Value::from_bool(self.count_ones() % 2 == 1)
+        }
+    }
+
+    /// Logical equality (`==`): X if the result depends on X or Z bits.
+    pub(super) fn logical_eq(&self, other: &Value) -> Option<bool> {
+        let width = self.width.max(other.width);
+        let unknown = &self.unknown | &other.unknown;
+        let known = width_mask(width) ^ &unknown;
+        if !((&self.value ^ &other.value) & known).is_zero() {
+            Some(false)
+        } else if !unknown.is_zero() {
+            None
+        } else {
+            Some(true)
+        }
+    }
+
+    /// Case equality (`===`): X and Z bits must match exactly.
+    pub(super) fn case_eq(&self, other: &Value) -> bool {
+        self.value == other.value && self.unknown == other.unknown
+    }
+
+    /// Apply an arithmetic operator to operands extended to the context width.
+    ///
+    /// The result is as wide as the widest operand or the context and wraps around
+    /// modulo 2^width. Any X or Z operand bit, or division by zero, makes the whole
+    /// result X. Signed division truncates toward zero and the remainder takes the
+    /// sign of the dividend, as in Verilog.
+    pub(super) fn arithmetic(
+        &self,
+        op: ArithOp,
+        other: &Value,
+        context_width: u32,
+        signed: bool,
+    ) -> Self {
+        let width = self.width.max(other.width).max(context_width);
+        if self.has_unknown() || other.has_unknown() {
+            return Value::all_x(width);
+        }
+        if signed {
+            let (left, right) = (
+                self.clone().sign_extend(width).to_signed(),
+                other.clone().sign_extend(width).to_signed(),
+            );
+            let result = match op {
+                ArithOp::Add => left + right,
+                ArithOp::Sub => left - right,
+                ArithOp::Mul => left * right,
+                ArithOp::Div | ArithOp::Mod if right.is_zero() => return Value::all_x(width),
+                ArithOp::Div => left / right,
+                ArithOp::Mod => left % right,
+            };
+            return Value::from_signed(result, width);
+        }
+        let (left, right) = (&self.value, &other.value);
+        let result = match op {
+            ArithOp::Add => left + right,
+            // Add 2^width before subtracting so the result wraps instead of underflowing
+            ArithOp::Sub => (left + width_mask(width) + 1u32) - right,
+            ArithOp::Mul => left * right,
+            ArithOp::Div | ArithOp::Mod if right.is_zero() => return Value::all_x(width),
+            ArithOp::Div => left / right,
+            ArithOp::Mod => left % right,
+        };
+        Value::known(result, width)
+    }
+
+    /// Two's complement negation of the value extended to the context width.
+    pub(super) fn negate(&self, context_width: u32) -> Self {
+        let width = self.width.max(context_width);
+        if self.has_unknown() {
+            return Value::all_x(width);
+        }
+        Value::known(
+            (width_mask(width) + 1u32 - &self.value) & width_mask(width),
+            width,
+        )
+    }
+
+    /// Apply a shift operator by `amount` bits, or by an unknown amount if `None`.
+    ///
+    /// The shifted value is extended to the context width; an unknown amount makes
+    /// the whole result X.
+    pub(super) fn shift(
+        &self,
+        op: ShiftOp,
+        amount: Option<u32>,
+        context_width: u32,
+        signed: bool,
+    ) -> Self {
+        let width = self.width.max(context_width);
+        let Some(amount) = amount else {
+            return Value::all_x(width);
+        };
+        match op {
+            // Arithmetic right shift only differs from logical shift for signed operands
+            ShiftOp::ArithRight if signed => self.shift_right_arith(amount, width),
+            // Shifting by the full width or more clears every bit
+            _ if amount >= width => Value::known(BigUint::zero(), width),
+            ShiftOp::Left => self.shift_left(amount, width),
+            _ => self.shift_right(amount, width),
+        }
+    }
+
+    /// The amount of a shift by this value: `None` if it has X or Z bits, and
+    /// saturated to `u32::MAX` if it is larger.
+    pub(super) fn shift_amount(&self) -> Option<u32> {
+        (!self.has_unknown()).then(|| self.value.to_u32().unwrap_or(u32::MAX))
+    }
+
+    /// Compare two operands sized to the same context.
+    ///
+    /// Returns `None` if the result is X: relational comparisons are X if any operand
+    /// bit is X or Z, and are signed only if `signed`.
+    pub(super) fn compare(&self, op: CompareOp, other: &Value, signed: bool) -> Option<bool> {
+        match op {
+            CompareOp::Eq => self.logical_eq(other),
+            CompareOp::Neq => self.logical_eq(other).map(|eq| !eq),
+            CompareOp::CaseEq => Some(self.case_eq(other)),
+            CompareOp::CaseNeq => Some(!self.case_eq(other)),
+            CompareOp::WildcardEq => self.wildcard_eq(other),
+            CompareOp::WildcardNeq => self.wildcard_eq(other).map(|eq| !eq),
+            _ if self.has_unknown() || other.has_unknown() => None,
+            _ if signed => Some(compare_ordering(
+                op,
+                self.to_signed().cmp(&other.to_signed()),
+            )),
+            _ => Some(compare_ordering(op, self.value.cmp(&other.value))),
+        }
+    }
+
+    /// Evaluate a sampled value function given the value at the previous sample.
+    ///
+    /// Edges look at the least significant bit only, and a change from X or Z counts;
+    /// stability compares every bit, including X and Z, like `===`.
+    pub(super) fn sampled_function(&self, function: SampledFunction, previous: &Value) -> bool {
+        let (one, zero) = (Value::from_bool(true), Value::from_bool(false));
+        match function {
+            SampledFunction::Rose => {
+                self.slice(0, 1).case_eq(&one) && !previous.slice(0, 1).case_eq(&one)
+            }
+            SampledFunction::Fell => {
+                self.slice(0, 1).case_eq(&zero) && !previous.slice(0, 1).case_eq(&zero)
+            }
+            SampledFunction::Stable => self.case_eq(previous),
+            SampledFunction::Changed => !self.case_eq(previous),
+        }
+    }
+
+    /// Wildcard equality (`==?`): X and Z bits in `pattern` match any bit.
+    pub(super) fn wildcard_eq(&self, pattern: &Value) -> Option<bool> {
+        let width = self.width.max(pattern.width);
+        let care = width_mask(width) ^ &pattern.unknown;
+        let known = &care ^ (&care & &self.unknown);
+        if !((&self.value ^ &pattern.value) & known).is_zero() {
+            Some(false)
+        } else if !(&self.unknown & care).is_zero() {
+            None
+        } else {
+            Some(true)
+        }
+    }
+}
+
+/// Create a mask with the lowest `width` bits set.
+pub(super) fn width_mask(width: u32) -> BigUint {
+    (BigUint::from(1u32) << width) - BigUint::from(1u32)
+}
+
+/// The result of a relational operator given the ordering of its operands.
+fn compare_ordering(op: CompareOp, ordering: std::cmp::Ordering) -> bool {
+    match op {
+        CompareOp::Lt => ordering.is_lt(),
+        CompareOp::Le => ordering.is_le(),
+        CompareOp::Gt => ordering.is_gt(),
+        _ => ordering.is_ge(),
+    }
+}
+
+/// A four-state value of at most 64 bits, with the same encoding and semantics as `Value`.
+///
+/// Compiled programs use it to evaluate narrow values without big integer arithmetic;
+/// every method matches the `Value` method of the same name.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct SmallValue {
+    pub(super) value: u64,
+    pub(super) unknown: u64,
+    pub(super) width: u32,
+}
+
+impl SmallValue {
+    pub(super) fn known(value: u64, width: u32) -> Self {
+        SmallValue {
+            value: value & small_mask(width),
+            unknown: 0,
+            width,
+        }
+    }
+
+    /// A value with the given value and unknown bits, as in `Value`.
+    pub(super) fn new(value: u64, unknown: u64, width: u32) -> Self {
+        SmallValue {
+            value: value & small_mask(width),
+            unknown: unknown & small_mask(width),
+            width,
+        }
+    }
+
+    pub(super) fn from_bool(value: bool) -> Self {
+        SmallValue::known(value as u64, 1)
+    }
+
+    pub(super) fn from_truth(truth: Option<bool>) -> Self {
+        match truth {
+            Some(value) => SmallValue::from_bool(value),
+            None => SmallValue::all_x(1),
+        }
+    }
+
+    pub(super) fn all_x(width: u32) -> Self {
+        SmallValue {
+            value: small_mask(width),
+            unknown: small_mask(width),
+            width,
+        }
+    }
+
+    /// Convert a value of at most 64 bits.
+    pub(super) fn from_value(value: &Value) -> Option<Self> {
+        if value.width > 64 {
+            return None;
+        }
+        Some(SmallValue {
+            value: value.value.to_u64()?,
+            unknown: value.unknown.to_u64()?,
+            width: value.width,
+        })
+    }
+
+    pub(super) fn to_value(self) -> Value {
+        Value {
+            value: BigUint::from(self.value),
+            unknown: BigUint::from(self.unknown),
+            width: self.width,
+        }
+    }
+
+    /// The two's complement value of a signed integer, wrapped to `width` bits.
+    fn from_signed(value: i128, width: u32) -> Self {
+        SmallValue::known(value as u64, width)
+    }
+
+    pub(super) fn to_signed(self) -> i128 {
+        if self.width > 0 && self.value >> (self.width - 1) & 1 == 1 {
+            self.value as i128 - (1i128 << self.width)
+        } else {
+            self.value as i128
+        }
+    }
+
+    pub(super) fn has_unknown(&self) -> bool {
+        self.unknown != 0
+    }
+
+    pub(super) fn truth(&self) -> Option<bool> {
+        if self.ones() != 0 {
+            Some(true)
+        } else if self.has_unknown() {
+            None
+        } else {
+            Some(false)
+        }
+    }
+
+    pub(super) fn count_ones(&self) -> u64 {
+        u64::from(self.ones().count_ones())
+    }
+
+    fn ones(&self) -> u64 {
+        self.value & !self.unknown
+    }
+
+    fn zeros(&self, width: u32) -> u64 {
+        small_mask(width) ^ ((self.value | self.unknown) & small_mask(width))
+    }
+
+    fn from_ones_and_zeros(ones: u64, zeros: u64, width: u32) -> Self {
+        let unknown = small_mask(width) ^ (ones | zeros);
+        SmallValue {
+            value: ones | unknown,
+            unknown,
+            width,
+        }
+    }
+
+    pub(super) fn resize(&self, width: u32) -> Self {
+        SmallValue {
+            value: self.value & small_mask(width),
+            unknown: self.unknown & small_mask(width),
+            width,
+        }
+    }
+
+    pub(super) fn sign_extend(self, width: u32) -> Self {
+        if width <= self.width || self.width == 0 {
+            return self;
+        }
+        let extension = small_mask(width) ^ small_mask(self.width);
+        let top = self.width - 1;
+        let extend = |bits: u64| {
+            if bits >> top & 1 == 1 {
+                bits | extension
+            } else {
+                bits
+            }
+        };
+        SmallValue {
+            value: extend(self.value),
+            unknown: extend(self.unknown),
+            width,
+        }
+    }
+
+    pub(super) fn bitand(&self, other: &SmallValue) -> Self {
+        let width = self.width.max(other.width);
+        SmallValue::from_ones_and_zeros(
+            self.ones() & other.ones(),
+            self.zeros(width) | other.zeros(width),
+            width,
+        )
+    }
+
+    pub(super) fn bitor(&self, other: &SmallValue) -> Self {
+        let width = self.width.max(other.width);
+        SmallValue::from_ones_and_zeros(
+            self.ones() | other.ones(),
+            self.zeros(width) & other.zeros(width),
+            width,
+        )
+    }
+
+    pub(super) fn bitxor(&self, other: &SmallValue) -> Self {
+        let width = self.width.max(other.width);
+        let unknown = self.unknown | other.unknown;
+        let known = small_mask(width) ^ unknown;
+        SmallValue {
+            value: ((self.value ^ other.value) & known) | unknown,
+            unknown,
+            width,
+        }
+    }
+
+    pub(super) fn bitnot(&self, width: u32) -> Self {
+        let width = self.width.max(width);
+        SmallValue::from_ones_and_zeros(self.zeros(width), self.ones(), width)
+    }
+
+    pub(super) fn slice(&self, lsb: u32, num_bits: u32) -> Self {
+        SmallValue {
+            value: shift_right(self.value, lsb) & small_mask(num_bits),
+            unknown: shift_right(self.unknown, lsb) & small_mask(num_bits),
+            width: num_bits,
+        }
+    }
+
+    pub(super) fn merge(&self, other: &SmallValue) -> Self {
+        let width = self.width.max(other.width);
+        let (a, b) = (self.resize(width), other.resize(width));
+        let differ = (a.value ^ b.value) | a.unknown | b.unknown;
+        SmallValue {
+            value: a.value | differ,
+            unknown: differ,
+            width,
+        }
+    }
+
+    /// Like `Value::select`; `num_bits` must be at most 64.
+    pub(super) fn select(&self, offset: i64, num_bits: u32) -> Self {
+        let width = self.width as i64;
+        let start = offset.clamp(0, width);
+        let end = offset.saturating_add(num_bits as i64).clamp(start, width);
+        let below = start.saturating_sub(offset).clamp(0, num_bits as i64) as u32;
+        let inside = self.slice(start as u32, (end - start) as u32);
+        let outside = small_mask(num_bits) ^ shift_left(small_mask(inside.width), below);
+        SmallValue {
+            value: shift_left(inside.value, below) | outside,
+            unknown: shift_left(inside.unknown, below) | outside,
+            width: num_bits,
+        }
+    }
+
+    /// Like `Value::concat`; the result must be at most 64 bits wide.
+    pub(super) fn concat(&self, low: &SmallValue) -> Self {
+        SmallValue {
+            value: shift_left(self.value, low.width) | low.value,
+            unknown: shift_left(self.unknown, low.width) | low.unknown,
+            width: self.width + low.width,
+        }
+    }
+
+    /// Like `Value::replicate`; the result must be at most 64 bits wide.
+    pub(super) fn replicate(&self, count: u32) -> Self {
+        (0..count).fold(SmallValue::known(0, 0), |result, _| result.concat(self))
+    }
+
+    pub(super) fn reduce_and(&self) -> Self {
+        if self.zeros(self.width) != 0 {
+            SmallValue::from_bool(false)
+        } else if self.has_unknown() {
+            SmallValue::all_x(1)
+        } else {
+            SmallValue::from_bool(true)
+        }
+    }
+
+    pub(super) fn reduce_or(&self) -> Self {
+        SmallValue::from_truth(self.truth())
+    }
+
+    pub(super) fn reduce_xor(&self) -> Self {
+        if self.has_unknown() {
+            SmallValue::all_x(1)
+        } else {
+            SmallValue::from_bool(self.count_ones() % 2 == 1)
+        }
+    }
+
+    fn logical_eq(&self, other: &SmallValue) -> Option<bool> {
+        let width = self.width.max(other.width);
+        let unknown = self.unknown | other.unknown;
+        let known = small_mask(width) ^ unknown;
+        if (self.value ^ other.value) & known != 0 {
+            Some(false)
+        } else if unknown != 0 {
+            None
+        } else {
+            Some(true)
+        }
+    }
+
+    fn case_eq(&self, other: &SmallValue) -> bool {
+        self.value == other.value && self.unknown == other.unknown
+    }
+
+    fn wildcard_eq(&self, pattern: &SmallValue) -> Option<bool> {
+        let width = self.width.max(pattern.width);
+        let care = small_mask(width) ^ pattern.unknown;
+        let known = care ^ (care & self.unknown);
+        if (self.value ^ pattern.value) & known != 0 {
+            Some(false)
+        } else if self.unknown & care != 0 {
+            None
+        } else {
+            Some(true)
+        }
+    }
+
+    /// Like `Value::arithmetic`; the result must be at most 64 bits wide.
+    pub(super) fn arithmetic(
+        &self,
+        op: ArithOp,
+        other: &SmallValue,
+        context_width: u32,
+        signed: bool,
+    ) -> Self {
+        let width = self.width.max(other.width).max(context_width);
+        if self.has_unknown() || other.has_unknown() {
+            return SmallValue::all_x(width);
+        }
+        if signed {
+            // Products of two 64-bit values fit in 128 bits
+            let (left, right) = (
+                self.sign_extend(width).to_signed(),
+                other.sign_extend(width).to_signed(),
+            );
+            let result = match op {
+                ArithOp::Add => left + right,
+                ArithOp::Sub => left - right,
+                ArithOp::Mul => left * right,
+                ArithOp::Div | ArithOp::Mod if right == 0 => return SmallValue::all_x(width),
+                ArithOp::Div => left / right,
+                ArithOp::Mod => left % right,
+            };
+            return SmallValue::from_signed(result, width);
+        }
+        let (left, right) = (self.value, other.value);
+        let result = match op {
+            ArithOp::Add => left.wrapping_add(right),
+            ArithOp::Sub => left.wrapping_sub(right),
+            ArithOp::Mul => left.wrapping_mul(right),
+            ArithOp::Div | ArithOp::Mod if right == 0 => return SmallValue::all_x(width),
+            ArithOp::Div => left / right,
+            ArithOp::Mod => left % right,
+        };
+        SmallValue::known(result, width)
+    }
+
+    /// Like `Value::negate`; the result must be at most 64 bits wide.
+    pub(super) fn negate(&self, context_width: u32) -> Self {
+        let width = self.width.max(context_width);
+        if self.has_unknown() {
+            return SmallValue::all_x(width);
+        }
+        SmallValue::known(self.value.wrapping_neg(), width)
+    }
+
+    /// Like `Value::shift`; the result must be at most 64 bits wide.
+    pub(super) fn shift(
+        &self,
+        op: ShiftOp,
+        amount: Option<u32>,
+        context_width: u32,
+        signed: bool,
+    ) -> Self {
+        let width = self.width.max(context_width);
+        let Some(amount) = amount else {
+            return SmallValue::all_x(width);
+        };
+        match op {
+            ShiftOp::ArithRight if signed => {
+                let extended = self.sign_extend(width);
+                if extended.width == 0 {
+                    return extended;
+                }
+                let width = extended.width;
+                let amount = amount.min(width);
+                let fill = small_mask(width) ^ small_mask(width - amount);
+                let top = width - 1;
+                let shift = |bits: u64| {
+                    let shifted = shift_right(bits, amount);
+                    if bits >> top & 1 == 1 {
+                        shifted | fill
+                    } else {
+                        shifted
+                    }
+                };
+                SmallValue {
+                    value: shift(extended.value),
+                    unknown: shift(extended.unknown),
+                    width,
+                }
+            }
+            _ if amount >= width => SmallValue::known(0, width),
+            ShiftOp::Left => SmallValue {
+                value: shift_left(self.value, amount) & small_mask(width),
+                unknown: shift_left(self.unknown, amount) & small_mask(width),
+                width,
+            },
+            _ => SmallValue {
+                value: shift_right(self.value, amount),
+                unknown: shift_right(self.unknown, amount),
+                width,
+            },
+        }
+    }
+
+    pub(super) fn shift_amount(&self) -> Option<u32> {
+        (!self.has_unknown()).then(|| u32::try_from(self.value).unwrap_or(u32::MAX))
+    }
+
+    pub(super) fn compare(&self, op: CompareOp, other: &SmallValue, signed: bool) -> Option<bool> {
+        match op {
+            CompareOp::Eq => self.logical_eq(other),
+            CompareOp::Neq => self.logical_eq(other).map(|eq| !eq),
+            CompareOp::CaseEq => Some(self.case_eq(other)),
+            CompareOp::CaseNeq => Some(!self.case_eq(other)),
+            CompareOp::WildcardEq => self.wildcard_eq(other),
+            CompareOp::WildcardNeq => self.wildcard_eq(other).map(|eq| !eq),
+            _ if self.has_unknown() || other.has_unknown() => None,
+            _ if signed => Some(compare_ordering(
+                op,
+                self.to_signed().cmp(&other.to_signed()),
+            )),
+            _ => Some(compare_ordering(op, self.value.cmp(&other.value))),
+        }
+    }
+
+    pub(super) fn sampled_function(
+        &self,
+        function: SampledFunction,
+        previous: &SmallValue,
+    ) -> bool {
+        let (one, zero) = (SmallValue::from_bool(true), SmallValue::from_bool(false));
+        match function {
+            SampledFunction::Rose => {
+                self.slice(0, 1).case_eq(&one) && !previous.slice(0, 1).case_eq(&one)
+            }
+            SampledFunction::Fell => {
+                self.slice(0, 1).case_eq(&zero) && !previous.slice(0, 1).case_eq(&zero)
+            }
+            SampledFunction::Stable => self.case_eq(previous),
+            SampledFunction::Changed => !self.case_eq(previous),
+        }
+    }
+}
+
+/// Create a mask with the lowest `width` bits set, for widths up to 64.
+fn small_mask(width: u32) -> u64 {
+    if width >= 64 {
+        u64::MAX
+    } else {
+        (1u64 << width) - 1
+    }
+}
+
+/// Shift left, giving 0 when shifting by 64 bits or more.
+fn shift_left(bits: u64, amount: u32) -> u64 {
+    bits.checked_shl(amount).unwrap_or(0)
+}
+
+/// Shift right, giving 0 when shifting by 64 bits or more.
+fn shift_right(bits: u64, amount: u32) -> u64 {
+    bits.checked_shr(amount).unwrap_or(0)
+}
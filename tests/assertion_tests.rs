@@ -0,0 +1,117 @@
+//! Assertion checking tests
+
+mod fixtures;
+
+use fixtures::{read_waveform, REQ_ACK_VCD};
+use waveform_mcp::check_assertions;
+use waveform_mcp::{ClockEdge, ConditionOptions, SamplingClock};
+
+#[test]
+fn test_check_assertions() {
+    let mut waveform = read_waveform(REQ_ACK_VCD);
+    let properties = vec![
+        "top.req |-> ##[1:4] top.ack".to_string(),
+        "!(top.req && top.ack)".to_string(),
+        "top.req |=> top.ack".to_string(),
+    ];
+
+    let reports = check_assertions(
+        &mut waveform,
+        &properties,
+        0,
+        11,
+        1,
+        &ConditionOptions::default(),
+    )
+    .expect("Should check assertions");
+    assert_eq!(reports.len(), 3, "Should report every property");
+
+    // The request at 4 is never acknowledged; the one at 10 is cut off by the end
+    let handshake = &reports[0];
+    assert_eq!(handshake.passed, 1);
+    assert_eq!(handshake.failed, 1);
+    assert_eq!(handshake.counterexamples.len(), 1);
+    let counterexample = &handshake.counterexamples[0];
+    assert_eq!(counterexample.start_idx, 4);
+    assert_eq!(counterexample.end_idx, 8);
+    assert_eq!(
+        counterexample.trace.len(),
+        5,
+        "Trace should cover every cycle of the window"
+    );
+    assert_eq!(
+        counterexample.trace[0],
+        "Time index 4 (40ns): top.req = 1'b1, top.ack = 1'b0"
+    );
+    assert!(handshake
+        .to_string()
+        .starts_with("FAIL 'top.req |-> ##[1:4] top.ack': 1 passed, 1 failed"));
+
+    // A plain condition is checked at every time index
+    let invariant = &reports[1];
+    assert_eq!(invariant.passed, 12);
+    assert_eq!(invariant.failed, 0);
+    assert!(invariant.counterexamples.is_empty());
+    assert!(invariant.to_string().starts_with("PASS"));
+
+    // Only the first counterexample is traced, but every failure is counted
+    let next_cycle = &reports[2];
+    assert_eq!(next_cycle.passed, 0);
+    assert_eq!(next_cycle.failed, 3);
+    assert_eq!(next_cycle.counterexamples.len(), 1);
+}
+
+#[test]
+fn test_check_assertions_with_clock() {
+    let mut waveform = read_waveform(REQ_ACK_VCD);
+
+    // Sampling on both edges of tick is the same as sampling every time index,
+    // except that time index 0 is never an edge
+    let options = ConditionOptions {
+        clock: Some(SamplingClock {
+            signal: "top.tick".to_string(),
+            edge: ClockEdge::Edge,
+        }),
+        ..Default::default()
+    };
+    let reports = check_assertions(
+        &mut waveform,
+        &["top.req |-> ##[1:4] top.ack".to_string()],
+        0,
+        11,
+        5,
+        &options,
+    )
+    .expect("Should check clock-sampled assertions");
+    assert_eq!(reports[0].failed, 1);
+
+    // Values are sampled just before each edge, so the trace is shifted by one
+    let counterexample = &reports[0].counterexamples[0];
+    assert_eq!(counterexample.start_idx, 5);
+    assert_eq!(counterexample.end_idx, 9);
+    assert_eq!(
+        counterexample.trace[0],
+        "Time index 5 (50ns): top.req = 1'b1, top.ack = 1'b0"
+    );
+}
+
+#[test]
+fn test_check_assertions_errors() {
+    let mut waveform = read_waveform(REQ_ACK_VCD);
+    let properties = vec!["top.req".to_string(), "top.missing |-> top.ack".to_string()];
+
+    let error = check_assertions(
+        &mut waveform,
+        &properties,
+        0,
+        11,
+        5,
+        &ConditionOptions::default(),
+    )
+    .expect_err("Should reject unknown signal");
+    assert!(
+        error.contains("top.missing |-> top.ack") && error.contains("Signal not found"),
+        "Error should name the property: {}",
+        error
+    );
+}
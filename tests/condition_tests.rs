@@ -1,6 +1,11 @@
 //! Condition tests
 
 use waveform_mcp::find_conditional_events;
+use waveform_mcp::find_conditional_events_with_options;
+use waveform_mcp::get_signal_metadata;
+use waveform_mcp::{evaluate_condition_values, Evaluator};
+use waveform_mcp::{read_derived_signal_values, DerivedSignals};
+use waveform_mcp::{ClockEdge, ConditionOptions, PathIndex, SamplingClock};
 
 #[test]
 fn test_find_conditional_events_lib() {
@@ -1197,3 +1202,2661 @@ b1010 !\n\
         "Should find 0 events where (~data & data) is zero"
     );
 }
+
+#[test]
+fn test_relational_operators() {
+    // Create a VCD file with a counter that increments each time step
+    let vcd_content = "\
+$date 2024-01-01 $end\n\
+$version Test VCD file $end\n\
+$timescale 1ns $end\n\
+$scope module top $end\n\
+$var wire 4 ! counter $end\n\
+$upscope $end\n\
+$enddefinitions $end\n\
+#0\n\
+b0000 !\n\
+#10\n\
+b0001 !\n\
+#20\n\
+b0010 !\n\
+#30\n\
+b0011 !\n\
+#40\n\
+b0100 !\n\
+#50\n\
+b0101 !\n\
+#60\n\
+b0110 !\n\
+";
+
+    let temp_file = tempfile::NamedTempFile::new().expect("Failed to create temp file");
+    std::fs::write(temp_file.path(), vcd_content).expect("Failed to write VCD file");
+
+    let mut waveform = wellen::simple::read(temp_file.path()).expect("Failed to read VCD file");
+
+    // counter > 4 is true at time indices 5 and 6
+    let events = find_conditional_events(&mut waveform, "top.counter > 4'd4", 0, 6, -1)
+        .expect("Should find events for greater than");
+    assert_eq!(events.len(), 2, "Should find 2 events where counter > 4");
+    assert!(
+        events[0].contains("Time index 5 (50ns)"),
+        "First event at time 5"
+    );
+
+    // counter >= 4 is true at time indices 4, 5 and 6
+    let events = find_conditional_events(&mut waveform, "top.counter >= 4'd4", 0, 6, -1)
+        .expect("Should find events for greater than or equal");
+    assert_eq!(events.len(), 3, "Should find 3 events where counter >= 4");
+    assert!(
+        events[0].contains("Time index 4 (40ns)"),
+        "First event at time 4"
+    );
+
+    // counter < 2 is true at time indices 0 and 1
+    let events = find_conditional_events(&mut waveform, "top.counter < 4'd2", 0, 6, -1)
+        .expect("Should find events for less than");
+    assert_eq!(events.len(), 2, "Should find 2 events where counter < 2");
+    assert!(
+        events[1].contains("Time index 1 (10ns)"),
+        "Second event at time 1"
+    );
+
+    // counter <= 2 is true at time indices 0, 1 and 2
+    let events = find_conditional_events(&mut waveform, "top.counter <= 4'd2", 0, 6, -1)
+        .expect("Should find events for less than or equal");
+    assert_eq!(events.len(), 3, "Should find 3 events where counter <= 2");
+
+    // Range check: 2 <= counter < 5 is true at time indices 2, 3 and 4
+    let events = find_conditional_events(
+        &mut waveform,
+        "top.counter >= 4'd2 && top.counter < 4'd5",
+        0,
+        6,
+        -1,
+    )
+    .expect("Should find events for range check");
+    assert_eq!(events.len(), 3, "Should find 3 events in range [2, 5)");
+    assert!(
+        events[0].contains("Time index 2 (20ns)"),
+        "First event at time 2"
+    );
+    assert!(
+        events[2].contains("Time index 4 (40ns)"),
+        "Last event at time 4"
+    );
+}
+
+#[test]
+fn test_relational_operator_precedence() {
+    // Create a VCD file with two 4-bit signals
+    let vcd_content = "\
+$date 2024-01-01 $end\n\
+$version Test VCD file $end\n\
+$timescale 1ns $end\n\
+$scope module top $end\n\
+$var wire 4 ! level $end\n\
+$var wire 4 0 watermark $end\n\
+$upscope $end\n\
+$enddefinitions $end\n\
+#0\n\
+b0010 !\n\
+b0100 0\n\
+#10\n\
+b0101 !\n\
+b0100 0\n\
+#20\n\
+b1000 !\n\
+b1001 0\n\
+";
+
+    let temp_file = tempfile::NamedTempFile::new().expect("Failed to create temp file");
+    std::fs::write(temp_file.path(), vcd_content).expect("Failed to write VCD file");
+
+    let mut waveform = wellen::simple::read(temp_file.path()).expect("Failed to read VCD file");
+
+    // Relational operators bind tighter than equality:
+    // `level > watermark == 1'b0` parses as `(level > watermark) == 1'b0`
+    let events =
+        find_conditional_events(&mut waveform, "top.level > top.watermark == 1'b0", 0, 2, -1)
+            .expect("Should find events for relational with equality");
+    assert_eq!(
+        events.len(),
+        2,
+        "Should find 2 events where level is not above watermark"
+    );
+    assert!(
+        events[0].contains("Time index 0 (0ns)"),
+        "First event at time 0"
+    );
+    assert!(
+        events[1].contains("Time index 2 (20ns)"),
+        "Second event at time 2"
+    );
+
+    // Comparisons are unsigned: 4'b1000 is greater than 4'b0101
+    let events = find_conditional_events(&mut waveform, "top.level > 4'b0101", 0, 2, -1)
+        .expect("Should find events for unsigned comparison");
+    assert_eq!(events.len(), 1, "Should find 1 event where level > 5");
+    assert!(events[0].contains("Time index 2 (20ns)"), "Event at time 2");
+}
+
+#[test]
+fn test_arithmetic_operators() {
+    // Create a VCD file with FIFO write and read pointers
+    // wr_ptr: 4, 9, 1, 15
+    // rd_ptr: 2, 1, 15, 15
+    let vcd_content = "\
+$date 2024-01-01 $end\n\
+$version Test VCD file $end\n\
+$timescale 1ns $end\n\
+$scope module top $end\n\
+$var wire 4 ! wr_ptr $end\n\
+$var wire 4 0 rd_ptr $end\n\
+$upscope $end\n\
+$enddefinitions $end\n\
+#0\n\
+b0100 !\n\
+b0010 0\n\
+#10\n\
+b1001 !\n\
+b0001 0\n\
+#20\n\
+b0001 !\n\
+b1111 0\n\
+#30\n\
+b1111 !\n\
+b1111 0\n\
+";
+
+    let temp_file = tempfile::NamedTempFile::new().expect("Failed to create temp file");
+    std::fs::write(temp_file.path(), vcd_content).expect("Failed to write VCD file");
+
+    let mut waveform = wellen::simple::read(temp_file.path()).expect("Failed to read VCD file");
+
+    // Subtraction wraps around modulo 2^4:
+    // time 0: 4 - 2 = 2, time 2: 1 - 15 = -14 = 2 (mod 16)
+    let events =
+        find_conditional_events(&mut waveform, "top.wr_ptr - top.rd_ptr == 4'd2", 0, 3, -1)
+            .expect("Should find events for subtraction");
+    assert_eq!(events.len(), 2, "Should find 2 events where level is 2");
+    assert!(
+        events[0].contains("Time index 0 (0ns)"),
+        "First event at time 0"
+    );
+    assert!(
+        events[1].contains("Time index 2 (20ns)"),
+        "Second event at time 2"
+    );
+
+    // Addition: 9 + 1 = 10 at time 1
+    let events =
+        find_conditional_events(&mut waveform, "top.wr_ptr + top.rd_ptr == 4'hA", 0, 3, -1)
+            .expect("Should find events for addition");
+    assert_eq!(events.len(), 1, "Should find 1 event where sum is 10");
+    assert!(events[0].contains("Time index 1 (10ns)"), "Event at time 1");
+
+    // Multiplication binds tighter than addition: 4 + 2 * 2 = 8 at time 0
+    let events = find_conditional_events(
+        &mut waveform,
+        "top.wr_ptr + top.rd_ptr * 4'd2 == 4'd8",
+        0,
+        3,
+        -1,
+    )
+    .expect("Should find events for multiplication precedence");
+    assert_eq!(events.len(), 1, "Should find 1 event where 4 + 2 * 2 == 8");
+    assert!(events[0].contains("Time index 0 (0ns)"), "Event at time 0");
+
+    // Division and modulo: 9 / 2 = 4 and 9 % 2 = 1 at time 1
+    let events = find_conditional_events(
+        &mut waveform,
+        "top.wr_ptr / 4'd2 == 4'd4 && top.wr_ptr % 4'd2 == 4'd1",
+        0,
+        3,
+        -1,
+    )
+    .expect("Should find events for division and modulo");
+    assert_eq!(events.len(), 1, "Should find 1 event for 9 / 2 and 9 % 2");
+    assert!(events[0].contains("Time index 1 (10ns)"), "Event at time 1");
+
+    // Division by zero does not match anything
+    let events = find_conditional_events(&mut waveform, "top.wr_ptr / 4'd0", 0, 3, -1)
+        .expect("Should evaluate division by zero");
+    assert!(events.is_empty(), "Division by zero should not be true");
+
+    // Arithmetic binds tighter than relational operators
+    let events = find_conditional_events(&mut waveform, "top.rd_ptr + 4'd1 > top.wr_ptr", 0, 3, -1)
+        .expect("Should find events for arithmetic with relational");
+    assert_eq!(events.len(), 0, "rd_ptr + 1 wraps to 0 when rd_ptr is 15");
+}
+
+#[test]
+fn test_arithmetic_width_semantics() {
+    // Create a VCD file with two 4-bit operands
+    let vcd_content = "\
+$date 2024-01-01 $end\n\
+$version Test VCD file $end\n\
+$timescale 1ns $end\n\
+$scope module top $end\n\
+$var wire 4 ! a $end\n\
+$var wire 4 0 b $end\n\
+$upscope $end\n\
+$enddefinitions $end\n\
+#0\n\
+b1000 !\n\
+b1000 0\n\
+#10\n\
+b0011 !\n\
+b0100 0\n\
+";
+
+    let temp_file = tempfile::NamedTempFile::new().expect("Failed to create temp file");
+    std::fs::write(temp_file.path(), vcd_content).expect("Failed to write VCD file");
+
+    let mut waveform = wellen::simple::read(temp_file.path()).expect("Failed to read VCD file");
+
+    // Self-determined width: 8 + 8 overflows 4 bits to 0, which is false
+    let events = find_conditional_events(&mut waveform, "top.a + top.b", 0, 1, -1)
+        .expect("Should evaluate self-determined addition");
+    assert_eq!(events.len(), 1, "Only 3 + 4 should be non-zero");
+    assert!(events[0].contains("Time index 1 (10ns)"), "Event at time 1");
+
+    // Context-determined width: comparing against a 5-bit value keeps the carry
+    let events = find_conditional_events(&mut waveform, "top.a + top.b == 5'd16", 0, 1, -1)
+        .expect("Should evaluate context-determined addition");
+    assert_eq!(events.len(), 1, "8 + 8 should equal 16 in a 5-bit context");
+    assert!(events[0].contains("Time index 0 (0ns)"), "Event at time 0");
+
+    // In a 4-bit context the sum wraps around
+    let events = find_conditional_events(&mut waveform, "top.a + top.b == 4'd0", 0, 1, -1)
+        .expect("Should evaluate wrapped addition");
+    assert_eq!(events.len(), 1, "8 + 8 should wrap to 0 in a 4-bit context");
+
+    // Multiplication result also takes the context width: 3 * 4 = 12 fits, 8 * 8 = 64 needs 7 bits
+    let events = find_conditional_events(&mut waveform, "top.a * top.b == 8'd64", 0, 1, -1)
+        .expect("Should evaluate context-determined multiplication");
+    assert_eq!(events.len(), 1, "8 * 8 should equal 64 in an 8-bit context");
+    assert!(events[0].contains("Time index 0 (0ns)"), "Event at time 0");
+}
+
+#[test]
+fn test_concatenation_and_replication() {
+    // Create a VCD file with a split bus and a single-bit signal
+    let vcd_content = "\
+$date 2024-01-01 $end\n\
+$version Test VCD file $end\n\
+$timescale 1ns $end\n\
+$scope module top $end\n\
+$var wire 8 ! hi $end\n\
+$var wire 8 0 lo $end\n\
+$var wire 1 1 bit $end\n\
+$upscope $end\n\
+$enddefinitions $end\n\
+#0\n\
+b10111110 !\n\
+b11101111 0\n\
+11\n\
+#10\n\
+b11011110 !\n\
+b10101101 0\n\
+01\n\
+";
+
+    let temp_file = tempfile::NamedTempFile::new().expect("Failed to create temp file");
+    std::fs::write(temp_file.path(), vcd_content).expect("Failed to write VCD file");
+
+    let mut waveform = wellen::simple::read(temp_file.path()).expect("Failed to read VCD file");
+
+    // Reassemble the bus from its halves
+    let events = find_conditional_events(&mut waveform, "{top.hi, top.lo} == 16'hBEEF", 0, 1, -1)
+        .expect("Should find events for concatenation");
+    assert_eq!(events.len(), 1, "Should find 1 event where bus is BEEF");
+    assert!(events[0].contains("Time index 0 (0ns)"), "Event at time 0");
+
+    // Concatenation with bit slices and literals
+    let events = find_conditional_events(
+        &mut waveform,
+        "{top.lo[3:0], 4'h0, top.bit} == 9'b110100000",
+        0,
+        1,
+        -1,
+    )
+    .expect("Should find events for mixed concatenation");
+    assert_eq!(
+        events.len(),
+        1,
+        "Should find 1 event for mixed concatenation"
+    );
+    assert!(events[0].contains("Time index 1 (10ns)"), "Event at time 1");
+
+    // Replication of a single bit
+    let events = find_conditional_events(&mut waveform, "{4{top.bit}} == 4'b1111", 0, 1, -1)
+        .expect("Should find events for replication");
+    assert_eq!(events.len(), 1, "Should find 1 event where bit is set");
+    assert!(events[0].contains("Time index 0 (0ns)"), "Event at time 0");
+
+    // Replication of a concatenation
+    let events = find_conditional_events(&mut waveform, "{2{top.bit, 1'b0}} == 4'b1010", 0, 1, -1)
+        .expect("Should find events for replicated concatenation");
+    assert_eq!(
+        events.len(),
+        1,
+        "Should find 1 event for replicated pattern"
+    );
+
+    // Replication width: {3{1'b1}} is 3 bits wide, so it zero-extends to 4'd7
+    let events = find_conditional_events(&mut waveform, "{3{1'b1}} == 4'd7", 0, 1, -1)
+        .expect("Should evaluate replication width");
+    assert_eq!(events.len(), 2, "Replicated ones should be 3 bits wide");
+
+    // Zero replication count is rejected
+    let result = find_conditional_events(&mut waveform, "{0{top.bit}}", 0, 1, -1);
+    assert!(result.is_err(), "Should fail for zero replication count");
+
+    // Wide replications are built without copying the value once per copy
+    let events = find_conditional_events(
+        &mut waveform,
+        "{100000{top.bit}} == {100000{1'b1}} && {100000{top.hi}} != 0",
+        0,
+        1,
+        -1,
+    )
+    .expect("Should evaluate wide replications");
+    assert_eq!(events.len(), 1, "Should find 1 event for wide replication");
+    assert!(events[0].contains("Time index 0 (0ns)"), "Event at time 0");
+
+    // Replications wider than any value are rejected, also when nested
+    for condition in [
+        "{4294967295{top.bit}} == 0",
+        "{65536{{65536{top.bit}}}} == 0",
+        "{2{top.bit}} == {4294967295{1'b1}}",
+    ] {
+        let error = find_conditional_events(&mut waveform, condition, 0, 1, -1)
+            .expect_err("Should reject replication that is too wide");
+        assert!(
+            error.contains("Replication is too wide"),
+            "Unexpected error for {}: {}",
+            condition,
+            error
+        );
+    }
+
+    // So are literals, whether sized or as wide as their digits
+    let long_hex = format!("'h{}", "F".repeat(300_000));
+    let long_decimal = format!("1{}", "0".repeat(400_000));
+    for condition in [
+        "top.bit == '1 + 2000000000'd0",
+        "top.bit == ~4000000000'd0",
+        "top.bit == 4294967295'b1",
+        long_hex.as_str(),
+        long_decimal.as_str(),
+    ] {
+        let error = find_conditional_events(&mut waveform, condition, 0, 1, -1)
+            .expect_err("Should reject a literal that is too wide");
+        assert!(
+            error.contains("Width of a literal must be at most 1048576"),
+            "Unexpected error for {}: {}",
+            &condition[..condition.len().min(40)],
+            error
+        );
+    }
+}
+
+#[test]
+fn test_shift_operators() {
+    // Create a VCD file with an 8-bit data signal
+    let vcd_content = "\
+$date 2024-01-01 $end\n\
+$version Test VCD file $end\n\
+$timescale 1ns $end\n\
+$scope module top $end\n\
+$var wire 8 ! data $end\n\
+$var wire 3 0 amount $end\n\
+$upscope $end\n\
+$enddefinitions $end\n\
+#0\n\
+b10010110 !\n\
+b010 0\n\
+#10\n\
+b00000101 !\n\
+b001 0\n\
+";
+
+    let temp_file = tempfile::NamedTempFile::new().expect("Failed to create temp file");
+    std::fs::write(temp_file.path(), vcd_content).expect("Failed to write VCD file");
+
+    let mut waveform = wellen::simple::read(temp_file.path()).expect("Failed to read VCD file");
+
+    // Left shift drops bits shifted out of the operand width: 0x96 << 2 = 0x58
+    let events =
+        find_conditional_events(&mut waveform, "top.data << top.amount == 8'h58", 0, 1, -1)
+            .expect("Should find events for left shift");
+    assert_eq!(events.len(), 1, "Should find 1 event for left shift");
+    assert!(events[0].contains("Time index 0 (0ns)"), "Event at time 0");
+
+    // In a wider context the shifted-out bits are kept: 0x96 << 2 = 0x258
+    let events =
+        find_conditional_events(&mut waveform, "top.data << top.amount == 12'h258", 0, 1, -1)
+            .expect("Should find events for left shift in wider context");
+    assert_eq!(
+        events.len(),
+        1,
+        "Should find 1 event for widened left shift"
+    );
+
+    // Logical right shift: 0x05 >> 1 = 0x02
+    let events =
+        find_conditional_events(&mut waveform, "top.data >> top.amount == 8'h02", 0, 1, -1)
+            .expect("Should find events for right shift");
+    assert_eq!(events.len(), 1, "Should find 1 event for right shift");
+    assert!(events[0].contains("Time index 1 (10ns)"), "Event at time 1");
+
+    // Arithmetic shifts on unsigned operands behave like logical shifts
+    let events = find_conditional_events(&mut waveform, "top.data >>> 3'd4 == 8'h09", 0, 1, -1)
+        .expect("Should find events for arithmetic right shift");
+    assert_eq!(events.len(), 1, "Should find 1 event for arithmetic shift");
+    let events = find_conditional_events(&mut waveform, "top.data <<< 3'd1 == 8'h0A", 0, 1, -1)
+        .expect("Should find events for arithmetic left shift");
+    assert_eq!(
+        events.len(),
+        1,
+        "Should find 1 event for arithmetic left shift"
+    );
+
+    // Shift binds looser than addition: 1 << (1 + 1) = 4
+    let events = find_conditional_events(&mut waveform, "4'd1 << 4'd1 + 4'd1 == 4'd4", 0, 1, -1)
+        .expect("Should evaluate shift precedence");
+    assert_eq!(events.len(), 2, "Shift amount should include the addition");
+
+    // Shifting by the full width or more clears all bits
+    let events = find_conditional_events(&mut waveform, "top.data >> 4'd8", 0, 1, -1)
+        .expect("Should evaluate oversized shift");
+    assert!(events.is_empty(), "Oversized shift should be zero");
+}
+
+#[test]
+fn test_four_state_propagation() {
+    // Create a VCD file with a bus that is X, partially unknown, then known
+    let vcd_content = "\
+$date 2024-01-01 $end\n\
+$version Test VCD file $end\n\
+$timescale 1ns $end\n\
+$scope module top $end\n\
+$var wire 4 ! bus $end\n\
+$var wire 1 0 en $end\n\
+$upscope $end\n\
+$enddefinitions $end\n\
+#0\n\
+bxxxx !\n\
+00\n\
+#10\n\
+b10zx !\n\
+10\n\
+#20\n\
+b0000 !\n\
+10\n\
+";
+
+    let temp_file = tempfile::NamedTempFile::new().expect("Failed to create temp file");
+    std::fs::write(temp_file.path(), vcd_content).expect("Failed to write VCD file");
+
+    let mut waveform = wellen::simple::read(temp_file.path()).expect("Failed to read VCD file");
+
+    // An all-X bus must not compare equal to a known value
+    let events = find_conditional_events(&mut waveform, "top.bus == 4'b0000", 0, 2, -1)
+        .expect("Should evaluate equality with X");
+    assert_eq!(events.len(), 1, "Only the known zero should match");
+    assert!(events[0].contains("Time index 2 (20ns)"), "Event at time 2");
+
+    // Inequality with X is also unknown, so it does not match either
+    let events = find_conditional_events(&mut waveform, "top.bus != 4'b0000", 0, 2, -1)
+        .expect("Should evaluate inequality with X");
+    assert_eq!(
+        events.len(),
+        1,
+        "Only the partially known bus differs for sure"
+    );
+    assert!(events[0].contains("Time index 1 (10ns)"), "Event at time 1");
+
+    // X propagates through arithmetic
+    let events = find_conditional_events(&mut waveform, "top.bus + 4'd1 == 4'd1", 0, 2, -1)
+        .expect("Should evaluate arithmetic with X");
+    assert_eq!(
+        events.len(),
+        1,
+        "Only the known bus should produce a result"
+    );
+
+    // A known 0 dominates X in logical AND, so the negation is known true
+    let events = find_conditional_events(&mut waveform, "!(top.en && top.bus)", 0, 2, -1)
+        .expect("Should evaluate logical AND with X");
+    assert_eq!(events.len(), 2, "Should match at times 0 and 2");
+    assert!(
+        events[0].contains("Time index 0 (0ns)"),
+        "First event at time 0"
+    );
+
+    // A known 1 bit dominates X in bitwise OR
+    let events = find_conditional_events(&mut waveform, "(top.bus | 4'b0001) == 4'b1001", 0, 2, -1)
+        .expect("Should evaluate bitwise OR with X");
+    assert!(
+        events.is_empty(),
+        "Bits 1 and 0 of the partial bus are still unknown"
+    );
+    let events = find_conditional_events(&mut waveform, "(top.bus & 4'b1100) == 4'b1000", 0, 2, -1)
+        .expect("Should evaluate bitwise AND with X");
+    assert_eq!(
+        events.len(),
+        1,
+        "Masking off the unknown bits gives a known result"
+    );
+    assert!(events[0].contains("Time index 1 (10ns)"), "Event at time 1");
+}
+
+#[test]
+fn test_case_and_wildcard_equality() {
+    // Create a VCD file with a bus that is X, partially unknown, then known
+    let vcd_content = "\
+$date 2024-01-01 $end\n\
+$version Test VCD file $end\n\
+$timescale 1ns $end\n\
+$scope module top $end\n\
+$var wire 4 ! bus $end\n\
+$upscope $end\n\
+$enddefinitions $end\n\
+#0\n\
+bxxxx !\n\
+#10\n\
+b10zx !\n\
+#20\n\
+b1011 !\n\
+";
+
+    let temp_file = tempfile::NamedTempFile::new().expect("Failed to create temp file");
+    std::fs::write(temp_file.path(), vcd_content).expect("Failed to write VCD file");
+
+    let mut waveform = wellen::simple::read(temp_file.path()).expect("Failed to read VCD file");
+
+    // Case equality matches X bits exactly; 4'bx is extended to 4'bxxxx
+    let events = find_conditional_events(&mut waveform, "top.bus === 4'bx", 0, 2, -1)
+        .expect("Should evaluate case equality");
+    assert_eq!(events.len(), 1, "Only the all-X bus should match");
+    assert!(events[0].contains("Time index 0 (0ns)"), "Event at time 0");
+
+    // Case equality distinguishes Z from X
+    let events = find_conditional_events(&mut waveform, "top.bus === 4'b10zx", 0, 2, -1)
+        .expect("Should evaluate case equality with Z");
+    assert_eq!(events.len(), 1, "Only the partial bus should match");
+    let events = find_conditional_events(&mut waveform, "top.bus === 4'b10xx", 0, 2, -1)
+        .expect("Should evaluate case equality with X");
+    assert!(events.is_empty(), "Z should not match X in case equality");
+
+    // Case inequality is never unknown
+    let events = find_conditional_events(&mut waveform, "top.bus !== 4'b1011", 0, 2, -1)
+        .expect("Should evaluate case inequality");
+    assert_eq!(events.len(), 2, "Both unknown values differ from 4'b1011");
+
+    // Wildcard equality ignores X, Z and ? bits in the right operand
+    let events = find_conditional_events(&mut waveform, "top.bus ==? 4'b10??", 0, 2, -1)
+        .expect("Should evaluate wildcard equality");
+    assert_eq!(events.len(), 2, "Should match the partial and known bus");
+    assert!(
+        events[0].contains("Time index 1 (10ns)"),
+        "First event at time 1"
+    );
+    assert!(
+        events[1].contains("Time index 2 (20ns)"),
+        "Second event at time 2"
+    );
+
+    // Unknown bits in the left operand at compared positions make the result unknown
+    let events = find_conditional_events(&mut waveform, "top.bus ==? 4'b1x11", 0, 2, -1)
+        .expect("Should evaluate wildcard equality with unknown left operand");
+    assert_eq!(events.len(), 1, "Only the known bus should match");
+    let events = find_conditional_events(&mut waveform, "top.bus !=? 4'b0xxx", 0, 2, -1)
+        .expect("Should evaluate wildcard inequality");
+    assert_eq!(events.len(), 2, "Bit 3 is a known 1 at times 1 and 2");
+}
+
+#[test]
+fn test_wide_signal_comparison() {
+    // Create a VCD file with a 12-bit signal spanning multiple bytes
+    let vcd_content = "\
+$date 2024-01-01 $end\n\
+$version Test VCD file $end\n\
+$timescale 1ns $end\n\
+$scope module top $end\n\
+$var wire 12 ! data $end\n\
+$upscope $end\n\
+$enddefinitions $end\n\
+#0\n\
+b101010111100 !\n\
+#10\n\
+b000000000001 !\n\
+";
+
+    let temp_file = tempfile::NamedTempFile::new().expect("Failed to create temp file");
+    std::fs::write(temp_file.path(), vcd_content).expect("Failed to write VCD file");
+
+    let mut waveform = wellen::simple::read(temp_file.path()).expect("Failed to read VCD file");
+
+    let events = find_conditional_events(&mut waveform, "top.data == 12'hABC", 0, 1, -1)
+        .expect("Should find events for wide signal");
+    assert_eq!(events.len(), 1, "Should find 1 event where data is ABC");
+    assert!(events[0].contains("Time index 0 (0ns)"), "Event at time 0");
+
+    let events = find_conditional_events(&mut waveform, "top.data[11:8] == 4'hA", 0, 1, -1)
+        .expect("Should find events for upper bits of wide signal");
+    assert_eq!(
+        events.len(),
+        1,
+        "Should find 1 event where upper nibble is A"
+    );
+}
+
+#[test]
+fn test_sampled_value_functions() {
+    // Create a VCD file with a signal: 0, 1, 1, 0, x, 1
+    let vcd_content = "\
+$date 2024-01-01 $end\n\
+$version Test VCD file $end\n\
+$timescale 1ns $end\n\
+$scope module top $end\n\
+$var wire 1 0 signal $end\n\
+$upscope $end\n\
+$enddefinitions $end\n\
+#0\n\
+00\n\
+#10\n\
+10\n\
+#20\n\
+10\n\
+#30\n\
+00\n\
+#40\n\
+x0\n\
+#50\n\
+10\n\
+";
+
+    let temp_file = tempfile::NamedTempFile::new().expect("Failed to create temp file");
+    std::fs::write(temp_file.path(), vcd_content).expect("Failed to write VCD file");
+
+    let mut waveform = wellen::simple::read(temp_file.path()).expect("Failed to read VCD file");
+
+    // $rose: changes to 1 at time 1 and from X to 1 at time 5
+    let events = find_conditional_events(&mut waveform, "$rose(top.signal)", 0, 5, -1)
+        .expect("Should find events for $rose");
+    assert_eq!(events.len(), 2, "Should find 2 rising edges");
+    assert!(
+        events[0].contains("Time index 1 (10ns)"),
+        "First rising edge at time 1"
+    );
+    assert!(
+        events[1].contains("Time index 5 (50ns)"),
+        "Second rising edge at time 5"
+    );
+
+    // $fell: changes to 0 at time 3 only; the change to X is not a falling edge
+    let events = find_conditional_events(&mut waveform, "$fell(top.signal)", 0, 5, -1)
+        .expect("Should find events for $fell");
+    assert_eq!(events.len(), 1, "Should find 1 falling edge");
+    assert!(
+        events[0].contains("Time index 3 (30ns)"),
+        "Falling edge at time 3"
+    );
+
+    // $stable: unchanged at time 0 (past defaults to 0) and time 2
+    let events = find_conditional_events(&mut waveform, "$stable(top.signal)", 0, 5, -1)
+        .expect("Should find events for $stable");
+    assert_eq!(events.len(), 2, "Should find 2 stable points");
+    assert!(
+        events[1].contains("Time index 2 (20ns)"),
+        "Second stable point at time 2"
+    );
+
+    // $changed: including the changes to and from X
+    let events = find_conditional_events(&mut waveform, "$changed(top.signal)", 0, 5, -1)
+        .expect("Should find events for $changed");
+    assert_eq!(events.len(), 4, "Should find 4 changes");
+    assert!(
+        events[2].contains("Time index 4 (40ns)"),
+        "Change to X at time 4"
+    );
+
+    // Sampled value functions combine with other operators
+    let events = find_conditional_events(
+        &mut waveform,
+        "$rose(top.signal) && !$past(top.signal)",
+        0,
+        5,
+        -1,
+    )
+    .expect("Should find events for combined condition");
+    assert_eq!(events.len(), 1, "$past of X is not known false");
+}
+
+#[test]
+fn test_past_with_depth() {
+    // Create a VCD file with a counter that increments each time step
+    let vcd_content = "\
+$date 2024-01-01 $end\n\
+$version Test VCD file $end\n\
+$timescale 1ns $end\n\
+$scope module top $end\n\
+$var wire 4 ! counter $end\n\
+$var wire 8 0 bus $end\n\
+$upscope $end\n\
+$enddefinitions $end\n\
+#0\n\
+b0000 !\n\
+b00000001 0\n\
+#10\n\
+b0001 !\n\
+b00000001 0\n\
+#20\n\
+b0010 !\n\
+b00000010 0\n\
+#30\n\
+b0011 !\n\
+b00000011 0\n\
+";
+
+    let temp_file = tempfile::NamedTempFile::new().expect("Failed to create temp file");
+    std::fs::write(temp_file.path(), vcd_content).expect("Failed to write VCD file");
+
+    let mut waveform = wellen::simple::read(temp_file.path()).expect("Failed to read VCD file");
+
+    // $past(counter, 2) is counter - 2 once two time indices are available
+    let events = find_conditional_events(
+        &mut waveform,
+        "$past(top.counter, 2) + 4'd2 == top.counter",
+        0,
+        3,
+        -1,
+    )
+    .expect("Should find events for $past with depth");
+    assert_eq!(events.len(), 2, "Should find 2 events at times 2 and 3");
+    assert!(
+        events[0].contains("Time index 2 (20ns)"),
+        "First event at time 2"
+    );
+
+    // $past(expr, 1) is the same as $past(expr)
+    let events = find_conditional_events(
+        &mut waveform,
+        "$past(top.counter, 1) == $past(top.counter)",
+        0,
+        3,
+        -1,
+    )
+    .expect("Should find events for $past with depth 1");
+    assert_eq!(events.len(), 4, "Depth 1 should match the default depth");
+
+    // $changed and $stable work on multi-bit values
+    let events = find_conditional_events(&mut waveform, "$stable(top.bus)", 0, 3, -1)
+        .expect("Should find events for $stable on a bus");
+    assert_eq!(events.len(), 1, "Bus is stable only at time 1");
+    assert!(
+        events[0].contains("Time index 1 (10ns)"),
+        "Stable at time 1"
+    );
+
+    // $rose looks at the least significant bit only: bit 0 rises at time 3
+    let events = find_conditional_events(&mut waveform, "$rose(top.bus)", 0, 3, -1)
+        .expect("Should find events for $rose on a bus");
+    assert_eq!(events.len(), 2, "Bit 0 rises at times 0 and 3");
+
+    // A depth of 0 is rejected
+    let result = find_conditional_events(&mut waveform, "$past(top.counter, 0)", 0, 3, -1);
+    assert!(result.is_err(), "Should fail for $past depth 0");
+}
+
+/// VCD with a clock and registers that update on each rising edge.
+const CLOCKED_VCD: &str = "\
+$date 2024-01-01 $end\n\
+$version Test VCD file $end\n\
+$timescale 1ns $end\n\
+$scope module top $end\n\
+$var wire 1 0 clk $end\n\
+$var wire 1 1 valid $end\n\
+$var wire 4 ! counter $end\n\
+$upscope $end\n\
+$enddefinitions $end\n\
+#0\n\
+00\n\
+01\n\
+b0000 !\n\
+#5\n\
+10\n\
+11\n\
+b0001 !\n\
+#10\n\
+00\n\
+#15\n\
+10\n\
+b0010 !\n\
+#20\n\
+00\n\
+#25\n\
+10\n\
+01\n\
+b0011 !\n\
+#30\n\
+00\n\
+";
+
+#[test]
+fn test_clock_sampled_conditions() {
+    let temp_file = tempfile::NamedTempFile::new().expect("Failed to create temp file");
+    std::fs::write(temp_file.path(), CLOCKED_VCD).expect("Failed to write VCD file");
+
+    let mut waveform = wellen::simple::read(temp_file.path()).expect("Failed to read VCD file");
+
+    // Rising edges are at time indices 1, 3 and 5; values are sampled just before each
+    // edge, so valid is seen as 0, 1, 1
+    let events = find_conditional_events(&mut waveform, "@(posedge top.clk) top.valid", 0, 6, -1)
+        .expect("Should find events for clock-sampled condition");
+    assert_eq!(events.len(), 2, "Should find 2 clock edges with valid set");
+    assert!(
+        events[0].contains("Time index 3 (15ns)"),
+        "First event at the second rising edge"
+    );
+    assert!(
+        events[0].contains("top.valid = 1'b1"),
+        "Should show the sampled value of valid"
+    );
+    assert!(
+        events[1].contains("Time index 5 (25ns)"),
+        "Second event at the third rising edge"
+    );
+
+    // $past refers to the previous clock edge, not the previous time index
+    let events = find_conditional_events(
+        &mut waveform,
+        "@(posedge top.clk) top.counter == $past(top.counter) + 4'd1",
+        0,
+        6,
+        -1,
+    )
+    .expect("Should find events for clock-sampled $past");
+    assert_eq!(events.len(), 2, "Counter increments once per clock cycle");
+    assert!(
+        events[0].contains("Time index 3 (15ns)"),
+        "First increment seen at the second rising edge"
+    );
+
+    // Sampled value functions also use clock edges
+    let events = find_conditional_events(
+        &mut waveform,
+        "@(posedge top.clk) $rose(top.valid)",
+        0,
+        6,
+        -1,
+    )
+    .expect("Should find events for clock-sampled $rose");
+    assert_eq!(events.len(), 1, "valid rises once between clock edges");
+    assert!(
+        events[0].contains("Time index 3 (15ns)"),
+        "Rise seen at the second rising edge"
+    );
+
+    // Falling edges are at time indices 2, 4 and 6
+    let events = find_conditional_events(&mut waveform, "@(negedge top.clk) top.valid", 0, 6, -1)
+        .expect("Should find events for negedge-sampled condition");
+    assert_eq!(
+        events.len(),
+        2,
+        "Should find 2 falling edges with valid set"
+    );
+    assert!(
+        events[0].contains("Time index 2 (10ns)"),
+        "First event at the first falling edge"
+    );
+
+    // Both edges
+    let events = find_conditional_events(&mut waveform, "@(edge top.clk) top.valid", 0, 6, -1)
+        .expect("Should find events for edge-sampled condition");
+    assert_eq!(events.len(), 4, "Should find 4 clock edges with valid set");
+
+    // The time range applies to the clock edges
+    let events = find_conditional_events(&mut waveform, "@(posedge top.clk) top.valid", 4, 6, -1)
+        .expect("Should find events in time range");
+    assert_eq!(events.len(), 1, "Only the last rising edge is in range");
+    assert!(
+        events[0].contains("Time index 5 (25ns)"),
+        "Event at the third rising edge"
+    );
+}
+
+#[test]
+fn test_clock_sampled_options() {
+    let temp_file = tempfile::NamedTempFile::new().expect("Failed to create temp file");
+    std::fs::write(temp_file.path(), CLOCKED_VCD).expect("Failed to write VCD file");
+
+    let mut waveform = wellen::simple::read(temp_file.path()).expect("Failed to read VCD file");
+
+    let options = ConditionOptions {
+        clock: Some(SamplingClock {
+            signal: "top.clk".to_string(),
+            edge: ClockEdge::Posedge,
+        }),
+        ..Default::default()
+    };
+
+    // Passing the clock as an option is the same as the @(posedge) prefix
+    let events =
+        find_conditional_events_with_options(&mut waveform, "top.valid", 0, 6, -1, &options)
+            .expect("Should find events with sampling clock option");
+    assert_eq!(events.len(), 2, "Should find 2 clock edges with valid set");
+    assert!(
+        events[0].contains("Time index 3 (15ns)"),
+        "First event at the second rising edge"
+    );
+
+    // Without a clock every time index is evaluated
+    let events = find_conditional_events_with_options(
+        &mut waveform,
+        "top.valid",
+        0,
+        6,
+        -1,
+        &ConditionOptions::default(),
+    )
+    .expect("Should find events without sampling clock");
+    assert_eq!(events.len(), 4, "valid is set at time indices 1 to 4");
+
+    // Giving the clock twice is an error
+    let result = find_conditional_events_with_options(
+        &mut waveform,
+        "@(posedge top.clk) top.valid",
+        0,
+        6,
+        -1,
+        &options,
+    );
+    assert!(result.is_err(), "Should fail for clock given twice");
+
+    // Unknown clock signal is an error
+    let result =
+        find_conditional_events(&mut waveform, "@(posedge top.missing) top.valid", 0, 6, -1);
+    assert!(result.is_err(), "Should fail for unknown clock signal");
+
+    // Clock edge names parse from strings
+    assert_eq!("negedge".parse::<ClockEdge>(), Ok(ClockEdge::Negedge));
+    assert!("rising".parse::<ClockEdge>().is_err());
+}
+
+#[test]
+fn test_reduction_operators() {
+    // Create a VCD file with a data bus and a parity bit that is wrong at time 2
+    let vcd_content = "\
+$date 2024-01-01 $end\n\
+$version Test VCD file $end\n\
+$timescale 1ns $end\n\
+$scope module top $end\n\
+$var wire 4 ! grant $end\n\
+$var wire 8 \" data $end\n\
+$var wire 1 0 parity $end\n\
+$upscope $end\n\
+$enddefinitions $end\n\
+#0\n\
+b0000 !\n\
+b00000000 \"\n\
+00\n\
+#10\n\
+b0100 !\n\
+b00000111 \"\n\
+10\n\
+#20\n\
+b0110 !\n\
+b11110000 \"\n\
+#30\n\
+b1x00 !\n\
+b1111111x \"\n\
+00\n\
+";
+
+    let temp_file = tempfile::NamedTempFile::new().expect("Failed to create temp file");
+    std::fs::write(temp_file.path(), vcd_content).expect("Failed to write VCD file");
+
+    let mut waveform = wellen::simple::read(temp_file.path()).expect("Failed to read VCD file");
+
+    // Parity mismatch; the parity of a bus with X bits is unknown
+    let events = find_conditional_events(&mut waveform, "^top.data != top.parity", 0, 3, -1)
+        .expect("Should evaluate reduction XOR");
+    assert_eq!(events.len(), 1, "Only time 2 has a parity error");
+    assert!(events[0].contains("Time index 2 (20ns)"), "Event at time 2");
+
+    // A known 1 bit makes reduction OR true even with X bits
+    let events = find_conditional_events(&mut waveform, "|top.data", 0, 3, -1)
+        .expect("Should evaluate reduction OR");
+    assert_eq!(events.len(), 3, "Should match at times 1, 2 and 3");
+
+    let events = find_conditional_events(&mut waveform, "~|top.data", 0, 3, -1)
+        .expect("Should evaluate reduction NOR");
+    assert_eq!(events.len(), 1, "Only the all-zero bus matches");
+    assert!(events[0].contains("Time index 0 (0ns)"), "Event at time 0");
+
+    // All ones except an X bit is unknown for reduction AND
+    let events = find_conditional_events(&mut waveform, "&top.data", 0, 3, -1)
+        .expect("Should evaluate reduction AND");
+    assert!(events.is_empty(), "No time has all data bits known to be 1");
+    let events = find_conditional_events(&mut waveform, "~&top.data", 0, 3, -1)
+        .expect("Should evaluate reduction NAND");
+    assert_eq!(events.len(), 3, "Times 0, 1 and 2 have a known 0 bit");
+
+    // Unary operators bind tighter than binary ones
+    let events = find_conditional_events(&mut waveform, "&top.grant[2:1] == 1'b1", 0, 3, -1)
+        .expect("Should evaluate reduction of a bit range");
+    assert_eq!(events.len(), 1, "Only time 2 has both bits set");
+    assert!(events[0].contains("Time index 2 (20ns)"), "Event at time 2");
+    let events = find_conditional_events(&mut waveform, "top.parity & ^top.data", 0, 3, -1)
+        .expect("Should evaluate reduction as a binary operand");
+    assert_eq!(events.len(), 1, "Only time 1 has both parity bits set");
+}
+
+#[test]
+fn test_bit_counting_functions() {
+    // Create a VCD file with a grant vector that is idle, one-hot, two-hot and partly X
+    let vcd_content = "\
+$date 2024-01-01 $end\n\
+$version Test VCD file $end\n\
+$timescale 1ns $end\n\
+$scope module top $end\n\
+$var wire 4 ! grant $end\n\
+$upscope $end\n\
+$enddefinitions $end\n\
+#0\n\
+b0000 !\n\
+#10\n\
+b0100 !\n\
+#20\n\
+b0110 !\n\
+#30\n\
+b1x00 !\n\
+";
+
+    let temp_file = tempfile::NamedTempFile::new().expect("Failed to create temp file");
+    std::fs::write(temp_file.path(), vcd_content).expect("Failed to write VCD file");
+
+    let mut waveform = wellen::simple::read(temp_file.path()).expect("Failed to read VCD file");
+
+    let events = find_conditional_events(&mut waveform, "!$onehot0(top.grant)", 0, 3, -1)
+        .expect("Should evaluate $onehot0");
+    assert_eq!(events.len(), 1, "Only time 2 grants more than one");
+    assert!(events[0].contains("Time index 2 (20ns)"), "Event at time 2");
+
+    // X bits are not counted as ones
+    let events = find_conditional_events(&mut waveform, "$onehot(top.grant)", 0, 3, -1)
+        .expect("Should evaluate $onehot");
+    assert_eq!(events.len(), 2, "Should match at times 1 and 3");
+
+    let events = find_conditional_events(&mut waveform, "$countones(top.grant) == 4'd2", 0, 3, -1)
+        .expect("Should evaluate $countones");
+    assert_eq!(events.len(), 1, "Only time 2 has two bits set");
+
+    let events = find_conditional_events(&mut waveform, "$isunknown(top.grant)", 0, 3, -1)
+        .expect("Should evaluate $isunknown");
+    assert_eq!(events.len(), 1, "Only time 3 has an X bit");
+    assert!(events[0].contains("Time index 3 (30ns)"), "Event at time 3");
+}
+
+#[test]
+fn test_signed_comparisons() {
+    // Create a VCD file with an 8-bit accumulator and a 4-bit offset holding
+    // positive and negative two's complement values
+    let vcd_content = "\
+$date 2024-01-01 $end\n\
+$version Test VCD file $end\n\
+$timescale 1ns $end\n\
+$scope module top $end\n\
+$var wire 8 ! acc $end\n\
+$var wire 4 \" small $end\n\
+$upscope $end\n\
+$enddefinitions $end\n\
+#0\n\
+b00000101 !\n\
+b0010 \"\n\
+#10\n\
+b11111011 !\n\
+b1111 \"\n\
+#20\n\
+b11110000 !\n\
+b1000 \"\n\
+#30\n\
+b01111111 !\n\
+b0111 \"\n\
+";
+
+    let temp_file = tempfile::NamedTempFile::new().expect("Failed to create temp file");
+    std::fs::write(temp_file.path(), vcd_content).expect("Failed to write VCD file");
+
+    let mut waveform = wellen::simple::read(temp_file.path()).expect("Failed to read VCD file");
+
+    // acc is 5, -5, -16 and 127
+    let events = find_conditional_events(&mut waveform, "$signed(top.acc) < -8'sd5", 0, 3, -1)
+        .expect("Should compare against a negative threshold");
+    assert_eq!(events.len(), 1, "Only -16 is below -5");
+    assert!(events[0].contains("Time index 2 (20ns)"), "Event at time 2");
+
+    let events = find_conditional_events(&mut waveform, "$signed(top.acc) <= -8'sd5", 0, 3, -1)
+        .expect("Should evaluate signed less-or-equal");
+    assert_eq!(events.len(), 2, "Should match -5 and -16");
+
+    let events = find_conditional_events(&mut waveform, "$signed(top.acc) < 8'sd0", 0, 3, -1)
+        .expect("Should evaluate signed comparison with zero");
+    assert_eq!(events.len(), 2, "Should match the negative values");
+
+    // Mixing signed and unsigned operands makes the comparison unsigned
+    let events = find_conditional_events(&mut waveform, "$signed(top.acc) < 8'd10", 0, 3, -1)
+        .expect("Should evaluate mixed comparison");
+    assert_eq!(events.len(), 1, "Only 5 is below 10 when unsigned");
+    assert!(events[0].contains("Time index 0 (0ns)"), "Event at time 0");
+
+    // Signed operands are sign-extended to the width of the comparison
+    let events = find_conditional_events(&mut waveform, "$signed(top.small) == -8'sd1", 0, 3, -1)
+        .expect("Should sign-extend a narrow operand");
+    assert_eq!(events.len(), 1, "4'b1111 is -1");
+    assert!(events[0].contains("Time index 1 (10ns)"), "Event at time 1");
+    let events = find_conditional_events(&mut waveform, "top.small == 8'h0F", 0, 3, -1)
+        .expect("Should zero-extend an unsigned operand");
+    assert_eq!(events.len(), 1, "4'b1111 is 15 when unsigned");
+    let events = find_conditional_events(
+        &mut waveform,
+        "$unsigned($signed(top.small)) == 8'd15",
+        0,
+        3,
+        -1,
+    )
+    .expect("Should evaluate $unsigned");
+    assert_eq!(events.len(), 1, "$unsigned zero-extends again");
+
+    // small is 2, -1, -8 and 7
+    let events = find_conditional_events(
+        &mut waveform,
+        "$signed(top.small) < $signed(top.acc)",
+        0,
+        3,
+        -1,
+    )
+    .expect("Should compare signed operands of different widths");
+    assert_eq!(events.len(), 2, "Should match at times 0 and 3");
+    assert!(
+        events[0].contains("Time index 0 (0ns)"),
+        "First event at time 0"
+    );
+    assert!(
+        events[1].contains("Time index 3 (30ns)"),
+        "Second event at time 3"
+    );
+}
+
+#[test]
+fn test_signed_arithmetic() {
+    // Same values as test_signed_comparisons
+    let vcd_content = "\
+$date 2024-01-01 $end\n\
+$version Test VCD file $end\n\
+$timescale 1ns $end\n\
+$scope module top $end\n\
+$var wire 8 ! acc $end\n\
+$var wire 4 \" small $end\n\
+$upscope $end\n\
+$enddefinitions $end\n\
+#0\n\
+b00000101 !\n\
+b0010 \"\n\
+#10\n\
+b11111011 !\n\
+b1111 \"\n\
+#20\n\
+b11110000 !\n\
+b1000 \"\n\
+#30\n\
+b01111111 !\n\
+b0111 \"\n\
+";
+
+    let temp_file = tempfile::NamedTempFile::new().expect("Failed to create temp file");
+    std::fs::write(temp_file.path(), vcd_content).expect("Failed to write VCD file");
+
+    let mut waveform = wellen::simple::read(temp_file.path()).expect("Failed to read VCD file");
+
+    // -1 + -5, with the 4-bit operand sign-extended before the addition
+    let events = find_conditional_events(
+        &mut waveform,
+        "$signed(top.small) + $signed(top.acc) == -8'sd6",
+        0,
+        3,
+        -1,
+    )
+    .expect("Should evaluate signed addition");
+    assert_eq!(events.len(), 1, "Only time 1 sums to -6");
+    assert!(events[0].contains("Time index 1 (10ns)"), "Event at time 1");
+
+    // Signed division truncates toward zero and the remainder follows the dividend
+    let events = find_conditional_events(
+        &mut waveform,
+        "$signed(top.acc) / 8'sd2 == -8'sd2",
+        0,
+        3,
+        -1,
+    )
+    .expect("Should evaluate signed division");
+    assert_eq!(events.len(), 1, "-5 / 2 is -2");
+    assert!(events[0].contains("Time index 1 (10ns)"), "Event at time 1");
+    let events = find_conditional_events(
+        &mut waveform,
+        "$signed(top.acc) % 8'sd2 == -8'sd1",
+        0,
+        3,
+        -1,
+    )
+    .expect("Should evaluate signed remainder");
+    assert_eq!(events.len(), 1, "-5 % 2 is -1");
+
+    // >>> fills with the sign bit only for signed operands
+    let events = find_conditional_events(
+        &mut waveform,
+        "($signed(top.acc) >>> 8'd2) == -8'sd4",
+        0,
+        3,
+        -1,
+    )
+    .expect("Should evaluate arithmetic shift");
+    assert_eq!(events.len(), 1, "-16 >>> 2 is -4");
+    assert!(events[0].contains("Time index 2 (20ns)"), "Event at time 2");
+    let events = find_conditional_events(&mut waveform, "(top.acc >>> 8'd2) == 8'h3C", 0, 3, -1)
+        .expect("Should evaluate logical shift of unsigned operand");
+    assert_eq!(events.len(), 1, "8'hF0 >>> 2 is 8'h3C when unsigned");
+    assert!(events[0].contains("Time index 2 (20ns)"), "Event at time 2");
+
+    // Unary minus negates in two's complement
+    let events = find_conditional_events(&mut waveform, "-top.acc == 8'd5", 0, 3, -1)
+        .expect("Should evaluate negation");
+    assert_eq!(events.len(), 1, "Only 8'hFB negates to 5");
+    assert!(events[0].contains("Time index 1 (10ns)"), "Event at time 1");
+}
+
+#[test]
+fn test_wide_and_unsized_literals() {
+    // Create a VCD file with a 128-bit bus that is zero, a pattern, then all ones
+    let pattern: u128 = 0xDEADBEEF_00000000_CAFEBABE_12345678;
+    let vcd_content = format!(
+        "\
+$date 2024-01-01 $end\n\
+$version Test VCD file $end\n\
+$timescale 1ns $end\n\
+$scope module top $end\n\
+$var wire 128 ! data $end\n\
+$var wire 8 \" count $end\n\
+$upscope $end\n\
+$enddefinitions $end\n\
+#0\n\
+b0 !\n\
+b0 \"\n\
+#10\n\
+b{:b} !\n\
+b101010 \"\n\
+#20\n\
+b{:b} !\n\
+b11111111 \"\n\
+",
+        pattern,
+        u128::MAX
+    );
+
+    let temp_file = tempfile::NamedTempFile::new().expect("Failed to create temp file");
+    std::fs::write(temp_file.path(), vcd_content).expect("Failed to write VCD file");
+
+    let mut waveform = wellen::simple::read(temp_file.path()).expect("Failed to read VCD file");
+
+    let events = find_conditional_events(
+        &mut waveform,
+        "top.data == 128'hDEADBEEF_00000000_CAFEBABE_12345678",
+        0,
+        2,
+        -1,
+    )
+    .expect("Should compare against a 128-bit hex literal");
+    assert_eq!(events.len(), 1, "Only time 1 has the pattern");
+    assert!(events[0].contains("Time index 1 (10ns)"), "Event at time 1");
+
+    let events = find_conditional_events(
+        &mut waveform,
+        "top.data == 128'd340282366920938463463374607431768211455",
+        0,
+        2,
+        -1,
+    )
+    .expect("Should compare against a 128-bit decimal literal");
+    assert_eq!(events.len(), 1, "Only time 2 is all ones");
+    assert!(events[0].contains("Time index 2 (20ns)"), "Event at time 2");
+    let events = find_conditional_events(
+        &mut waveform,
+        "top.data == 340282366920938463463374607431768211455",
+        0,
+        2,
+        -1,
+    )
+    .expect("Should compare against a wide unsized literal");
+    assert_eq!(events.len(), 1, "Only time 2 is all ones");
+
+    // X digits in hex literals match anything with wildcard equality
+    let events = find_conditional_events(
+        &mut waveform,
+        "top.data ==? 128'hDEADBEEF_xxxxxxxx_xxxxxxxx_xxxxxxxx",
+        0,
+        2,
+        -1,
+    )
+    .expect("Should compare against a hex literal with X digits");
+    assert_eq!(events.len(), 1, "Only time 1 starts with DEADBEEF");
+
+    // Fill literals take the width of the other operand
+    let events = find_conditional_events(&mut waveform, "top.data == '1", 0, 2, -1)
+        .expect("Should compare against a fill literal");
+    assert_eq!(events.len(), 1, "Only time 2 is all ones");
+    assert!(events[0].contains("Time index 2 (20ns)"), "Event at time 2");
+    let events = find_conditional_events(&mut waveform, "top.data == '0", 0, 2, -1)
+        .expect("Should compare against a zero fill literal");
+    assert_eq!(events.len(), 1, "Only time 0 is all zeros");
+
+    // Unsized, octal and unsized based literals
+    let events = find_conditional_events(&mut waveform, "top.count == 42", 0, 2, -1)
+        .expect("Should compare against an unsized decimal");
+    assert_eq!(events.len(), 1, "Only time 1 has count 42");
+    assert!(events[0].contains("Time index 1 (10ns)"), "Event at time 1");
+    let events = find_conditional_events(&mut waveform, "top.count > 100", 0, 2, -1)
+        .expect("Should compare against an unsized decimal");
+    assert_eq!(events.len(), 1, "Only time 2 has count above 100");
+    let events = find_conditional_events(&mut waveform, "top.count == 8'o52", 0, 2, -1)
+        .expect("Should compare against an octal literal");
+    assert_eq!(events.len(), 1, "42 is 8'o52");
+    let events = find_conditional_events(&mut waveform, "top.count == 'h2A", 0, 2, -1)
+        .expect("Should compare against an unsized hex literal");
+    assert_eq!(events.len(), 1, "42 is 'h2A");
+}
+
+#[test]
+fn test_invalid_literals() {
+    // Create a simple VCD file
+    let vcd_content = "\
+$date 2024-01-01 $end\n\
+$version Test VCD file $end\n\
+$timescale 1ns $end\n\
+$scope module top $end\n\
+$var wire 8 ! count $end\n\
+$upscope $end\n\
+$enddefinitions $end\n\
+#0\n\
+b0 !\n\
+";
+
+    let temp_file = tempfile::NamedTempFile::new().expect("Failed to create temp file");
+    std::fs::write(temp_file.path(), vcd_content).expect("Failed to write VCD file");
+
+    let mut waveform = wellen::simple::read(temp_file.path()).expect("Failed to read VCD file");
+
+    for literal in ["4'd20", "8'hFFF", "2'b111", "3'o17"] {
+        let error = find_conditional_events(
+            &mut waveform,
+            &format!("top.count == {}", literal),
+            0,
+            0,
+            -1,
+        )
+        .expect_err("Should reject a literal wider than its width");
+        assert!(
+            error.contains("does not fit in its declared width"),
+            "Unexpected error for {}: {}",
+            literal,
+            error
+        );
+    }
+
+    // Leading zeros do not count towards the width
+    assert!(
+        find_conditional_events(&mut waveform, "top.count == 4'b0001010", 0, 0, -1).is_ok(),
+        "Leading zeros should be allowed"
+    );
+
+    let error = find_conditional_events(&mut waveform, "top.count == 0'd0", 0, 0, -1)
+        .expect_err("Should reject a zero-width literal");
+    assert!(
+        error.contains("at least 1 bit"),
+        "Unexpected error: {}",
+        error
+    );
+    let error = find_conditional_events(&mut waveform, "top.count == 8'b102", 0, 0, -1)
+        .expect_err("Should reject an invalid digit");
+    assert!(
+        error.contains("Invalid digit '2'"),
+        "Unexpected error: {}",
+        error
+    );
+}
+
+#[test]
+fn test_parse_error_reporting() {
+    // Create a simple VCD file
+    let vcd_content = "\
+$date 2024-01-01 $end\n\
+$version Test VCD file $end\n\
+$timescale 1ns $end\n\
+$scope module top $end\n\
+$var wire 1 ! valid $end\n\
+$var wire 8 \" count $end\n\
+$upscope $end\n\
+$enddefinitions $end\n\
+#0\n\
+0!\n\
+b0 \"\n\
+";
+
+    let temp_file = tempfile::NamedTempFile::new().expect("Failed to create temp file");
+    std::fs::write(temp_file.path(), vcd_content).expect("Failed to write VCD file");
+
+    let mut waveform = wellen::simple::read(temp_file.path()).expect("Failed to read VCD file");
+
+    // The error points at the offending token with a caret snippet
+    let error = find_conditional_events(&mut waveform, "top.valid and top.count", 0, 0, -1)
+        .expect_err("Should reject `and`");
+    assert_eq!(
+        error.lines().collect::<Vec<_>>()[1..],
+        [
+            "  top.valid and top.count",
+            "            ^^^",
+            "  hint: did you mean `&&`?"
+        ],
+        "Unexpected error: {}",
+        error
+    );
+    assert!(
+        error.starts_with("Unexpected `and` at column 11, expected one of"),
+        "Unexpected error: {}",
+        error
+    );
+    assert!(error.contains("`&&`"), "Unexpected error: {}", error);
+
+    // Hints for common mistakes
+    for (condition, hint) in [
+        ("top.count = 1", "did you mean `==`?"),
+        ("top.count <> 1", "did you mean `!=`?"),
+        ("top.valid or top.valid", "did you mean `||`?"),
+        ("top.count == 0xFF", "hex literals are written like 'hFF"),
+        ("(top.valid && top.count", "a `)` is missing"),
+        ("top.valid)", "this `)` has no matching `(`"),
+        ("$bogus(top.valid)", "unknown system function"),
+    ] {
+        let error = find_conditional_events(&mut waveform, condition, 0, 0, -1)
+            .expect_err("Should reject malformed condition");
+        assert!(
+            error.contains(hint),
+            "Unexpected error for {}: {}",
+            condition,
+            error
+        );
+    }
+
+    // A missing operand is reported at the end of the input
+    let error = find_conditional_events(&mut waveform, "top.valid &&", 0, 0, -1)
+        .expect_err("Should reject a missing operand");
+    assert!(
+        error.starts_with("Unexpected end of input at column 13, expected an expression"),
+        "Unexpected error: {}",
+        error
+    );
+
+    // Errors in literals and numbers point at the literal instead of panicking
+    let error = find_conditional_events(&mut waveform, "top.count == 4'd20", 0, 0, -1)
+        .expect_err("Should reject an oversized literal");
+    assert!(
+        error.contains("at column 14\n  top.count == 4'd20\n               ^^^^^"),
+        "Unexpected error: {}",
+        error
+    );
+    let error = find_conditional_events(&mut waveform, "top.count[99999999999:0]", 0, 0, -1)
+        .expect_err("Should reject an oversized range bound");
+    assert!(
+        error.starts_with("Number 99999999999 is too large at column 11"),
+        "Unexpected error: {}",
+        error
+    );
+}
+
+#[test]
+fn test_part_selects() {
+    // A zero-based bus, an index, and buses declared [0:7] and [15:8]
+    let vcd_content = "\
+$date 2024-01-01 $end\n\
+$version Test VCD file $end\n\
+$timescale 1ns $end\n\
+$scope module top $end\n\
+$var wire 16 ! data $end\n\
+$var wire 2 \" idx $end\n\
+$var wire 8 # asc [0:7] $end\n\
+$var wire 8 $ hi [15:8] $end\n\
+$upscope $end\n\
+$enddefinitions $end\n\
+#0\n\
+b1010010111000011 !\n\
+b01 \"\n\
+b10000000 #\n\
+b10000001 $\n\
+#10\n\
+b0001001000110100 !\n\
+bxx \"\n\
+b00000001 #\n\
+b00000010 $\n\
+#20\n\
+b11 \"\n\
+";
+
+    let temp_file = tempfile::NamedTempFile::new().expect("Failed to create temp file");
+    std::fs::write(temp_file.path(), vcd_content).expect("Failed to write VCD file");
+
+    let mut waveform = wellen::simple::read(temp_file.path()).expect("Failed to read VCD file");
+
+    let mut matching_times = |condition: &str| -> Vec<usize> {
+        find_conditional_events(&mut waveform, condition, 0, 2, -1)
+            .unwrap_or_else(|e| panic!("Should evaluate {}: {}", condition, e))
+            .iter()
+            .map(|event| {
+                event["Time index ".len()..]
+                    .split(' ')
+                    .next()
+                    .and_then(|idx| idx.parse().ok())
+                    .expect("Event should start with its time index")
+            })
+            .collect()
+    };
+
+    // Indexed part-selects with a dynamic base; an unknown base selects X
+    assert_eq!(matching_times("top.data[top.idx*4 +: 4] == 4'hC"), [0]);
+    assert_eq!(matching_times("top.data[top.idx*4 +: 4] === 4'bx"), [1]);
+    assert_eq!(matching_times("top.data[15 -: 4] == 4'hA"), [0]);
+    assert_eq!(matching_times("top.data[top.idx]"), [0]);
+
+    // Part-selects of expressions
+    assert_eq!(matching_times("(top.data ^ 16'hFFFF)[3:0] == 4'hC"), [0]);
+    assert_eq!(matching_times("$past(top.data)[15:12] == 4'hA"), [1]);
+    assert_eq!(matching_times("{top.idx, top.data}[17:16] == 2'd3"), [2]);
+    assert_eq!(matching_times("top.data[7:0][7:4] == 4'hC"), [0]);
+
+    // Declared ranges: [0:7] has its most significant bit at index 0
+    assert_eq!(matching_times("top.asc[0]"), [0]);
+    assert_eq!(matching_times("top.asc[7]"), [1, 2]);
+    assert_eq!(matching_times("top.asc[0:3] == 4'b1000"), [0]);
+    assert_eq!(matching_times("top.asc[4 +: 4] == 4'b0001"), [1, 2]);
+    assert_eq!(matching_times("top.hi[15] && top.hi[8]"), [0]);
+    assert_eq!(matching_times("top.hi[9]"), [1, 2]);
+    assert_eq!(matching_times("top.hi[15:12] == 4'h8"), [0]);
+
+    // Out-of-range bits read as X
+    assert_eq!(matching_times("top.hi[0] === 1'bx"), [0, 1, 2]);
+    assert_eq!(matching_times("top.data[17:14] === 4'bxx00"), [1, 2]);
+
+    // The direction of a range must match the declaration
+    let error = find_conditional_events(&mut waveform, "top.asc[7:0] == 8'd0", 0, 2, -1)
+        .expect_err("Should reject a descending range of an ascending signal");
+    assert!(
+        error.contains("declared [0:7]"),
+        "Unexpected error: {}",
+        error
+    );
+    let error = find_conditional_events(&mut waveform, "top.data[0:3] == 4'd0", 0, 2, -1)
+        .expect_err("Should reject an ascending range of a descending signal");
+    assert!(
+        error.contains("msb must be >= lsb"),
+        "Unexpected error: {}",
+        error
+    );
+    assert!(
+        find_conditional_events(&mut waveform, "top.data[top.idx +: 0] == 1'b0", 0, 2, -1).is_err(),
+        "Should reject a zero-width indexed part-select"
+    );
+
+    // Part-selects wider than any value are rejected rather than built
+    for condition in [
+        "top.data[4294967295:0] == 0",
+        "top.data[4294967295:0]",
+        "top.data[0 +: 4294967295] == 0",
+        "top.data[top.idx -: 2000000] == 0",
+    ] {
+        let error = find_conditional_events(&mut waveform, condition, 0, 2, -1)
+            .expect_err("Should reject a part-select that is too wide");
+        assert!(
+            error.contains("Part-select is too wide") || error.contains("must be at most"),
+            "Unexpected error for {}: {}",
+            condition,
+            error
+        );
+    }
+}
+
+#[test]
+fn test_ternary_operator() {
+    // A mode bit that is X at times 2 and 3, choosing between a 4-bit and an 8-bit value
+    let vcd_content = "\
+$date 2024-01-01 $end\n\
+$version Test VCD file $end\n\
+$timescale 1ns $end\n\
+$scope module top $end\n\
+$var wire 1 ! mode $end\n\
+$var wire 4 \" a $end\n\
+$var wire 8 # b $end\n\
+$upscope $end\n\
+$enddefinitions $end\n\
+#0\n\
+0!\n\
+b0011 \"\n\
+b00010010 #\n\
+#10\n\
+1!\n\
+#20\n\
+x!\n\
+b00010011 #\n\
+#30\n\
+b00000011 #\n\
+";
+
+    let temp_file = tempfile::NamedTempFile::new().expect("Failed to create temp file");
+    std::fs::write(temp_file.path(), vcd_content).expect("Failed to write VCD file");
+
+    let mut waveform = wellen::simple::read(temp_file.path()).expect("Failed to read VCD file");
+
+    let mut matching_times = |condition: &str| -> Vec<usize> {
+        find_conditional_events(&mut waveform, condition, 0, 3, -1)
+            .unwrap_or_else(|e| panic!("Should evaluate {}: {}", condition, e))
+            .iter()
+            .map(|event| {
+                event["Time index ".len()..]
+                    .split(' ')
+                    .next()
+                    .and_then(|idx| idx.parse().ok())
+                    .expect("Event should start with its time index")
+            })
+            .collect()
+    };
+
+    assert_eq!(matching_times("(top.mode ? top.a : top.b) == 8'h12"), [0]);
+
+    // With an unknown condition, bits on which both values agree are kept
+    assert_eq!(matching_times("(top.mode ? top.a : top.b) == 4'h3"), [1, 3]);
+    assert_eq!(
+        matching_times("(top.mode ? top.a : top.b) === 8'b000x0011"),
+        [2]
+    );
+
+    // Both values take the width of the wider one, here inside a concatenation
+    assert_eq!(
+        matching_times("{1'b1, top.mode ? top.a : top.b} == 9'h103"),
+        [1, 3]
+    );
+    // A signed value is sign-extended when both values are signed
+    assert_eq!(
+        matching_times("(top.mode ? $signed(top.a | 4'hC) : 8'sd1) == -8'sd1"),
+        [1]
+    );
+    assert_eq!(
+        matching_times("(top.mode ? top.a | 4'hC : 8'sd1) == 8'h0F"),
+        [1]
+    );
+
+    // `?:` has the lowest precedence and groups to the right
+    assert_eq!(
+        matching_times("top.mode ? top.a == 4'h3 : top.b == 8'h12"),
+        [0, 1]
+    );
+    assert_eq!(
+        matching_times("(top.mode ? 2'd1 : top.b == 8'h12 ? 2'd2 : 2'd3) == 2'd2"),
+        [0]
+    );
+    assert_eq!(matching_times("top.b[top.mode ? 4 : 0 +: 4] == 4'h1"), [1]);
+}
+
+#[test]
+fn test_special_identifiers() {
+    // A generate block, an escaped identifier, a name with `$`, a bit-blasted vector,
+    // a name starting with a digit and a name that is also a keyword
+    let vcd_content = "\
+$date 2024-01-01 $end\n\
+$version Test VCD file $end\n\
+$timescale 1ns $end\n\
+$scope module top $end\n\
+$scope module gen_loop[3] $end\n\
+$scope module u_core $end\n\
+$var wire 4 ! data $end\n\
+$upscope $end\n\
+$upscope $end\n\
+$var wire 1 \" \\a.b $end\n\
+$var wire 1 # cnt$next $end\n\
+$var wire 1 $ bits[0] $end\n\
+$var wire 1 % bits[1] $end\n\
+$var wire 1 & 2nd $end\n\
+$var wire 1 ' edge $end\n\
+$upscope $end\n\
+$enddefinitions $end\n\
+#0\n\
+b0101 !\n\
+0\"\n\
+1#\n\
+0$\n\
+1%\n\
+1&\n\
+0'\n\
+#10\n\
+b1010 !\n\
+1\"\n\
+0#\n\
+1$\n\
+0%\n\
+0&\n\
+1'\n\
+";
+
+    let temp_file = tempfile::NamedTempFile::new().expect("Failed to create temp file");
+    std::fs::write(temp_file.path(), vcd_content).expect("Failed to write VCD file");
+
+    let mut waveform = wellen::simple::read(temp_file.path()).expect("Failed to read VCD file");
+
+    let mut matching_times = |condition: &str| -> Vec<usize> {
+        find_conditional_events(&mut waveform, condition, 0, 1, -1)
+            .unwrap_or_else(|e| panic!("Should evaluate {}: {}", condition, e))
+            .iter()
+            .map(|event| {
+                event["Time index ".len()..]
+                    .split(' ')
+                    .next()
+                    .and_then(|idx| idx.parse().ok())
+                    .expect("Event should start with its time index")
+            })
+            .collect()
+    };
+
+    // Generate indices in scope names, and part-selects of such signals
+    assert_eq!(matching_times("top.gen_loop[3].u_core.data == 4'h5"), [0]);
+    assert_eq!(matching_times("top.gen_loop[3].u_core.data[3]"), [1]);
+    assert_eq!(
+        matching_times("top.`gen_loop[3]`.u_core.data[1:0] == 2'b01"),
+        [0]
+    );
+
+    // Escaped identifiers end at whitespace, as in Verilog
+    assert_eq!(matching_times("top.\\a.b && top.cnt$next"), []);
+    assert_eq!(matching_times("top.\\a.b || top.cnt$next"), [0, 1]);
+    assert_eq!(matching_times("!top.`a.b`"), [0]);
+
+    // Elements of a bit-blasted vector are quoted with their index
+    assert_eq!(matching_times("top.`bits[1]` && !top.`bits[0]`"), [0]);
+    assert_eq!(
+        matching_times("{top.`bits[1]`, top.`bits[0]`} == 2'b01"),
+        [1]
+    );
+
+    // Only the first name of a path must start with a letter, and keywords are
+    // names after a `.`
+    assert_eq!(matching_times("top.2nd && !top.edge"), [0]);
+    assert_eq!(matching_times("top.`2nd` || top.`edge`"), [0, 1]);
+
+    let error = find_conditional_events(&mut waveform, "top.gen_loop[2].u_core.data", 0, 1, -1)
+        .expect_err("Should not find a signal under another generate index");
+    assert!(error.contains("not found"), "Unexpected error: {}", error);
+
+    // A relative name that is a keyword must be quoted
+    let in_top = ConditionOptions {
+        scope: Some("top".to_string()),
+        ..Default::default()
+    };
+    let events = find_conditional_events_with_options(&mut waveform, "`edge`", 0, 1, -1, &in_top)
+        .expect("Should resolve a quoted keyword relative to the scope");
+    assert_eq!(events.len(), 1, "edge is set at time 1");
+    assert!(
+        find_conditional_events_with_options(&mut waveform, "edge", 0, 1, -1, &in_top).is_err(),
+        "Should not take a bare keyword for a signal"
+    );
+}
+
+#[test]
+fn test_relative_scopes() {
+    // Two CPUs with the same signal names; cpu0 has a core that also has a `valid`
+    let vcd_content = "\
+$date 2024-01-01 $end\n\
+$version Test VCD file $end\n\
+$timescale 1ns $end\n\
+$scope module top $end\n\
+$var wire 1 ! rst $end\n\
+$scope module soc $end\n\
+$scope module cpu0 $end\n\
+$var wire 1 \" valid $end\n\
+$var wire 1 # ready $end\n\
+$scope module core $end\n\
+$var wire 1 $ valid $end\n\
+$upscope $end\n\
+$upscope $end\n\
+$scope module cpu1 $end\n\
+$var wire 1 % valid $end\n\
+$var wire 1 & ready $end\n\
+$upscope $end\n\
+$upscope $end\n\
+$upscope $end\n\
+$enddefinitions $end\n\
+#0\n\
+1!\n\
+1\"\n\
+0#\n\
+0$\n\
+0%\n\
+1&\n\
+#10\n\
+0!\n\
+1#\n\
+1$\n\
+1%\n\
+0&\n\
+";
+
+    let temp_file = tempfile::NamedTempFile::new().expect("Failed to create temp file");
+    std::fs::write(temp_file.path(), vcd_content).expect("Failed to write VCD file");
+
+    let mut waveform = wellen::simple::read(temp_file.path()).expect("Failed to read VCD file");
+
+    let in_scope = |scope: &str| ConditionOptions {
+        scope: Some(scope.to_string()),
+        ..Default::default()
+    };
+
+    // Names are resolved in the scope and reported with their full path
+    let events = find_conditional_events_with_options(
+        &mut waveform,
+        "valid && ready",
+        0,
+        1,
+        -1,
+        &in_scope("top.soc.cpu0"),
+    )
+    .expect("Should resolve names relative to the scope");
+    assert_eq!(events.len(), 1, "Only time 1 has cpu0 valid and ready");
+    assert!(
+        events[0].contains("Time index 1 (10ns)")
+            && events[0].contains("top.soc.cpu0.valid = 1'b1"),
+        "Unexpected event: {}",
+        events[0]
+    );
+
+    // Names outside the scope are found from the top, or with $root
+    for condition in ["valid && top.rst", "valid && $root.top.rst"] {
+        let events = find_conditional_events_with_options(
+            &mut waveform,
+            condition,
+            0,
+            1,
+            -1,
+            &in_scope("top.soc.cpu0"),
+        )
+        .expect("Should resolve names from the top of the hierarchy");
+        assert_eq!(events.len(), 1, "Only time 0 has reset for {}", condition);
+    }
+
+    // `with` blocks, which may be nested and relative to the enclosing scope
+    let events = find_conditional_events(
+        &mut waveform,
+        "with top.soc.cpu1 { valid } && with top.soc.cpu0 { valid }",
+        0,
+        1,
+        -1,
+    )
+    .expect("Should resolve names in with blocks");
+    assert_eq!(events.len(), 1, "Both CPUs are valid at time 1");
+    let events = find_conditional_events(
+        &mut waveform,
+        "with top.soc { with cpu1 { ready } }",
+        0,
+        1,
+        -1,
+    )
+    .expect("Should resolve nested with blocks");
+    assert_eq!(events.len(), 1, "cpu1 is ready at time 0");
+    assert!(events[0].contains("top.soc.cpu1.ready = 1'b1"));
+
+    // A name that resolves in two enclosing scopes is ambiguous
+    let error = find_conditional_events_with_options(
+        &mut waveform,
+        "with core { valid }",
+        0,
+        1,
+        -1,
+        &in_scope("top.soc.cpu0"),
+    )
+    .expect_err("Should reject an ambiguous name");
+    assert!(
+        error.contains("ambiguous")
+            && error.contains("top.soc.cpu0.core.valid and top.soc.cpu0.valid"),
+        "Unexpected error: {}",
+        error
+    );
+
+    let error = find_conditional_events_with_options(
+        &mut waveform,
+        "ready",
+        0,
+        1,
+        -1,
+        &in_scope("top.soc"),
+    )
+    .expect_err("Should reject a name that is not in the scope");
+    assert!(
+        error.contains("Signal not found: ready (relative to scope top.soc)"),
+        "Unexpected error: {}",
+        error
+    );
+    let error = find_conditional_events_with_options(
+        &mut waveform,
+        "valid",
+        0,
+        1,
+        -1,
+        &in_scope("top.nothing"),
+    )
+    .expect_err("Should reject an unknown scope");
+    assert!(
+        error.contains("Scope not found: top.nothing"),
+        "Unexpected error: {}",
+        error
+    );
+}
+
+#[test]
+fn test_enum_value_names() {
+    // GTKWave-style enum table: state_t has IDLE = 00, BUSY = 01 and DONE = 10
+    let vcd_content = "\
+$date 2024-01-01 $end\n\
+$version Test VCD file $end\n\
+$timescale 1ns $end\n\
+$scope module top $end\n\
+$scope module fsm $end\n\
+$attrbegin misc 07 state_t 3 IDLE BUSY DONE 00 01 10 1 $end\n\
+$attrbegin misc 07 \"\" 1 $end\n\
+$var wire 2 ! state $end\n\
+$var wire 1 \" IDLE $end\n\
+$upscope $end\n\
+$upscope $end\n\
+$enddefinitions $end\n\
+#0\n\
+b00 !\n\
+0\"\n\
+#10\n\
+b01 !\n\
+#20\n\
+b01 !\n\
+#30\n\
+b10 !\n\
+#40\n\
+b00 !\n\
+";
+
+    let temp_file = tempfile::NamedTempFile::new().expect("Failed to create temp file");
+    std::fs::write(temp_file.path(), vcd_content).expect("Failed to write VCD file");
+
+    let mut waveform = wellen::simple::read(temp_file.path()).expect("Failed to read VCD file");
+
+    let info = get_signal_metadata(waveform.hierarchy(), "top.fsm.state")
+        .expect("Should get signal metadata");
+    assert!(
+        info.ends_with("\nEnum: state_t (IDLE = 00, BUSY = 01, DONE = 10)"),
+        "Metadata should list enum values: {}",
+        info
+    );
+    let paths = std::sync::Arc::new(PathIndex::new(waveform.hierarchy()));
+
+    let mut matching_times = |condition: &str, options: &ConditionOptions| {
+        find_conditional_events_with_options(&mut waveform, condition, 0, 4, -1, options).map(
+            |events| {
+                events
+                    .iter()
+                    .map(|event| {
+                        event["Time index ".len()..]
+                            .split(' ')
+                            .next()
+                            .unwrap()
+                            .parse::<usize>()
+                            .unwrap()
+                    })
+                    .collect::<Vec<_>>()
+            },
+        )
+    };
+    let defaults = ConditionOptions::default();
+
+    // Enum value names on either side of a comparison
+    assert_eq!(
+        matching_times("top.fsm.state == BUSY", &defaults),
+        Ok(vec![1, 2])
+    );
+    assert_eq!(
+        matching_times("IDLE != top.fsm.state", &defaults),
+        Ok(vec![1, 2, 3])
+    );
+    assert_eq!(
+        matching_times("top.fsm.state >= DONE", &defaults),
+        Ok(vec![3])
+    );
+    assert_eq!(
+        matching_times(
+            "$past(top.fsm.state) == BUSY && top.fsm.state == DONE",
+            &defaults
+        ),
+        Ok(vec![3])
+    );
+    assert_eq!(
+        matching_times("with top.fsm { state == DONE }", &defaults),
+        Ok(vec![3])
+    );
+
+    // A name with a path is always a signal
+    assert_eq!(
+        matching_times("top.fsm.state == top.fsm.IDLE", &defaults),
+        Ok(vec![0, 4])
+    );
+
+    let error = matching_times("top.fsm.state == WAIT", &defaults)
+        .expect_err("Should reject unknown enum value");
+    assert!(
+        error.contains(
+            "Unknown value WAIT for top.fsm.state of enum state_t, expected one of: IDLE, BUSY, DONE"
+        ),
+        "Unexpected error: {}",
+        error
+    );
+
+    let in_fsm = ConditionOptions {
+        scope: Some("top.fsm".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(matching_times("state == BUSY", &in_fsm), Ok(vec![1, 2]));
+    let error = matching_times("state == IDLE", &in_fsm)
+        .expect_err("Should reject a name that is also a signal");
+    assert!(
+        error.contains("ambiguous") && error.contains("top.fsm.IDLE"),
+        "Unexpected error: {}",
+        error
+    );
+
+    // Paths are looked up the same way in an index of the waveform
+    let indexed = ConditionOptions {
+        scope: Some("top.fsm".to_string()),
+        paths: Some(paths),
+        ..Default::default()
+    };
+    assert_eq!(matching_times("state == BUSY", &indexed), Ok(vec![1, 2]));
+    assert_eq!(
+        matching_times("top.fsm.state == top.fsm.IDLE", &indexed),
+        Ok(vec![0, 4])
+    );
+    assert!(matching_times("state == IDLE", &indexed).is_err());
+}
+
+#[test]
+fn test_real_and_string_values() {
+    let vcd_content = "\
+$date 2024-01-01 $end\n\
+$version Test VCD file $end\n\
+$timescale 1ns $end\n\
+$scope module top $end\n\
+$var real 64 ! vout $end\n\
+$var real 64 \" vref $end\n\
+$var wire 8 # code $end\n\
+$var wire 16 $ word $end\n\
+$scope module tb $end\n\
+$var string 1 % phase $end\n\
+$upscope $end\n\
+$upscope $end\n\
+$enddefinitions $end\n\
+#0\n\
+r0.5 !\n\
+r1.0 \"\n\
+b00000011 #\n\
+b0100111101001011 $\n\
+sRESET %\n\
+#10\n\
+r1.25 !\n\
+sRUN %\n\
+#20\n\
+r1.21 !\n\
+b0100111001001111 $\n\
+sRUN_FAST %\n\
+#30\n\
+r0.9 !\n\
+bxxxxxxxx #\n\
+sDONE %\n\
+#40\n\
+r-0.3 !\n\
+";
+
+    let temp_file = tempfile::NamedTempFile::new().expect("Failed to create temp file");
+    std::fs::write(temp_file.path(), vcd_content).expect("Failed to write VCD file");
+
+    let mut waveform = wellen::simple::read(temp_file.path()).expect("Failed to read VCD file");
+
+    let events = find_conditional_events(&mut waveform, "top.vout > 1.2", 0, 4, -1)
+        .expect("Should compare real signal");
+    assert_eq!(events.len(), 2);
+    assert!(
+        events[0].contains("Time index 1 (10ns)") && events[0].contains("top.vout = 1.25"),
+        "Unexpected event: {}",
+        events[0]
+    );
+
+    let mut matching_times = |condition: &str| {
+        find_conditional_events(&mut waveform, condition, 0, 4, -1).map(|events| {
+            events
+                .iter()
+                .map(|event| {
+                    event["Time index ".len()..]
+                        .split(' ')
+                        .next()
+                        .unwrap()
+                        .parse::<usize>()
+                        .unwrap()
+                })
+                .collect::<Vec<_>>()
+        })
+    };
+
+    // Real comparisons and arithmetic; bit vectors are converted to reals
+    assert_eq!(matching_times("top.vout > top.vref"), Ok(vec![1, 2]));
+    assert_eq!(matching_times("top.vout < 0"), Ok(vec![4]));
+    assert_eq!(matching_times("top.vout <= 5e-1"), Ok(vec![0, 4]));
+    assert_eq!(matching_times("top.vout - top.vref >= 0.2"), Ok(vec![1, 2]));
+    assert_eq!(matching_times("top.vout * 4 > top.code"), Ok(vec![1, 2]));
+    assert_eq!(
+        matching_times("(top.tb.phase == \"DONE\" ? top.vref : top.vout) > 0.9"),
+        Ok(vec![1, 2, 3, 4])
+    );
+
+    // Tolerances
+    assert_eq!(
+        matching_times("$approx(top.vout, 1.2, 0.1)"),
+        Ok(vec![1, 2])
+    );
+    assert_eq!(matching_times("$abs(top.vout) < 0.4"), Ok(vec![4]));
+
+    // Strings
+    assert_eq!(matching_times("top.tb.phase == \"RUN\""), Ok(vec![1]));
+    assert_eq!(
+        matching_times("top.tb.phase != \"RESET\" && top.tb.phase != \"DONE\""),
+        Ok(vec![1, 2])
+    );
+    assert_eq!(
+        matching_times("$contains(top.tb.phase, \"RUN\")"),
+        Ok(vec![1, 2])
+    );
+    assert_eq!(
+        matching_times("$changed(top.tb.phase)"),
+        Ok(vec![0, 1, 2, 3])
+    );
+    assert_eq!(
+        matching_times("$past(top.tb.phase) == \"RUN\""),
+        Ok(vec![2])
+    );
+    assert_eq!(matching_times("top.tb.phase < \"RUN\""), Ok(vec![0, 3, 4]));
+
+    // A string literal compared against a bit vector is a vector of its characters
+    assert_eq!(matching_times("top.word == \"OK\""), Ok(vec![0, 1]));
+
+    let errors = [
+        (
+            "top.tb.phase == 3",
+            "can only be compared against another string",
+        ),
+        ("top.vout", "real-valued"),
+        ("top.vout ==? 1.0", "Wildcard equality"),
+        ("top.tb.phase == \"\\q\"", "Unknown escape sequence \\q"),
+        (
+            "$contains(top.word, \"O\")",
+            "$contains needs two string values",
+        ),
+    ];
+    for (condition, message) in errors {
+        let error = matching_times(condition).expect_err("Should reject condition");
+        assert!(
+            error.contains(message),
+            "Unexpected error for {}: {}",
+            condition,
+            error
+        );
+    }
+}
+
+#[test]
+fn test_time_conditions() {
+    // One timescale unit is 10ps; the clock rises at index 3
+    let vcd_content = "\
+$date 2024-01-01 $end\n\
+$version Test VCD file $end\n\
+$timescale 10ps $end\n\
+$scope module top $end\n\
+$var wire 1 0 clk $end\n\
+$var wire 1 1 err $end\n\
+$upscope $end\n\
+$enddefinitions $end\n\
+#0\n\
+00\n\
+01\n\
+#100\n\
+11\n\
+#150\n\
+01\n\
+#250\n\
+10\n\
+#400\n\
+11\n\
+";
+
+    let temp_file = tempfile::NamedTempFile::new().expect("Failed to create temp file");
+    std::fs::write(temp_file.path(), vcd_content).expect("Failed to write VCD file");
+
+    let mut waveform = wellen::simple::read(temp_file.path()).expect("Failed to read VCD file");
+
+    let mut matching_times = |condition: &str| {
+        find_conditional_events(&mut waveform, condition, 0, 4, -1).map(|events| {
+            events
+                .iter()
+                .map(|event| {
+                    event["Time index ".len()..]
+                        .split(' ')
+                        .next()
+                        .unwrap()
+                        .parse::<usize>()
+                        .unwrap()
+                })
+                .collect::<Vec<_>>()
+        })
+    };
+
+    assert_eq!(matching_times("$time > 1.2ns && top.err"), Ok(vec![4]));
+    assert_eq!(matching_times("$time == 1.5ns"), Ok(vec![2]));
+    assert_eq!(matching_times("$time == 150"), Ok(vec![2]));
+    assert_eq!(
+        matching_times("$time >= 1000ps && $time < 0.0025us"),
+        Ok(vec![1, 2])
+    );
+    assert_eq!(matching_times("$past($time) == 1ns"), Ok(vec![2]));
+
+    // Times that are not a whole number of timescale units are compared as reals
+    assert_eq!(matching_times("$time > 5ps"), Ok(vec![1, 2, 3, 4]));
+    assert_eq!(matching_times("$time == 1005ps"), Ok(vec![]));
+
+    // Clocked samples read values before the edge, but their time is that of the edge
+    assert_eq!(
+        matching_times("@(posedge top.clk) $time == 2.5ns && !top.err"),
+        Ok(vec![3])
+    );
+
+    let errors = [
+        ("$time > 1.0000001fs", "too many decimal places"),
+        ("$time > 1000000000000000000000s", "too large"),
+        ("$times > 1ns", "$time"),
+    ];
+    for (condition, message) in errors {
+        let error = matching_times(condition).expect_err("Should reject condition");
+        assert!(
+            error.contains(message),
+            "Unexpected error for {}: {}",
+            condition,
+            error
+        );
+    }
+}
+
+#[test]
+fn test_sparse_changes_match_full_evaluation() {
+    // top.noise changes at every time index; the searched signals change rarely
+    let mut vcd_content = "\
+$date 2024-01-01 $end\n\
+$version Test VCD file $end\n\
+$timescale 1ns $end\n\
+$scope module top $end\n\
+$var wire 1 0 noise $end\n\
+$var wire 1 1 a $end\n\
+$var wire 4 2 b $end\n\
+$upscope $end\n\
+$enddefinitions $end\n"
+        .to_string();
+    for time in 0..200 {
+        vcd_content.push_str(&format!("#{}\n{}0\n", time, time % 2));
+        match time {
+            0 => vcd_content.push_str("01\nb0000 2\n"),
+            20 | 51 => vcd_content.push_str("11\n"),
+            50 | 120 => vcd_content.push_str("01\n"),
+            30 => vcd_content.push_str("b0011 2\n"),
+            90 => vcd_content.push_str("b0100 2\n"),
+            150 => vcd_content.push_str("bxxxx 2\n"),
+            _ => {}
+        }
+    }
+
+    let temp_file = tempfile::NamedTempFile::new().expect("Failed to create temp file");
+    std::fs::write(temp_file.path(), vcd_content).expect("Failed to write VCD file");
+
+    let mut waveform = wellen::simple::read(temp_file.path()).expect("Failed to read VCD file");
+    let all_indices: Vec<usize> = (0..200).collect();
+
+    let conditions = [
+        "top.a",
+        "!top.a && top.b == 3",
+        "$rose(top.a)",
+        "$fell(top.a) || $changed(top.b)",
+        "$past(top.b, 5) == 3",
+        "$past($past(top.a, 3)) && !top.a",
+        "$stable(top.b) && top.a",
+        "top.b === 4'bxxxx",
+        "$time > 100ns && top.a",
+        "top.noise && top.a",
+    ];
+    for condition in conditions {
+        // Evaluating the condition as a derived signal reads it at every time index
+        let mut derived = DerivedSignals::default();
+        let signal = derived
+            .define(waveform.hierarchy(), "cond", condition)
+            .expect("Should define condition")
+            .clone();
+        let values = read_derived_signal_values(&mut waveform, &signal, &all_indices)
+            .expect("Should read condition values");
+        let holds: Vec<usize> = all_indices
+            .iter()
+            .copied()
+            .filter(|&idx| values[idx].ends_with(": 1'b1"))
+            .collect();
+
+        for (start_idx, end_idx, limit) in [(0, 199, -1), (55, 130, -1), (0, 199, 3), (52, 60, 2)] {
+            let expected: Vec<usize> = holds
+                .iter()
+                .copied()
+                .filter(|&idx| idx >= start_idx && idx <= end_idx)
+                .take(if limit < 0 {
+                    usize::MAX
+                } else {
+                    limit as usize
+                })
+                .collect();
+            let events =
+                find_conditional_events(&mut waveform, condition, start_idx, end_idx, limit)
+                    .expect("Should find events");
+            let found: Vec<usize> = events
+                .iter()
+                .map(|event| {
+                    event["Time index ".len()..]
+                        .split(' ')
+                        .next()
+                        .unwrap()
+                        .parse::<usize>()
+                        .unwrap()
+                })
+                .collect();
+            assert_eq!(
+                found, expected,
+                "Events of {} from {} to {} with limit {}",
+                condition, start_idx, end_idx, limit
+            );
+        }
+    }
+}
+
+#[test]
+fn test_compiled_conditions_match_tree_evaluation() {
+    let vcd_content = "\
+$date 2024-01-01 $end\n\
+$version Test VCD file $end\n\
+$timescale 1ns $end\n\
+$scope module top $end\n\
+$var wire 1 ! flag $end\n\
+$var wire 4 \" nib $end\n\
+$var wire 8 # a $end\n\
+$var wire 8 $ b $end\n\
+$var wire 64 % word $end\n\
+$var wire 100 & wide $end\n\
+$var real 64 ' vout $end\n\
+$var string 1 ( phase $end\n\
+$upscope $end\n\
+$enddefinitions $end\n\
+#0\n\
+0!\n\
+b0011 \"\n\
+b00000101 #\n\
+b11111011 $\n\
+b1000000000000000000000000000000000000000000000000000000000000001 %\n\
+b1 &\n\
+r0.5 '\n\
+sIDLE (\n\
+#10\n\
+1!\n\
+b1x01 \"\n\
+b10000000 #\n\
+b00000011 $\n\
+b1111111111111111111111111111111111111111111111111111111111111111 %\n\
+r1.25 '\n\
+#20\n\
+x!\n\
+b1111 \"\n\
+b0101z011 #\n\
+bxxxxxxxx $\n\
+b1x11 &\n\
+sRUN (\n\
+#30\n\
+z!\n\
+b0000 \"\n\
+b11111111 #\n\
+b10000001 $\n\
+b0 %\n\
+b11 &\n\
+r-0.3 '\n\
+#40\n\
+1!\n\
+b0111 \"\n\
+b01111111 #\n\
+b00000000 $\n\
+b11111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111 &\n\
+sDONE (\n\
+#50\n\
+0!\n\
+b1000 \"\n\
+b00010000 #\n\
+b11110000 $\n\
+b10101010 %\n\
+";
+
+    let temp_file = tempfile::NamedTempFile::new().expect("Failed to create temp file");
+    std::fs::write(temp_file.path(), vcd_content).expect("Failed to write VCD file");
+
+    let mut waveform = wellen::simple::read(temp_file.path()).expect("Failed to read VCD file");
+
+    let conditions = [
+        "top.flag",
+        "top.a + top.b",
+        "top.a + top.b == 9'd256",
+        "top.a - top.b",
+        "top.a * top.b",
+        "top.a / top.nib",
+        "top.a % top.nib",
+        "$signed(top.a) / $signed(top.nib)",
+        "$signed(top.a) % $signed(top.nib)",
+        "$signed(top.a) + $signed(top.nib)",
+        "$signed(top.a) < $signed(top.b)",
+        "$signed(top.a) >>> 2",
+        "top.a >>> top.nib",
+        "top.a << top.nib",
+        "top.a >> top.nib",
+        "top.word << 3",
+        "top.word + top.word",
+        "$signed(top.word) * 3",
+        "-top.a",
+        "-$signed(top.nib)",
+        "~top.a & top.b | top.nib ^ top.a",
+        "~top.nib",
+        "top.flag ? top.a : top.b",
+        "top.flag ? $signed(top.nib) : $signed(top.a)",
+        "top.nib[0] ? top.wide : top.word",
+        "top.a > top.b ? 1'b1 : 'x",
+        "{top.nib, top.a, top.flag}",
+        "{3{top.nib}}",
+        "{top.wide, top.word}",
+        "{20{top.a}}",
+        "top.a[3:0] == top.nib",
+        "top.a[top.nib]",
+        "top.a[$signed(top.nib)]",
+        "top.a[top.nib +: 3]",
+        "top.a[top.nib -: 3]",
+        "top.wide[70:2]",
+        "top.wide[top.nib]",
+        "top.word[63:32] != 0",
+        "top.a === top.b",
+        "top.a !== 8'bxxxxxxxx",
+        "top.a ==? 8'b0101x011",
+        "top.a !=? 8'b1xxxxxxx",
+        "top.a <= top.b",
+        "top.wide > top.word",
+        "top.a == 'hff",
+        "top.a | '1",
+        "top.wide & '0",
+        "$past(top.a)",
+        "$past(top.a, 2) + top.b",
+        "$past(top.wide, 3)",
+        "$past($signed(top.nib))",
+        "$rose(top.flag)",
+        "$fell(top.nib)",
+        "$stable(top.a) || $changed(top.wide)",
+        "$countones(top.a) + $countones(top.wide)",
+        "$onehot(top.nib) && !$onehot0(top.a)",
+        "$isunknown(top.b)",
+        "&top.a || |top.nib ^ ^top.word",
+        "top.flag && top.a || !top.b",
+        "$time + top.a",
+        "$time >= 20ns && top.flag",
+        "top.vout > 1.0",
+        "top.vout < top.a",
+        "$changed(top.vout)",
+        "$stable(top.phase) && top.flag",
+        "top.phase == \"RUN\"",
+        "$contains(top.phase, \"N\") || top.nib == 0",
+        "$approx(top.vout, 1.2, 0.1)",
+        "top.vout - top.a > 0.0",
+        "(top.vout > 0.0) ? top.a : top.b",
+        "3 + 4 * 2",
+        "8'd200 + 8'd100",
+        "$signed(4'b1000) < 0 && top.flag",
+    ];
+    for condition in conditions {
+        let tree = evaluate_condition_values(&mut waveform, condition, Evaluator::Tree)
+            .unwrap_or_else(|e| panic!("Should evaluate {}: {}", condition, e));
+        let compiled = evaluate_condition_values(&mut waveform, condition, Evaluator::Compiled)
+            .unwrap_or_else(|e| panic!("Should compile {}: {}", condition, e));
+        assert_eq!(compiled, tree, "Values of {}", condition);
+    }
+
+    // Errors the tree evaluator reports at every sample are reported when compiling
+    for condition in [
+        "top.a[0:3]",
+        "{0{top.a}}",
+        "$past(top.a, 0)",
+        "$abs(top.vout)",
+    ] {
+        assert!(
+            evaluate_condition_values(&mut waveform, condition, Evaluator::Tree).is_err(),
+            "Tree evaluation of {} should fail",
+            condition
+        );
+        assert!(
+            evaluate_condition_values(&mut waveform, condition, Evaluator::Compiled).is_err(),
+            "Compiling {} should fail",
+            condition
+        );
+    }
+}
+
+#[test]
+fn test_parallel_search_of_long_ranges() {
+    // Long enough to be searched in several chunks, with req and data changing at
+    // pseudo-random time indices
+    let mut vcd_content = String::from(
+        "$timescale 1ns $end\n\
+         $scope module top $end\n\
+         $var wire 1 ! req $end\n\
+         $var wire 8 \" data $end\n\
+         $upscope $end\n\
+         $enddefinitions $end\n",
+    );
+    let mut state: u32 = 1;
+    for time in 0..40_000 {
+        state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+        let bits = state >> 16;
+        vcd_content.push_str(&format!("#{}\n", time * 10));
+        if time == 0 || bits.is_multiple_of(7) {
+            vcd_content.push_str(&format!("{}!\n", bits >> 3 & 1));
+        }
+        if time == 0 || bits.is_multiple_of(5) {
+            vcd_content.push_str(&format!("b{:b} \"\n", bits >> 4 & 0xff));
+        }
+    }
+
+    let temp_file = tempfile::NamedTempFile::new().expect("Failed to create temp file");
+    std::fs::write(temp_file.path(), vcd_content).expect("Failed to write VCD file");
+
+    let mut waveform = wellen::simple::read(temp_file.path()).expect("Failed to read VCD file");
+
+    let conditions = [
+        "top.req && top.data > 8'd200",
+        "$past(top.req, 3) && !top.req",
+        "$time >= 100ns && top.data[0]",
+        "top.data == 8'd7",
+    ];
+    for condition in conditions {
+        // Every time index evaluated in order is the reference
+        let values = evaluate_condition_values(&mut waveform, condition, Evaluator::Tree)
+            .expect("Should evaluate condition");
+        let holds: Vec<usize> = (0..values.len())
+            .filter(|&idx| values[idx] == "1'b1")
+            .collect();
+
+        for (start_idx, end_idx, limit) in [
+            (0, 39_999, -1),
+            (16_000, 35_000, -1),
+            (0, 39_999, 5),
+            (20_000, 39_999, 3_000),
+            (39_990, 50_000, -1),
+        ] {
+            let expected: Vec<usize> = holds
+                .iter()
+                .copied()
+                .filter(|&idx| idx >= start_idx && idx <= end_idx)
+                .take(if limit < 0 {
+                    usize::MAX
+                } else {
+                    limit as usize
+                })
+                .collect();
+            let events =
+                find_conditional_events(&mut waveform, condition, start_idx, end_idx, limit)
+                    .expect("Should find events");
+            let found: Vec<usize> = events
+                .iter()
+                .map(|event| {
+                    event["Time index ".len()..]
+                        .split(' ')
+                        .next()
+                        .unwrap()
+                        .parse::<usize>()
+                        .unwrap()
+                })
+                .collect();
+            assert_eq!(
+                found, expected,
+                "Events of {} from {} to {} with limit {}",
+                condition, start_idx, end_idx, limit
+            );
+        }
+    }
+}
@@ -0,0 +1,278 @@
+//! Derived signal tests
+
+mod fixtures;
+
+use fixtures::read_waveform;
+use std::sync::Arc;
+use waveform_mcp::{
+    find_conditional_events_with_options, find_derived_signal_events, find_sequence_matches,
+    get_derived_signal_metadata, read_derived_signal_values, ConditionOptions, DerivedSignals,
+    PathIndex,
+};
+
+// A write address handshake fires at time indices 2 and 4; data is partly X at 3
+const AXI_VCD: &str = "\
+$date 2024-01-01 $end\n\
+$version Test VCD file $end\n\
+$timescale 1ns $end\n\
+$scope module top $end\n\
+$var wire 1 ! awvalid $end\n\
+$var wire 1 \" awready $end\n\
+$var wire 8 # data $end\n\
+$upscope $end\n\
+$enddefinitions $end\n\
+#0\n\
+0!\n\
+1\"\n\
+b00010000 #\n\
+#10\n\
+1!\n\
+0\"\n\
+b00100000 #\n\
+#20\n\
+1\"\n\
+b00110000 #\n\
+#30\n\
+0!\n\
+b0011xxxx #\n\
+#40\n\
+1!\n\
+b01000000 #\n\
+";
+
+#[test]
+fn test_derived_signals_in_conditions() {
+    let mut waveform = read_waveform(AXI_VCD);
+    let mut derived = DerivedSignals::default();
+
+    let aw_fire = derived
+        .define(
+            waveform.hierarchy(),
+            "aw_fire",
+            "top.awvalid && top.awready",
+        )
+        .expect("Should define derived signal");
+    assert_eq!(aw_fire.width, 1);
+
+    let options = ConditionOptions {
+        derived: derived.clone(),
+        ..Default::default()
+    };
+    let events = find_conditional_events_with_options(&mut waveform, "aw_fire", 0, 4, -1, &options)
+        .expect("Should use derived signal in condition");
+    assert_eq!(events.len(), 2);
+    assert!(
+        events[0].starts_with("Time index 2 (20ns): ") && events[0].contains("top.awvalid = 1'b1"),
+        "Events should show the underlying signals: {}",
+        events[0]
+    );
+
+    let matches = find_sequence_matches(
+        &mut waveform,
+        "aw_fire |=> !aw_fire",
+        0,
+        4,
+        -1,
+        false,
+        &options,
+    )
+    .expect("Should use derived signal in property");
+    assert_eq!(
+        matches,
+        vec!["Pass from time index 2 (20ns) to time index 3 (30ns)"]
+    );
+
+    // Derived signals may use earlier ones, which are expanded when defined
+    derived
+        .define(
+            waveform.hierarchy(),
+            "fire_data",
+            "aw_fire ? top.data : 8'h00",
+        )
+        .expect("Should define signal from derived signal");
+    derived
+        .define(waveform.hierarchy(), "aw_fire", "top.awvalid")
+        .expect("Should redefine derived signal");
+    let values =
+        read_derived_signal_values(&mut waveform, derived.get("fire_data").unwrap(), &[1, 2])
+            .expect("Should read derived signal");
+    assert_eq!(
+        values,
+        vec!["Time index 1 (10ns): 8'h00", "Time index 2 (20ns): 8'h30"]
+    );
+
+    // A name that also resolves relative to the scope is ambiguous
+    derived
+        .define(waveform.hierarchy(), "awvalid", "top.awready")
+        .expect("Should define derived signal");
+    let options = ConditionOptions {
+        scope: Some("top".to_string()),
+        derived: derived.clone(),
+        ..Default::default()
+    };
+    let error = find_conditional_events_with_options(&mut waveform, "awvalid", 0, 4, -1, &options)
+        .expect_err("Should reject ambiguous name");
+    assert!(
+        error.contains("ambiguous") && error.contains("top.awvalid"),
+        "Unexpected error: {}",
+        error
+    );
+}
+
+#[test]
+fn test_read_derived_signals() {
+    let mut waveform = read_waveform(AXI_VCD);
+    let mut derived = DerivedSignals::default();
+    let hierarchy = waveform.hierarchy();
+
+    let data_hi = derived
+        .define(hierarchy, "data_hi", "top.data[7:4]")
+        .expect("Should define derived signal")
+        .clone();
+    assert_eq!(data_hi.width, 4);
+    assert_eq!(
+        get_derived_signal_metadata(&data_hi),
+        "Signal: data_hi\nType: Derived (top.data[7:4])\nWidth: 4 bits\nIndex: [3:0]"
+    );
+    let values = read_derived_signal_values(&mut waveform, &data_hi, &[0, 3, 9])
+        .expect("Should read derived signal");
+    assert_eq!(
+        values,
+        vec![
+            "Time index 0 (0ns): 4'b0001",
+            "Time index 3 (30ns): 4'b0011",
+            "Time index 9 out of range (max: 4)",
+        ]
+    );
+
+    // Values with X bits are shown in binary
+    let data_lo = derived
+        .define(waveform.hierarchy(), "data_lo", "{4'b0, top.data[3:0]}")
+        .expect("Should define derived signal")
+        .clone();
+    let values = read_derived_signal_values(&mut waveform, &data_lo, &[2, 3])
+        .expect("Should read derived signal");
+    assert_eq!(
+        values,
+        vec![
+            "Time index 2 (20ns): 8'h00",
+            "Time index 3 (30ns): 8'b0000xxxx"
+        ]
+    );
+}
+
+#[test]
+fn test_derived_signal_events() {
+    let mut waveform = read_waveform(AXI_VCD);
+    let mut derived = DerivedSignals::default();
+    let aw_fire = derived
+        .define(
+            waveform.hierarchy(),
+            "aw_fire",
+            "top.awvalid && top.awready",
+        )
+        .expect("Should define derived signal")
+        .clone();
+
+    // Like a waveform signal: the initial value, then every change
+    let events = find_derived_signal_events(&mut waveform, &aw_fire, 0, 4, -1)
+        .expect("Should find derived signal events");
+    assert_eq!(
+        events,
+        vec![
+            "Time index 0 (0ns): 1'b0",
+            "Time index 2 (20ns): 1'b1",
+            "Time index 3 (30ns): 1'b0",
+            "Time index 4 (40ns): 1'b1",
+        ]
+    );
+
+    let events = find_derived_signal_events(&mut waveform, &aw_fire, 1, 3, -1)
+        .expect("Should find derived signal events");
+    assert_eq!(events.len(), 2, "Time index 1 is not a change");
+    let events = find_derived_signal_events(&mut waveform, &aw_fire, 0, 4, 1)
+        .expect("Should find derived signal events");
+    assert_eq!(events.len(), 1, "Should respect limit");
+}
+
+#[test]
+fn test_derived_signals_with_path_index() {
+    let mut waveform = read_waveform(AXI_VCD);
+    let paths = Arc::new(PathIndex::new(waveform.hierarchy()));
+    let mut derived = DerivedSignals::with_paths(paths);
+
+    let data_hi = derived
+        .define(waveform.hierarchy(), "data_hi", "with top { data[7:4] }")
+        .expect("Should define derived signal")
+        .clone();
+    let aw_fire = derived
+        .define(
+            waveform.hierarchy(),
+            "aw_fire",
+            "top.awvalid && top.awready && data_hi != 0",
+        )
+        .expect("Should define derived signal")
+        .clone();
+    let values = read_derived_signal_values(&mut waveform, &data_hi, &[0, 3])
+        .expect("Should read derived signal");
+    assert_eq!(
+        values,
+        vec![
+            "Time index 0 (0ns): 4'b0001",
+            "Time index 3 (30ns): 4'b0011"
+        ]
+    );
+    let events = find_derived_signal_events(&mut waveform, &aw_fire, 0, 4, -1)
+        .expect("Should find derived signal events");
+    assert_eq!(events.len(), 4, "Unexpected events: {:?}", events);
+
+    let error = derived
+        .define(waveform.hierarchy(), "fire", "top.nothing")
+        .expect_err("Should reject unknown signal");
+    assert!(
+        error.contains("Signal not found: top.nothing"),
+        "Unexpected error: {}",
+        error
+    );
+}
+
+#[test]
+fn test_invalid_derived_signals() {
+    let waveform = read_waveform(AXI_VCD);
+    let hierarchy = waveform.hierarchy();
+    let mut derived = DerivedSignals::default();
+
+    for name in ["top.fire", "with", "1st", ""] {
+        let error = derived
+            .define(hierarchy, name, "top.awvalid")
+            .expect_err("Should reject invalid name");
+        assert!(
+            error.contains("Invalid derived signal name"),
+            "Unexpected error for '{}': {}",
+            name,
+            error
+        );
+    }
+
+    let error = derived
+        .define(hierarchy, "fire", "@(posedge top.awvalid) top.awready")
+        .expect_err("Should reject sampling clock");
+    assert!(
+        error.contains("sampling clock"),
+        "Unexpected error: {}",
+        error
+    );
+
+    let error = derived
+        .define(hierarchy, "fire", "top.nothing")
+        .expect_err("Should reject unknown signal");
+    assert!(
+        error.contains("Signal not found: top.nothing"),
+        "Unexpected error: {}",
+        error
+    );
+    assert!(
+        derived.names().is_empty(),
+        "Failed definitions are not kept"
+    );
+}
@@ -0,0 +1,58 @@
+//! Waveforms and helpers shared by the test files
+
+// Each test file uses only some of the fixtures
+#![allow(dead_code)]
+
+// One change per time index: req is raised at 1, 4 and 10, ack only at 3
+pub const REQ_ACK_VCD: &str = "\
+$date 2024-01-01 $end\n\
+$version Test VCD file $end\n\
+$timescale 1ns $end\n\
+$scope module top $end\n\
+$var wire 1 0 tick $end\n\
+$var wire 1 1 req $end\n\
+$var wire 1 2 ack $end\n\
+$upscope $end\n\
+$enddefinitions $end\n\
+#0\n\
+00\n\
+01\n\
+02\n\
+#10\n\
+10\n\
+11\n\
+#20\n\
+00\n\
+01\n\
+#30\n\
+10\n\
+12\n\
+#40\n\
+00\n\
+11\n\
+02\n\
+#50\n\
+10\n\
+01\n\
+#60\n\
+00\n\
+#70\n\
+10\n\
+#80\n\
+00\n\
+#90\n\
+10\n\
+#100\n\
+00\n\
+11\n\
+#110\n\
+10\n\
+01\n\
+";
+
+/// Read a waveform from VCD text.
+pub fn read_waveform(vcd: &str) -> wellen::simple::Waveform {
+    let temp_file = tempfile::NamedTempFile::new().expect("Failed to create temp file");
+    std::fs::write(temp_file.path(), vcd).expect("Failed to write VCD file");
+    wellen::simple::read(temp_file.path()).expect("Failed to read VCD file")
+}
@@ -5,6 +5,8 @@ use tempfile::NamedTempFile;
 use waveform_mcp::find_scope_by_path;
 use waveform_mcp::find_signal_by_path;
 use waveform_mcp::list_signals;
+use waveform_mcp::PathIndex;
+use waveform_mcp::{get_signal_metadata, get_signal_metadata_with_index, list_signals_with_index};
 
 #[test]
 fn test_signal_full_name() {
@@ -255,3 +257,166 @@ $enddefinitions $end\n\
         "Should return all signals with -1 limit"
     );
 }
+
+#[test]
+fn test_find_by_path_with_special_names() {
+    // A generate block, an escaped identifier, a name with `$` and a bit-blasted vector
+    let vcd_content = "\
+$date 2024-01-01 $end\n\
+$version Test VCD file $end\n\
+$timescale 1ns $end\n\
+$scope module top $end\n\
+$scope module gen_loop[3] $end\n\
+$scope module u_core $end\n\
+$var wire 1 ! valid $end\n\
+$upscope $end\n\
+$upscope $end\n\
+$var wire 1 \" \\a.b $end\n\
+$var wire 1 # cnt$next $end\n\
+$var wire 1 $ bits[0] $end\n\
+$var wire 1 % bits[1] $end\n\
+$upscope $end\n\
+$enddefinitions $end\n\
+#0\n\
+0!\n\
+0\"\n\
+0#\n\
+0$\n\
+0%";
+
+    let mut temp_file = NamedTempFile::new().expect("Failed to create temp file");
+    write!(temp_file, "{}", vcd_content).expect("Failed to write VCD content");
+    temp_file.flush().expect("Failed to flush");
+
+    let waveform = wellen::simple::read(temp_file.path()).expect("Failed to read VCD file");
+    let hierarchy = waveform.hierarchy();
+
+    let valid = find_signal_by_path(hierarchy, "top.gen_loop[3].u_core.valid")
+        .expect("Should find signal under a generate block");
+    assert_eq!(
+        find_signal_by_path(hierarchy, "top.`gen_loop[3]`.u_core.valid"),
+        Some(valid),
+        "Quoted names should match"
+    );
+    assert!(find_scope_by_path(hierarchy, "top.gen_loop[3].u_core").is_some());
+    assert!(find_signal_by_path(hierarchy, "top.gen_loop[2].u_core.valid").is_none());
+
+    // Escaped identifiers end at whitespace; the backslash is optional when quoted
+    let escaped =
+        find_signal_by_path(hierarchy, "top.\\a.b").expect("Should find escaped identifier");
+    assert_eq!(find_signal_by_path(hierarchy, "top.\\a.b "), Some(escaped));
+    assert_eq!(find_signal_by_path(hierarchy, "top.`a.b`"), Some(escaped));
+
+    assert!(find_signal_by_path(hierarchy, "top.cnt$next").is_some());
+
+    // Elements of a bit-blasted vector are referenced with their index
+    let bit0 = find_signal_by_path(hierarchy, "top.`bits[0]`").expect("Should find element 0");
+    let bit1 = find_signal_by_path(hierarchy, "top.`bits[1]`").expect("Should find element 1");
+    assert_ne!(bit0, bit1, "Elements should be different signals");
+}
+
+#[test]
+fn test_path_index_matches_search() {
+    // Special names, a scope and a signal with the same name, names that differ only
+    // by an escape, and a signal at the top of the hierarchy
+    let vcd_content = "\
+$date 2024-01-01 $end\n\
+$version Test VCD file $end\n\
+$timescale 1ns $end\n\
+$var wire 1 & rst $end\n\
+$scope module top $end\n\
+$scope module gen_loop[3] $end\n\
+$scope module u_core $end\n\
+$var wire 1 ! valid $end\n\
+$upscope $end\n\
+$upscope $end\n\
+$scope module \\dup $end\n\
+$var wire 1 ' x $end\n\
+$upscope $end\n\
+$scope module dup $end\n\
+$var wire 1 ( x $end\n\
+$var wire 1 ) y $end\n\
+$upscope $end\n\
+$var wire 1 \" \\a.b $end\n\
+$var wire 1 # cnt$next $end\n\
+$var wire 1 $ bits[0] $end\n\
+$var wire 1 % bits[1] $end\n\
+$var wire 4 * bus [3:0] $end\n\
+$var wire 1 + dup $end\n\
+$upscope $end\n\
+$enddefinitions $end\n\
+#0\n\
+0!\n";
+
+    let mut temp_file = NamedTempFile::new().expect("Failed to create temp file");
+    write!(temp_file, "{}", vcd_content).expect("Failed to write VCD content");
+    temp_file.flush().expect("Failed to flush");
+
+    let waveform = wellen::simple::read(temp_file.path()).expect("Failed to read VCD file");
+    let hierarchy = waveform.hierarchy();
+    let paths = PathIndex::new(hierarchy);
+
+    let mut queries: Vec<String> = [
+        "rst",
+        "top",
+        "top.gen_loop[3]",
+        "top.gen_loop[3].u_core",
+        "top.`gen_loop[3]`.u_core.valid",
+        "top.gen_loop[2].u_core.valid",
+        "top.dup",
+        "top.\\dup",
+        "top.dup.x",
+        "top.\\dup.x",
+        "top.dup.y",
+        "top.\\dup.y",
+        "top.\\a.b",
+        "top.\\a.b ",
+        "top.`a.b`",
+        "top.a.b",
+        "top.cnt$next",
+        "top.`bits[0]`",
+        "top.bits[1]",
+        "top.bus",
+        "top.bus[3:0]",
+        "top.missing",
+        "missing.rst",
+        "",
+    ]
+    .iter()
+    .map(|query| query.to_string())
+    .collect();
+    // Every full name, as reported by `list_signals`
+    queries.extend(list_signals(hierarchy, None, None, true, None));
+    queries.extend(
+        hierarchy
+            .iter_scopes()
+            .map(|scope| scope.full_name(hierarchy)),
+    );
+
+    for query in &queries {
+        assert_eq!(
+            paths.find_signal(hierarchy, query),
+            find_signal_by_path(hierarchy, query),
+            "Signal {}",
+            query
+        );
+        assert_eq!(
+            paths.find_scope(query),
+            find_scope_by_path(hierarchy, query),
+            "Scope {}",
+            query
+        );
+        assert_eq!(
+            list_signals_with_index(hierarchy, &paths, None, Some(query), false, None),
+            list_signals(hierarchy, None, Some(query), false, None),
+            "Signals in {}",
+            query
+        );
+        assert_eq!(
+            get_signal_metadata_with_index(hierarchy, &paths, query),
+            get_signal_metadata(hierarchy, query),
+            "Metadata of {}",
+            query
+        );
+    }
+}
@@ -0,0 +1,350 @@
+//! Sequence and property tests
+
+mod fixtures;
+
+use fixtures::{read_waveform, REQ_ACK_VCD};
+use waveform_mcp::find_sequence_matches;
+use waveform_mcp::{ClockEdge, ConditionOptions, SamplingClock};
+
+// Clock with rising edges at time indices 1, 3, 5 and 7
+const CLOCKED_REQ_ACK_VCD: &str = "\
+$date 2024-01-01 $end\n\
+$version Test VCD file $end\n\
+$timescale 1ns $end\n\
+$scope module top $end\n\
+$var wire 1 0 clk $end\n\
+$var wire 1 1 req $end\n\
+$var wire 1 2 ack $end\n\
+$upscope $end\n\
+$enddefinitions $end\n\
+#0\n\
+00\n\
+11\n\
+02\n\
+#5\n\
+10\n\
+#10\n\
+00\n\
+01\n\
+12\n\
+#15\n\
+10\n\
+#20\n\
+00\n\
+11\n\
+02\n\
+#25\n\
+10\n\
+#30\n\
+00\n\
+01\n\
+#35\n\
+10\n\
+#40\n\
+00\n\
+";
+
+#[test]
+fn test_implication_with_delay_range() {
+    let mut waveform = read_waveform(REQ_ACK_VCD);
+    let options = ConditionOptions::default();
+
+    // The request at 1 is acknowledged at 3, the request at 4 is never acknowledged,
+    // and the window of the request at 10 runs past the end of the waveform
+    let results = find_sequence_matches(
+        &mut waveform,
+        "top.req |-> ##[1:4] top.ack",
+        0,
+        11,
+        -1,
+        false,
+        &options,
+    )
+    .expect("Should check implication");
+    assert_eq!(
+        results,
+        vec![
+            "Pass from time index 1 (10ns) to time index 3 (30ns)",
+            "Fail from time index 4 (40ns) to time index 8 (80ns)",
+        ]
+    );
+
+    let failures = find_sequence_matches(
+        &mut waveform,
+        "top.req |-> ##[1:4] top.ack",
+        0,
+        11,
+        -1,
+        true,
+        &options,
+    )
+    .expect("Should check implication");
+    assert_eq!(
+        failures,
+        vec!["Fail from time index 4 (40ns) to time index 8 (80ns)"]
+    );
+
+    // Attempts are only started inside the time range
+    let results = find_sequence_matches(
+        &mut waveform,
+        "top.req |-> ##[1:4] top.ack",
+        2,
+        11,
+        -1,
+        false,
+        &options,
+    )
+    .expect("Should check implication");
+    assert_eq!(results.len(), 1, "Only the request at 4 is in range");
+
+    let results = find_sequence_matches(
+        &mut waveform,
+        "top.req |-> ##[1:4] top.ack",
+        0,
+        11,
+        1,
+        false,
+        &options,
+    )
+    .expect("Should check implication");
+    assert_eq!(results.len(), 1, "Should respect limit");
+}
+
+#[test]
+fn test_overlapping_and_non_overlapping_implication() {
+    let mut waveform = read_waveform(REQ_ACK_VCD);
+    let options = ConditionOptions::default();
+
+    // |-> checks ack in the same cycle as req
+    let results = find_sequence_matches(
+        &mut waveform,
+        "top.req |-> top.ack",
+        0,
+        11,
+        -1,
+        false,
+        &options,
+    )
+    .expect("Should check overlapping implication");
+    assert_eq!(results.len(), 3);
+    assert_eq!(
+        results[0],
+        "Fail from time index 1 (10ns) to time index 1 (10ns)"
+    );
+
+    // |=> checks ack one cycle later
+    let results = find_sequence_matches(
+        &mut waveform,
+        "top.req |=> top.ack",
+        0,
+        11,
+        -1,
+        false,
+        &options,
+    )
+    .expect("Should check non-overlapping implication");
+    assert_eq!(results.len(), 3);
+    assert_eq!(
+        results[0],
+        "Fail from time index 1 (10ns) to time index 2 (20ns)"
+    );
+
+    // |=> is the same as |-> with a one cycle delay
+    let delayed = find_sequence_matches(
+        &mut waveform,
+        "top.req |-> ##1 top.ack",
+        0,
+        11,
+        -1,
+        false,
+        &options,
+    )
+    .expect("Should check delayed implication");
+    assert_eq!(results, delayed);
+
+    // A sequence in the antecedent: the consequent starts where the antecedent ends, so
+    // the request at 10 needs an ack at 12, past the end of the waveform
+    let results = find_sequence_matches(
+        &mut waveform,
+        "top.req ##1 !top.req |-> ##1 top.ack",
+        0,
+        11,
+        -1,
+        false,
+        &options,
+    )
+    .expect("Should check implication with sequence antecedent");
+    assert_eq!(
+        results,
+        vec![
+            "Pass from time index 1 (10ns) to time index 3 (30ns)",
+            "Fail from time index 4 (40ns) to time index 6 (60ns)",
+        ]
+    );
+}
+
+#[test]
+fn test_sequence_matches() {
+    let mut waveform = read_waveform(REQ_ACK_VCD);
+    let options = ConditionOptions::default();
+
+    let results = find_sequence_matches(
+        &mut waveform,
+        "top.req ##2 top.ack",
+        0,
+        11,
+        -1,
+        false,
+        &options,
+    )
+    .expect("Should match sequence");
+    assert_eq!(
+        results,
+        vec!["Match from time index 1 (10ns) to time index 3 (30ns)"]
+    );
+
+    // A range reports the earliest match
+    let results = find_sequence_matches(
+        &mut waveform,
+        "top.req ##[1:6] top.ack || top.req",
+        0,
+        11,
+        -1,
+        false,
+        &options,
+    )
+    .expect("Should match sequence with range");
+    assert_eq!(results.len(), 2);
+    assert_eq!(
+        results[0],
+        "Match from time index 1 (10ns) to time index 3 (30ns)"
+    );
+    assert_eq!(
+        results[1],
+        "Match from time index 4 (40ns) to time index 10 (100ns)"
+    );
+
+    // A leading delay is measured from the start of the attempt
+    let results = find_sequence_matches(&mut waveform, "##2 top.ack", 0, 11, -1, false, &options)
+        .expect("Should match sequence with leading delay");
+    assert_eq!(
+        results,
+        vec!["Match from time index 1 (10ns) to time index 3 (30ns)"]
+    );
+}
+
+#[test]
+fn test_clock_sampled_sequences() {
+    let mut waveform = read_waveform(CLOCKED_REQ_ACK_VCD);
+
+    // Sampled just before each rising edge: req is seen at the first and third
+    // edges, ack only at the second
+    let results = find_sequence_matches(
+        &mut waveform,
+        "@(posedge top.clk) top.req |-> ##1 top.ack",
+        0,
+        8,
+        -1,
+        false,
+        &ConditionOptions::default(),
+    )
+    .expect("Should check clock-sampled implication");
+    assert_eq!(
+        results,
+        vec![
+            "Pass from time index 1 (5ns) to time index 3 (15ns)",
+            "Fail from time index 5 (25ns) to time index 7 (35ns)",
+        ]
+    );
+
+    let options = ConditionOptions {
+        clock: Some(SamplingClock {
+            signal: "top.clk".to_string(),
+            edge: ClockEdge::Posedge,
+        }),
+        ..Default::default()
+    };
+    let clocked = find_sequence_matches(
+        &mut waveform,
+        "top.req |-> ##1 top.ack",
+        0,
+        8,
+        -1,
+        false,
+        &options,
+    )
+    .expect("Should check implication with clock option");
+    assert_eq!(results, clocked);
+}
+
+#[test]
+fn test_invalid_sequences() {
+    let mut waveform = read_waveform(REQ_ACK_VCD);
+    let options = ConditionOptions::default();
+
+    let error = find_sequence_matches(
+        &mut waveform,
+        "top.req |-> ##[4:1] top.ack",
+        0,
+        11,
+        -1,
+        false,
+        &options,
+    )
+    .expect_err("Should reject inverted delay range");
+    assert!(error.contains("##[4:1]"), "Unexpected error: {}", error);
+    assert!(
+        error.contains("at column 13\n  top.req |-> ##[4:1] top.ack\n              ^^^^^^^"),
+        "Error should point at the delay range: {}",
+        error
+    );
+
+    assert!(
+        find_sequence_matches(&mut waveform, "top.req ##", 0, 11, -1, false, &options).is_err(),
+        "Should reject a delay without an expression"
+    );
+    assert!(
+        find_sequence_matches(
+            &mut waveform,
+            "top.req |-> top.ack |-> top.req",
+            0,
+            11,
+            -1,
+            false,
+            &options
+        )
+        .is_err(),
+        "Should reject chained implications"
+    );
+}
+
+#[test]
+fn test_relative_scope_in_properties() {
+    let mut waveform = read_waveform(REQ_ACK_VCD);
+
+    let absolute = find_sequence_matches(
+        &mut waveform,
+        "top.req |-> ##[1:4] top.ack",
+        0,
+        11,
+        -1,
+        false,
+        &ConditionOptions::default(),
+    )
+    .expect("Should check implication");
+    let options = ConditionOptions {
+        scope: Some("top".to_string()),
+        ..Default::default()
+    };
+    let relative = find_sequence_matches(
+        &mut waveform,
+        "req |-> ##[1:4] ack",
+        0,
+        11,
+        -1,
+        false,
+        &options,
+    )
+    .expect("Should check implication with relative names");
+    assert_eq!(absolute, relative);
+}
@@ -176,3 +176,62 @@ $enddefinitions $end\n\
         "Should find events in specified range"
     );
 }
+
+#[test]
+fn test_find_signal_events_in_long_range() {
+    // A counter that changes at every other time index, long enough to be formatted by
+    // several tasks
+    let mut vcd_content = String::from(
+        "$timescale 1ns $end\n\
+         $scope module top $end\n\
+         $var wire 16 ! count $end\n\
+         $upscope $end\n\
+         $enddefinitions $end\n",
+    );
+    for time in 0..20_000u32 {
+        vcd_content.push_str(&format!("#{}\n", time * 10));
+        if time.is_multiple_of(2) {
+            vcd_content.push_str(&format!("b{:b} !\n", time / 2));
+        }
+    }
+
+    let mut temp_file = NamedTempFile::new().expect("Failed to create temp file");
+    write!(temp_file, "{}", vcd_content).expect("Failed to write VCD content");
+    temp_file.flush().expect("Failed to flush");
+
+    let mut waveform = wellen::simple::read(temp_file.path()).expect("Failed to read VCD file");
+    let signal_ref = find_signal_by_path(waveform.hierarchy(), "top.count")
+        .expect("Should find 'top.count' signal");
+    waveform.load_signals(&[signal_ref]);
+
+    let events = find_signal_events(&waveform, signal_ref, 0, 19_999, -1)
+        .expect("Should find signal events");
+    assert_eq!(events.len(), 10_000, "Should find every change");
+    for (change, event) in events.iter().enumerate() {
+        let time_idx = change * 2;
+        assert_eq!(
+            *event,
+            format!(
+                "Time index {} ({}ns): 16'h{:04x}",
+                time_idx,
+                time_idx * 10,
+                change
+            ),
+            "Events should be in time order"
+        );
+    }
+
+    let events = find_signal_events(&waveform, signal_ref, 5_001, 19_999, 3_000)
+        .expect("Should find limited events");
+    assert_eq!(events.len(), 3_000, "Should respect limit");
+    assert!(events[0].starts_with("Time index 5002 "), "{}", events[0]);
+    assert!(
+        events[2_999].starts_with("Time index 11000 "),
+        "{}",
+        events[2_999]
+    );
+
+    let events = find_signal_events(&waveform, signal_ref, 19_000, 18_000, -1)
+        .expect("Should accept an empty range");
+    assert!(events.is_empty(), "Should find no events in an empty range");
+}
@@ -0,0 +1,216 @@
+//! Waveform store tests
+
+use std::fmt::Write;
+use std::time::Duration;
+
+use tempfile::NamedTempFile;
+use waveform_mcp::{
+    find_conditional_events_with_options, list_signals_with_index, ConditionOptions, WaveformBody,
+    WaveformStore,
+};
+
+const HEADER: &str = "\
+$timescale 1ns $end\n\
+$scope module top $end\n\
+$var wire 1 ! req $end\n\
+$var wire 8 \" data $end\n\
+$upscope $end\n\
+$enddefinitions $end\n";
+
+/// A waveform with a body long enough that it is usually still loading after `open`.
+fn long_vcd(num_time_steps: usize) -> String {
+    let mut vcd = HEADER.to_string();
+    for time in 0..num_time_steps {
+        writeln!(vcd, "#{}\n{}!\nb{:b} \"", time * 10, time % 2, time % 256).unwrap();
+    }
+    vcd
+}
+
+fn write_vcd(vcd: &str) -> NamedTempFile {
+    let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+    std::fs::write(temp_file.path(), vcd).expect("Failed to write VCD file");
+    temp_file
+}
+
+#[tokio::test]
+async fn test_hierarchy_while_loading() {
+    let file = write_vcd(&long_vcd(50_000));
+    let store = WaveformStore::default();
+    store
+        .open(file.path(), "long")
+        .await
+        .expect("Should read header");
+
+    // The hierarchy is there right away, whether or not the body is loaded yet
+    let entry = store.get("long").await.expect("Should find waveform");
+    {
+        let mut entry = entry.write().await;
+        let entry = &mut *entry;
+        let hierarchy = entry.body.hierarchy().expect("Should have hierarchy");
+        assert_eq!(
+            list_signals_with_index(hierarchy, &entry.paths, None, None, true, None),
+            vec!["top.req", "top.data"]
+        );
+        entry
+            .derived
+            .define(hierarchy, "fire", "top.req && top.data > 8'd250")
+            .expect("Should define derived signal while loading");
+    }
+
+    // Signal values can be read once the body is loaded
+    let entry = store
+        .get_loaded("long")
+        .await
+        .expect("Should find waveform");
+    let mut entry = entry.write().await;
+    let entry = &mut *entry;
+    let options = ConditionOptions {
+        derived: entry.derived.clone(),
+        paths: Some(entry.paths.clone()),
+        ..Default::default()
+    };
+    let waveform = entry.body.waveform().expect("Should be loaded");
+    assert_eq!(waveform.time_table().len(), 50_000);
+    let events = find_conditional_events_with_options(waveform, "fire", 0, 49_999, 2, &options)
+        .expect("Should evaluate derived signal");
+    assert_eq!(events.len(), 2);
+    assert!(
+        events[0].starts_with("Time index 251 (2510ns)"),
+        "Unexpected event: {}",
+        events[0]
+    );
+}
+
+#[tokio::test]
+async fn test_loading_body() {
+    let file = write_vcd(&long_vcd(10));
+    let header = wellen::viewers::read_header_from_file(file.path(), &Default::default())
+        .expect("Should read header");
+    let (loader, loaded) = tokio::sync::watch::channel(());
+    let mut body = WaveformBody::Loading {
+        hierarchy: header.hierarchy,
+        loaded,
+    };
+    assert!(body.hierarchy().is_ok(), "Hierarchy is known while loading");
+    assert_eq!(
+        body.waveform()
+            .expect_err("Values are not known while loading"),
+        "Waveform is still loading"
+    );
+    drop(loader);
+}
+
+#[tokio::test]
+async fn test_failed_body() {
+    let store = WaveformStore::default();
+    for (alias, body) in [
+        ("bad_token", "#0\n1!\nfoo bar baz\n"),
+        // wellen panics on this value, which fails the load as well
+        ("bad_value", "#0\n1!\n#10\nb2 !\n"),
+    ] {
+        let file = write_vcd(&format!("{}{}", HEADER, body));
+        store
+            .open(file.path(), alias)
+            .await
+            .expect("Should read header");
+
+        let entry = store.get_loaded(alias).await.expect("Should find waveform");
+        let mut entry = entry.write().await;
+        let error = entry.body.waveform().expect_err("Body should fail to load");
+        assert!(
+            error.starts_with("Failed to read waveform: "),
+            "Unexpected error for {}: {}",
+            alias,
+            error
+        );
+        assert!(entry.body.hierarchy().is_err());
+    }
+
+    let file = write_vcd("not a waveform\n");
+    assert!(store.open(file.path(), "text").await.is_err());
+    assert!(store
+        .open(std::path::Path::new("/nonexistent/file.vcd"), "missing")
+        .await
+        .is_err());
+    assert!(store.get("text").await.is_none());
+    assert!(store.get("missing").await.is_none());
+}
+
+#[tokio::test]
+async fn test_reopen_while_loading() {
+    let long = write_vcd(&long_vcd(50_000));
+    let short = write_vcd(&format!(
+        "{}#0\n1!\nb1 \"\n",
+        HEADER.replace("req", "valid")
+    ));
+    let store = WaveformStore::default();
+    store
+        .open(long.path(), "wave")
+        .await
+        .expect("Should read header");
+
+    // A tool waiting for the first waveform waits for the one that replaces it
+    let waiter = {
+        let store = store.clone();
+        tokio::spawn(async move {
+            let entry = store
+                .get_loaded("wave")
+                .await
+                .expect("Should find waveform");
+            let mut entry = entry.write().await;
+            entry
+                .body
+                .waveform()
+                .map(|waveform| waveform.time_table().len())
+        })
+    };
+    tokio::task::yield_now().await;
+    store
+        .open(short.path(), "wave")
+        .await
+        .expect("Should read header");
+
+    let waited = waiter.await.expect("Waiter should finish");
+    assert!(
+        matches!(waited, Ok(50_000) | Ok(1)),
+        "Unexpected waveform: {:?}",
+        waited
+    );
+
+    let entry = store
+        .get_loaded("wave")
+        .await
+        .expect("Should find waveform");
+    let mut entry = entry.write().await;
+    let entry = &mut *entry;
+    let waveform = entry.body.waveform().expect("Should be loaded");
+    assert_eq!(waveform.time_table().len(), 1);
+    assert_eq!(
+        list_signals_with_index(waveform.hierarchy(), &entry.paths, None, None, true, None),
+        vec!["top.valid", "top.data"]
+    );
+}
+
+#[tokio::test]
+async fn test_waveforms_locked_separately() {
+    let first = write_vcd(&long_vcd(10));
+    let second = write_vcd(&long_vcd(20));
+    let store = WaveformStore::default();
+    store.open(first.path(), "first").await.unwrap();
+    store.open(second.path(), "second").await.unwrap();
+
+    // A long search holds one waveform, other waveforms can still be used and loaded
+    let first = store.get_loaded("first").await.unwrap();
+    let _search = first.write().await;
+    let second = tokio::time::timeout(Duration::from_secs(60), async {
+        let entry = store.get_loaded("second").await.unwrap();
+        let mut entry = entry.write().await;
+        entry
+            .body
+            .waveform()
+            .map(|waveform| waveform.time_table().len())
+    })
+    .await
+    .expect("Should not wait for the other waveform");
+    assert_eq!(second, Ok(20));
+}